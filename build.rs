@@ -1 +1,27 @@
-fn main() {}
+use std::process::Command;
+
+fn main() {
+    // Commit the crate was built from, for `ServerInfo.git_hash`. Left unset
+    // outside a git checkout (e.g. a crates.io tarball).
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/packed-refs");
+    // A new commit moves the branch ref, not HEAD itself.
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD") {
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            println!("cargo:rerun-if-changed=.git/{}", reference);
+        }
+    }
+    println!("cargo:rerun-if-env-changed=JEEVES_GIT_HASH");
+    if std::env::var_os("JEEVES_GIT_HASH").is_some() {
+        return;
+    }
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok());
+    if let Some(hash) = hash.map(|h| h.trim().to_string()).filter(|h| !h.is_empty()) {
+        println!("cargo:rustc-env=JEEVES_GIT_HASH={}", hash);
+    }
+}
@@ -136,6 +136,7 @@ fn merge_agents(
                 temperature: stage.agent_config.temperature,
                 max_tokens: stage.agent_config.max_tokens,
                 model: stage.agent_config.model_role.clone(),
+                model_fallbacks: stage.agent_config.model_fallbacks.clone(),
                 max_tool_rounds: crate::agent::DEFAULT_MAX_TOOL_ROUNDS,
                 content_resolver: ctx.content_resolver.clone(),
                 hooks: ctx.hooks.clone(),
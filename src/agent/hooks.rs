@@ -230,6 +230,7 @@ mod tests {
             outputs: HashMap::new(),
             state: HashMap::new(),
             metadata: HashMap::new(),
+            inputs: HashMap::new(),
             event_tx: None,
             stage_name: None,
             workflow_name: Arc::from("test"),
@@ -274,6 +275,7 @@ mod tests {
             outputs: HashMap::new(),
             state: HashMap::new(),
             metadata: HashMap::new(),
+            inputs: HashMap::new(),
             event_tx: None,
             stage_name: None,
             workflow_name: Arc::from("t"),
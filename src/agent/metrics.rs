@@ -23,4 +23,8 @@ pub struct AgentExecutionMetrics {
     pub duration_ms: i64,
     #[serde(default)]
     pub tool_results: Vec<ToolCallResult>,
+    /// Model requested for the last LLM call, after any fallback. `None`
+    /// for non-LLM agents or when the provider default was used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
 }
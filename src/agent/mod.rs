@@ -44,6 +44,8 @@ pub struct AgentContext {
     /// State fields preserved across pipeline loop-backs (`state_schema`).
     pub state: HashMap<String, serde_json::Value>,
     pub metadata: HashMap<String, serde_json::Value>,
+    /// Stage `inputs` resolved by the kernel: `name → value`.
+    pub inputs: HashMap<String, serde_json::Value>,
     /// `None` = buffered execution; `Some` = stream events back to consumer.
     pub event_tx: Option<mpsc::Sender<RunEvent>>,
     pub stage_name: Option<String>,
@@ -76,6 +78,9 @@ pub struct LlmAgent {
     pub temperature: Option<f64>,
     pub max_tokens: Option<i32>,
     pub model: Option<String>,
+    /// Models tried in order when the provider rejects a call on the
+    /// current one. Once the agent falls back it stays on that model.
+    pub model_fallbacks: Vec<String>,
     pub max_tool_rounds: u32,
     pub content_resolver: Option<Arc<dyn ContentResolver>>,
     /// Hooks run in registration order; the first non-`Continue` decision wins.
@@ -93,6 +98,7 @@ impl Default for LlmAgent {
             temperature: None,
             max_tokens: None,
             model: None,
+            model_fallbacks: Vec::new(),
             max_tool_rounds: 10,
             content_resolver: None,
             hooks: Vec::new(),
@@ -119,6 +125,9 @@ impl Agent for LlmAgent {
         for (key, value) in &ctx.metadata {
             vars.insert(key.clone(), value_to_string(value));
         }
+        for (key, value) in &ctx.inputs {
+            vars.insert(key.clone(), value_to_string(value));
+        }
 
         let prompt_text = self
             .prompts
@@ -138,6 +147,8 @@ impl Agent for LlmAgent {
         let mut total_tokens_out = 0i64;
         let mut last_response = None;
         let mut tool_results: Vec<ToolCallResult> = Vec::new();
+        let mut model = self.model.clone();
+        let mut fallbacks = self.model_fallbacks.iter();
 
         for _round in 0..self.max_tool_rounds {
             if let Some(max_tokens) = ctx.max_context_tokens {
@@ -173,6 +184,7 @@ impl Agent for LlmAgent {
                                     tokens_out: Some(total_tokens_out),
                                     duration_ms: start.elapsed().as_millis() as i64,
                                     tool_results: tool_results.clone(),
+                                    model: model.clone(),
                                 },
                                 success: false,
                                 error_message: format!(
@@ -190,21 +202,31 @@ impl Agent for LlmAgent {
                 hook.before_llm_call(&mut messages).await;
             }
 
-            let req = ChatRequest {
+            let mut req = ChatRequest {
                 messages: messages.clone(),
                 temperature: self.temperature,
                 max_tokens: self.max_tokens,
-                model: self.model.clone(),
+                model: model.clone(),
                 tools: if tool_defs.is_empty() { None } else { Some(tool_defs.clone()) },
                 response_format: ctx.response_format.clone(),
             };
 
-            let mut resp = match self.llm.chat_stream(&req).await {
-                Ok(stream) => match collect_stream(stream, ctx.event_tx.as_ref(), ctx.stage_name.as_deref(), ctx.workflow_name.clone()).await {
-                    Ok(resp) => resp,
-                    Err(e) => return Ok(make_error_output(e, start, total_llm_calls + 1)),
-                },
-                Err(e) => return Ok(make_error_output(e, start, total_llm_calls + 1)),
+            let stream = loop {
+                match self.llm.chat_stream(&req).await {
+                    Ok(stream) => break stream,
+                    Err(e) => match fallbacks.next() {
+                        Some(next) => {
+                            tracing::warn!(failed = ?model, fallback = %next, error = %e, "llm_model_fallback");
+                            model = Some(next.clone());
+                            req.model = model.clone();
+                        }
+                        None => return Ok(make_error_output(e, start, total_llm_calls + 1, model)),
+                    },
+                }
+            };
+            let mut resp = match collect_stream(stream, ctx.event_tx.as_ref(), ctx.stage_name.as_deref(), ctx.workflow_name.clone()).await {
+                Ok(resp) => resp,
+                Err(e) => return Ok(make_error_output(e, start, total_llm_calls + 1, model)),
             };
 
             for hook in &self.hooks {
@@ -232,7 +254,8 @@ impl Agent for LlmAgent {
                 if ctx.interrupt_response.is_none() {
                     if let Some(confirmation) = self.tools.requires_confirmation(&tc.name, &tc.arguments) {
                         let mut interrupt = crate::run::FlowInterrupt::new()
-                            .with_message(confirmation.message.clone());
+                            .with_message(confirmation.message.clone())
+                            .with_response_spec(crate::run::ResponseSpec { require_approved: true, ..Default::default() });
                         if let Some(data) = confirmation.action_data {
                             interrupt = interrupt.with_data(HashMap::from([("action_data".to_string(), data)]));
                         }
@@ -250,6 +273,7 @@ impl Agent for LlmAgent {
                                 tokens_out: Some(total_tokens_out),
                                 duration_ms: start.elapsed().as_millis() as i64,
                                 tool_results: tool_results.clone(),
+                                model: model.clone(),
                             },
                             success: true,
                             error_message: String::new(),
@@ -383,6 +407,7 @@ impl Agent for LlmAgent {
             tokens_out: Some(total_tokens_out),
             duration_ms: duration.as_millis() as i64,
             tool_results,
+            model,
         };
 
         let output = match last_response {
@@ -461,7 +486,8 @@ impl Agent for ToolDelegatingAgent {
         if ctx.interrupt_response.is_none() {
             if let Some(confirmation) = self.tools.requires_confirmation(self.tool_name.as_str(), &params) {
                 let mut interrupt = crate::run::FlowInterrupt::new()
-                    .with_message(confirmation.message.clone());
+                    .with_message(confirmation.message.clone())
+                    .with_response_spec(crate::run::ResponseSpec { require_approved: true, ..Default::default() });
                 if let Some(data) = confirmation.action_data {
                     interrupt = interrupt.with_data(HashMap::from([("action_data".to_string(), data)]));
                 }
@@ -478,6 +504,7 @@ impl Agent for ToolDelegatingAgent {
                         tokens_out: None,
                         duration_ms: 0,
                         tool_results: vec![],
+                        model: None,
                     },
                     success: true,
                     error_message: String::new(),
@@ -529,6 +556,7 @@ impl Agent for ToolDelegatingAgent {
                     latency_ms: duration_ms as u64,
                     error_type: if error_message.is_empty() { None } else { Some(error_message.clone()) },
                 }],
+                model: None,
             },
             success,
             error_message,
@@ -554,6 +582,7 @@ impl Agent for DeterministicAgent {
                 tokens_out: None,
                 duration_ms: 0,
                 tool_results: vec![],
+                model: None,
             },
             success: true,
             error_message: String::new(),
@@ -657,6 +686,7 @@ fn make_error_output(
     e: crate::types::Error,
     start: Instant,
     llm_calls: i32,
+    model: Option<String>,
 ) -> AgentOutput {
     AgentOutput {
         output: serde_json::json!({"error": e.to_string()}),
@@ -667,6 +697,7 @@ fn make_error_output(
             tokens_out: None,
             duration_ms: start.elapsed().as_millis() as i64,
             tool_results: vec![],
+            model,
         },
         success: false,
         error_message: e.to_string(),
@@ -689,6 +720,7 @@ mod tests {
             outputs: HashMap::new(),
             state: HashMap::new(),
             metadata: HashMap::new(),
+            inputs: HashMap::new(),
             event_tx: None,
             stage_name: Some("test_stage".to_string()),
             workflow_name: Arc::from("test"),
@@ -714,6 +746,7 @@ mod tests {
             temperature: None,
             max_tokens: None,
             model: None,
+            model_fallbacks: Vec::new(),
             max_tool_rounds: 10,
             content_resolver: None,
             hooks: Vec::new(),
@@ -726,6 +759,46 @@ mod tests {
         assert_eq!(result.metrics.llm_calls, 1);
     }
 
+    #[tokio::test]
+    async fn test_model_fallback_after_provider_error() {
+        use crate::agent::llm::{ChatRequest, ChatResponse, StreamChunk};
+
+        /// Rejects every call on the "primary" model.
+        #[derive(Debug)]
+        struct PrimaryDown(MockLlmProvider);
+        #[async_trait]
+        impl LlmProvider for PrimaryDown {
+            async fn chat(&self, req: &ChatRequest) -> crate::types::Result<ChatResponse> {
+                self.0.chat(req).await
+            }
+            async fn chat_stream(
+                &self,
+                req: &ChatRequest,
+            ) -> crate::types::Result<std::pin::Pin<Box<dyn futures::stream::Stream<Item = crate::types::Result<StreamChunk>> + Send>>> {
+                if req.model.as_deref() == Some("primary") {
+                    return Err(crate::types::Error::internal("primary overloaded"));
+                }
+                self.0.chat_stream(req).await
+            }
+        }
+
+        let mut agent = LlmAgent {
+            llm: Arc::new(PrimaryDown(MockLlmProvider::new(r#"{"response":"ok"}"#))),
+            model: Some("primary".into()),
+            model_fallbacks: vec!["primary".into(), "secondary".into()],
+            ..LlmAgent::default()
+        };
+        let ctx = ctx_with_overflow("hi", 10_000, ContextOverflow::Fail);
+        let result = agent.process(&ctx).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.metrics.model.as_deref(), Some("secondary"));
+
+        agent.model_fallbacks.clear();
+        let result = agent.process(&ctx).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.metrics.model.as_deref(), Some("primary"));
+    }
+
     #[tokio::test]
     async fn test_truncate_oldest_drops_intermediate_messages_in_tool_loop() {
         // Tool result is large enough that the second ReAct round triggers
@@ -780,6 +853,7 @@ mod tests {
             temperature: None,
             max_tokens: None,
             model: None,
+            model_fallbacks: Vec::new(),
             max_tool_rounds: 5,
             content_resolver: None,
             hooks: Vec::new(),
@@ -805,6 +879,7 @@ mod tests {
             temperature: None,
             max_tokens: None,
             model: None,
+            model_fallbacks: Vec::new(),
             max_tool_rounds: 10,
             content_resolver: None,
             hooks: Vec::new(),
@@ -832,6 +907,7 @@ mod tests {
             temperature: None,
             max_tokens: None,
             model: None,
+            model_fallbacks: Vec::new(),
             max_tool_rounds: 10,
             content_resolver: None,
             hooks: Vec::new(),
@@ -842,6 +918,7 @@ mod tests {
             outputs: HashMap::new(),
             state: HashMap::new(),
             metadata: HashMap::new(),
+            inputs: HashMap::new(),
             event_tx: None,
             stage_name: Some("test".to_string()),
             workflow_name: Arc::from("test"),
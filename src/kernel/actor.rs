@@ -14,8 +14,9 @@ use tokio_util::sync::CancellationToken;
 /// Spawn the kernel actor as a tokio task. Returns a cloneable handle.
 pub fn spawn(kernel: Kernel, cancel: CancellationToken) -> KernelHandle {
     let (tx, rx) = mpsc::channel(256);
+    let clock = kernel.clock.clone();
     tokio::spawn(run_kernel_actor(kernel, rx, cancel).instrument(tracing::Span::current()));
-    KernelHandle::new(tx)
+    KernelHandle::new(tx, clock)
 }
 
 /// The kernel actor loop. Processes commands sequentially (single &mut).
@@ -25,6 +26,8 @@ async fn run_kernel_actor(
     cancel: CancellationToken,
 ) {
     tracing::info!("Kernel actor started");
+    // State handed in by the host may not be consistent; see `integrity`.
+    kernel.reconcile();
     loop {
         tokio::select! {
             _ = cancel.cancelled() => {
@@ -36,7 +39,10 @@ async fn run_kernel_actor(
                     tracing::info!("Kernel actor channel closed");
                     break;
                 };
+                let name = cmd.name();
+                let started = std::time::Instant::now();
                 dispatch(&mut kernel, cmd).await;
+                kernel.perf.record(name, started.elapsed());
             }
         }
     }
@@ -53,22 +59,20 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
             force,
             resp_tx,
         } => {
-            // Auto-create a run record if not already registered.
-            if kernel.lifecycle.get(&run_id).is_none() {
-                let _ = kernel.create_run(
-                    run_id.clone(),
-                    run.identity.request_id.clone(),
-                    run.identity.user_id.clone(),
-                    run.identity.session_id.clone(),
-                    None,
-                );
-            }
-            let result = kernel.initialize_orchestration(
-                run_id.clone(),
-                *workflow,
-                *run,
-                force,
-            );
+            let result = kernel
+                .initialize_run(run_id, *workflow, *run, force, None)
+                .map(|(_, state)| state);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::StartRun {
+            run_id,
+            workflow,
+            run,
+            quota,
+            resp_tx,
+        } => {
+            let result = kernel.initialize_run(run_id, *workflow, *run, false, quota);
             let _ = resp_tx.send(result);
         }
 
@@ -95,6 +99,7 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
             success,
             error_message,
             break_loop,
+            dispatch_id,
             resp_tx,
         } => {
             let result = kernel.process_agent_result(
@@ -106,10 +111,16 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
                 success,
                 &error_message,
                 break_loop,
+                dispatch_id.as_deref(),
             );
             let _ = resp_tx.send(result);
         }
 
+        KernelCommand::ReportHeartbeat { run_id, dispatch_id, progress, resp_tx } => {
+            let result = kernel.report_heartbeat(&run_id, &dispatch_id, progress);
+            let _ = resp_tx.send(result);
+        }
+
         KernelCommand::GetSessionState {
             run_id,
             resp_tx,
@@ -135,6 +146,14 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
             let _ = resp_tx.send(result);
         }
 
+        KernelCommand::RequestCancel { run_id, reason, ack_tx, resp_tx } => {
+            let _ = resp_tx.send(kernel.request_cancel(&run_id, &reason, Some(ack_tx)));
+        }
+
+        KernelCommand::FinishCancel { run_id, resp_tx } => {
+            let _ = resp_tx.send(kernel.finish_cancel(&run_id));
+        }
+
         KernelCommand::TerminateRun {
             run_id,
             resp_tx,
@@ -148,6 +167,67 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
             let _ = resp_tx.send(status);
         }
 
+        KernelCommand::RunMaintenance { resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.run_maintenance()));
+        }
+
+        KernelCommand::RunDiagnostics { resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.run_diagnostics()));
+        }
+
+        KernelCommand::ValidatePipeline { workflow, resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.validate_pipeline(&workflow)));
+        }
+
+        KernelCommand::FindStuckRuns { thresholds, resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.find_stuck_runs(&thresholds)));
+        }
+
+        KernelCommand::SearchRuns { query, resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.search_runs(&query)));
+        }
+
+        KernelCommand::UpdateRun { run_id, caller, updates, base_revisions, expected_revision, resp_tx } => {
+            let _ = resp_tx.send(kernel.update_run(&run_id, &caller, updates, &base_revisions, expected_revision));
+        }
+
+        KernelCommand::AnnotateRuns { query, caller, patch, dry_run, resp_tx } => {
+            let _ = resp_tx.send(kernel.annotate_runs(&query, &caller, patch, dry_run));
+        }
+
+        KernelCommand::SetAgentQuarantine { agent, reason, resp_tx } => {
+            let _ = resp_tx.send(kernel.set_agent_quarantine(&agent, reason));
+        }
+
+        KernelCommand::ListQuarantinedAgents { resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.quarantine.list()));
+        }
+
+        KernelCommand::GetResolutionStats { resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.resolution_stats()));
+        }
+
+        KernelCommand::GetInterruptStats { resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.interrupts.stats()));
+        }
+
+        KernelCommand::GetPostProcessorStats { resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.post_processor_stats()));
+        }
+
+        KernelCommand::GetTerminalRecords { user_id, limit, resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.terminal_records(user_id.as_deref(), limit)));
+        }
+
+        KernelCommand::GetTerminalRecordsSince { after_seq, limit, resp_tx } => {
+            let _ = resp_tx.send(Ok(kernel.terminal_records_since(after_seq, limit)));
+        }
+
+        KernelCommand::WaitForInterrupt { run_id, resp_tx } => {
+            // Reply is sent when the run raises an interrupt, possibly later.
+            kernel.wait_for_interrupt(&run_id, resp_tx);
+        }
+
         KernelCommand::ResolveInterrupt {
             run_id,
             interrupt_id,
@@ -158,12 +238,44 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
             let _ = resp_tx.send(result);
         }
 
+        KernelCommand::DelegateInterrupt { run_id, interrupt_id, to_user, note, resp_tx } => {
+            let _ = resp_tx.send(kernel.delegate_interrupt(&run_id, &interrupt_id, &to_user, note));
+        }
+
         KernelCommand::SetRunInterrupt {
             run_id,
             interrupt,
             resp_tx,
         } => {
-            let result = kernel.set_run_interrupt(&run_id, interrupt);
+            let result = kernel.set_run_interrupt(&run_id, *interrupt);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::SetWorkflowConcurrencyLimit { workflow_name, limit, resp_tx } => {
+            let result = kernel.set_workflow_concurrency_limit(&workflow_name, limit);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::GetKernelPerf { resp_tx } => {
+            let _ = resp_tx.send(kernel.perf.report());
+        }
+
+        KernelCommand::GetServerInfo { resp_tx } => {
+            let _ = resp_tx.send(kernel.server_info());
+        }
+
+        KernelCommand::SetRunExtension { run_id, key, extension, resp_tx } => {
+            let result = kernel.set_run_extension(&run_id, &key, extension);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::SignalRun {
+            run_id,
+            signal_name,
+            payload,
+            resp_tx,
+        } => {
+            let result = kernel.signal_run(&run_id, &signal_name, payload);
             let _ = resp_tx.send(result);
         }
 
@@ -178,6 +290,26 @@ async fn dispatch(kernel: &mut Kernel, cmd: KernelCommand) {
             let _ = resp_tx.send(result);
         }
 
+        KernelCommand::TransferQuota { from, to, amounts, resp_tx } => {
+            let result = kernel.transfer_quota(&from, &to, amounts);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::DefineStageGroup { name, capacity, resp_tx } => {
+            let result = kernel.define_stage_group(&name, capacity);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::GetStageGroupStats { name, resp_tx } => {
+            let result = kernel.get_stage_group_stats(&name);
+            let _ = resp_tx.send(result);
+        }
+
+        KernelCommand::RecommendQuota { workflow_name, headroom, resp_tx } => {
+            let result = kernel.recommend_quota(&workflow_name, headroom);
+            let _ = resp_tx.send(result);
+        }
+
         KernelCommand::RegisterRoutingFn { name, routing_fn, resp_tx } => {
             kernel.register_routing_fn(name, routing_fn);
             let _ = resp_tx.send(());
@@ -0,0 +1,388 @@
+//! Stage-to-agent bindings.
+//!
+//! `Config.agents` declares, statically, the agents this deployment runs
+//! and the semver versions of each. When it declares any,
+//! `initialize_orchestration` refuses a workflow whose stages name an agent
+//! it does not declare, or pin an `agent_version` no declared version
+//! matches. With nothing declared, only stages that pin an `agent_version`
+//! are refused. It lists every such stage at once instead of failing at the
+//! first dispatch. `validate_pipeline` reports the same as
+//! `undeclared_agent` diagnostics. Workers do not register with the kernel;
+//! keeping the declaration in step with what is deployed is the host's job.
+//!
+//! At dispatch the kernel resolves each stage's agent to the highest
+//! declared version matching its `agent_version` requirement, sends it as
+//! `agent_version` on `RunAgent` and records it on the stage's
+//! `ProcessingRecord`. If the declarations have changed since the run
+//! started and no declared version matches a pinned stage any more, the
+//! run gets `WaitAgent` until one does. An [`AgentRollout`] holds matching
+//! versions back from all but a stable percentage of sessions.
+
+use serde::{Deserialize, Serialize};
+use semver::{Version, VersionReq};
+use std::collections::HashMap;
+
+use super::interrupts::DEFAULT_POLL_MS;
+use super::protocol::Instruction;
+use super::Kernel;
+use crate::types::{AgentName, Error, Result, RunId};
+use crate::workflow::{Stage, Workflow};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentBindings {
+    /// Agents the deployment runs, with the semver versions available.
+    /// Empty = no binding check.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub served: HashMap<AgentName, Vec<String>>,
+    /// Staged rollouts, by agent name. An agent's rollouts take consecutive
+    /// shares of its sessions, so their percents sum to at most 100.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rollouts: HashMap<AgentName, Vec<AgentRollout>>,
+    /// `rollouts` with their versions parsed, filled in as the bindings are
+    /// installed on a kernel.
+    #[serde(skip)]
+    parsed_rollouts: HashMap<AgentName, Vec<(VersionReq, u8)>>,
+}
+
+/// Limit versions matching `version` (e.g. `^3`) to `percent` of sessions.
+/// Other sessions get the best version outside it, or it anyway when
+/// nothing else is declared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRollout {
+    pub version: String,
+    /// 0–100.
+    pub percent: u8,
+}
+
+impl AgentRollout {
+    fn validate(&self) -> Result<VersionReq> {
+        if self.percent > 100 {
+            return Err(Error::validation(format!("rollout percent must be 0-100, got {}", self.percent)));
+        }
+        VersionReq::parse(&self.version)
+            .map_err(|e| Error::validation(format!("invalid rollout version '{}': {}", self.version, e)))
+    }
+}
+
+/// The session's rollout bucket, 0–99. FNV-1a 64 so that it is stable
+/// across builds and processes, and a session keeps its version across
+/// stages and restarts.
+fn rollout_bucket(agent: &str, session_id: &str) -> u64 {
+    let bytes = agent.bytes().chain(std::iter::once(0)).chain(session_id.bytes());
+    let hash = bytes.fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash % 100
+}
+
+impl AgentBindings {
+    /// Every declared version must be semver, and every rollout must name
+    /// a declared version of its agent.
+    pub fn validate(&self) -> Result<()> {
+        self.parse_rollouts().map(|_| ())
+    }
+
+    /// Validate and fill in `parsed_rollouts`, ready to install.
+    fn prepared(mut self) -> Result<Self> {
+        self.parsed_rollouts = self.parse_rollouts()?;
+        Ok(self)
+    }
+
+    fn parse_rollouts(&self) -> Result<HashMap<AgentName, Vec<(VersionReq, u8)>>> {
+        for (agent, versions) in &self.served {
+            if agent.is_empty() {
+                return Err(Error::validation("declared agent names must be non-empty"));
+            }
+            if let Some(bad) = versions.iter().find(|v| Version::parse(v).is_err()) {
+                return Err(Error::validation(format!("agent '{}' declares version '{}', which is not semver", agent, bad)));
+            }
+        }
+        self.rollouts.iter()
+            .map(|(agent, rollouts)| Ok((agent.clone(), self.validate_rollouts(agent.as_str(), rollouts)?)))
+            .collect()
+    }
+
+    /// Percents sum to at most 100, and each rollout's version matches a
+    /// declared version of `agent`. Returns the parsed rollouts.
+    fn validate_rollouts(&self, agent: &str, rollouts: &[AgentRollout]) -> Result<Vec<(VersionReq, u8)>> {
+        let total: u32 = rollouts.iter().map(|r| u32::from(r.percent)).sum();
+        if total > 100 {
+            return Err(Error::validation(format!("rollouts of agent '{}' cover {}% of sessions, over 100", agent, total)));
+        }
+        rollouts.iter()
+            .map(|rollout| {
+                let requirement = rollout.validate()?;
+                if !self.is_served(agent, Some(&requirement)) {
+                    return Err(Error::validation(format!(
+                        "rollout '{}' of agent '{}' matches no declared version", rollout.version, agent
+                    )));
+                }
+                Ok((requirement, rollout.percent))
+            })
+            .collect()
+    }
+
+    /// Declared versions of `agent` matching `requirement`, ascending.
+    fn versions(&self, agent: &str, requirement: Option<&VersionReq>) -> Vec<Version> {
+        let mut versions: Vec<Version> = self.served.get(agent).into_iter().flatten()
+            .filter_map(|v| Version::parse(v).ok())
+            .filter(|v| requirement.map_or(true, |r| r.matches(v)))
+            .collect();
+        versions.sort();
+        versions.dedup();
+        versions
+    }
+
+    /// Whether `agent` is declared at a version matching `requirement`.
+    pub fn is_served(&self, agent: &str, requirement: Option<&VersionReq>) -> bool {
+        !self.versions(agent, requirement).is_empty()
+    }
+
+    /// Best declared version of `agent` for `session_id`, applying its
+    /// rollouts. The session's bucket falls in at most one rollout's share;
+    /// versions only the other rollouts match are held back from it.
+    pub(crate) fn resolve(&self, agent: &str, requirement: Option<&VersionReq>, session_id: &str) -> Option<Version> {
+        let mut versions = self.versions(agent, requirement);
+        let rollouts = self.parsed_rollouts.get(agent).map(Vec::as_slice).unwrap_or_default();
+        if !rollouts.is_empty() {
+            let bucket = rollout_bucket(agent, session_id);
+            let mut start = 0;
+            let mut own = None;
+            let mut held = Vec::new();
+            for (req, percent) in rollouts {
+                let end = start + u64::from(*percent);
+                if (start..end).contains(&bucket) {
+                    own = Some(req);
+                } else {
+                    held.push(req);
+                }
+                start = end;
+            }
+            let allowed = |v: &Version| {
+                own.is_some_and(|r| r.matches(v)) || !held.iter().any(|r| r.matches(v))
+            };
+            if versions.iter().any(allowed) {
+                versions.retain(allowed);
+            }
+        }
+        versions.pop()
+    }
+}
+
+impl Kernel {
+    /// Install the declared agents and rollouts.
+    pub fn set_agent_bindings(&mut self, bindings: AgentBindings) -> Result<()> {
+        self.agents = bindings.prepared()?;
+        Ok(())
+    }
+
+    /// Replace the staged rollouts of `agent`. Empty ends them.
+    pub fn set_agent_rollout(&mut self, agent: &str, rollouts: Vec<AgentRollout>) -> Result<()> {
+        if rollouts.is_empty() {
+            self.agents.rollouts.remove(agent);
+            self.agents.parsed_rollouts.remove(agent);
+            return Ok(());
+        }
+        let parsed = self.agents.validate_rollouts(agent, &rollouts)?;
+        self.agents.rollouts.insert(agent.into(), rollouts);
+        self.agents.parsed_rollouts.insert(agent.into(), parsed);
+        Ok(())
+    }
+
+    /// Stages of `workflow` whose agent is not declared at a version
+    /// matching the stage's `agent_version`. When nothing is declared, only
+    /// stages that pin a version are listed, since no version can ever
+    /// match them.
+    pub(crate) fn undeclared_stages<'a>(&self, workflow: &'a Workflow) -> Vec<&'a Stage> {
+        if self.agents.served.is_empty() {
+            return workflow.stages.iter().filter(|s| s.agent_version.is_some()).collect();
+        }
+        workflow.stages.iter()
+            .filter(|s| {
+                let requirement = s.agent_version.as_deref().and_then(|r| VersionReq::parse(r).ok());
+                !self.agents.is_served(s.agent.as_str(), requirement.as_ref())
+            })
+            .collect()
+    }
+
+    /// The version to dispatch `agent` at for the run's current stage.
+    /// `Ok(None)` when the agent is not declared and the stage pins no
+    /// version, so deployments without declarations dispatch as before.
+    /// `Err(WaitAgent)` when the stage pins a version nothing declared
+    /// matches; initialization already refuses such stages, so this only
+    /// happens when the declarations change under a running run.
+    pub(crate) fn resolve_agent_version(&self, run_id: &RunId, agent: &str) -> Result<std::result::Result<Option<String>, Instruction>> {
+        let Some(run) = self.runs.get(run_id) else {
+            return Ok(Ok(None));
+        };
+        let requirement = self.orchestrator.get_stage_config(run_id, run.current_stage.as_str())
+            .and_then(|s| s.agent_version.as_deref())
+            .map(VersionReq::parse)
+            .transpose()
+            .map_err(|e| Error::validation(format!("invalid agent_version: {}", e)))?;
+        Ok(match self.agents.resolve(agent, requirement.as_ref(), run.identity.session_id.as_str()) {
+            Some(version) => Ok(Some(version.to_string())),
+            None => match requirement {
+                Some(requirement) => Err(Instruction::WaitAgent {
+                    agent: agent.to_string(),
+                    requirement: requirement.to_string(),
+                    poll_after_ms: DEFAULT_POLL_MS,
+                }),
+                None => Ok(None),
+            },
+        })
+    }
+
+    /// Reject workflows with stages bound to undeclared agents.
+    pub(crate) fn check_agent_bindings(&self, workflow: &Workflow) -> Result<()> {
+        let undeclared = self.undeclared_stages(workflow);
+        if undeclared.is_empty() {
+            return Ok(());
+        }
+        let missing: Vec<String> = undeclared.iter().map(|s| format!("{} (stage '{}')", s.agent, s.name)).collect();
+        Err(Error::validation(format!(
+            "Workflow '{}' names agents the deployment does not declare: {}",
+            workflow.name, missing.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+
+    fn served(agents: &[(&str, &[&str])]) -> AgentBindings {
+        AgentBindings {
+            served: agents.iter().map(|(name, versions)| ((*name).into(), versions.iter().map(|v| v.to_string()).collect())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn undeclared_agents_are_all_listed() {
+        let mut kernel = Kernel::new();
+        let workflow = test_helpers::create_test_workflow();
+        // Nothing declared: no check.
+        assert!(kernel.undeclared_stages(&workflow).is_empty());
+
+        kernel.set_agent_bindings(served(&[("summarizer", &["1.0.0"])])).unwrap();
+        let err = kernel.initialize_run(RunId::must("early"), workflow.clone(), test_helpers::create_test_run(), false, None).unwrap_err();
+        for stage in &workflow.stages {
+            assert!(err.to_string().contains(stage.agent.as_str()), "{err}");
+        }
+        assert!(kernel.runs.is_empty());
+
+        let all: Vec<(&str, &[&str])> = workflow.stages.iter().map(|s| (s.agent.as_str(), &["1.0.0"][..])).collect();
+        kernel.set_agent_bindings(served(&all)).unwrap();
+        assert!(kernel.initialize_run(RunId::must("served"), workflow, test_helpers::create_test_run(), false, None).is_ok());
+        assert!(kernel.set_agent_bindings(served(&[("x", &["latest"])])).is_err());
+    }
+
+    #[test]
+    fn dispatch_resolves_pinned_version_and_records_it() {
+        let mut kernel = Kernel::new();
+        kernel.set_agent_bindings(served(&[("agent1", &["1.4.0", "2.1.0", "3.0.0"]), ("agent2", &["1.0.0"])])).unwrap();
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].agent_version = Some("^2".into());
+        let run_id = RunId::must("pinned");
+        let _ = kernel.initialize_run(run_id.clone(), workflow, test_helpers::create_test_run(), false, None).unwrap();
+
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        assert_eq!(context.agent_version.as_deref(), Some("2.1.0"));
+        kernel.process_agent_result(&run_id, "agent1", serde_json::json!({}), None, Default::default(), true, "", false, context.dispatch_id.as_deref()).unwrap();
+        let record = &kernel.runs[&run_id].audit.processing_history[0];
+        assert_eq!(record.agent_version.as_deref(), Some("2.1.0"));
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        assert_eq!(context.agent_version.as_deref(), Some("1.0.0"));
+    }
+
+    #[test]
+    fn undeclared_deployments_dispatch_without_a_version() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("plain");
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        assert_eq!(context.agent_version, None);
+    }
+
+    #[test]
+    fn pinned_stage_is_refused_without_declarations() {
+        let mut kernel = Kernel::new();
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].agent_version = Some("^2".into());
+
+        let err = kernel.initialize_run(RunId::must("pinned"), workflow.clone(), test_helpers::create_test_run(), false, None).unwrap_err();
+        assert!(err.to_string().contains("agent1"), "{err}");
+        assert!(!err.to_string().contains("agent2"), "{err}");
+        assert!(kernel.runs.is_empty());
+        let report = kernel.validate_pipeline(&workflow);
+        assert!(report.diagnostics.iter().any(|d| d.code == "undeclared_agent"));
+    }
+
+    #[test]
+    fn rollout_limits_version_to_a_share_of_sessions() {
+        let mut kernel = Kernel::new();
+        kernel.set_agent_bindings(served(&[("agent1", &["2.0.0", "3.0.0"])])).unwrap();
+        let rollout = |version: &str, percent| AgentRollout { version: version.into(), percent };
+        assert!(kernel.set_agent_rollout("agent1", vec![rollout("^3", 101)]).is_err());
+        assert!(kernel.set_agent_rollout("agent1", vec![rollout("^3", 60), rollout("^2", 50)]).is_err());
+        assert!(kernel.set_agent_rollout("agent1", vec![rollout("^4", 10)]).is_err());
+        kernel.set_agent_rollout("agent1", vec![rollout("^3", 10)]).unwrap();
+
+        let on_v3 = (0..1000)
+            .filter(|i| kernel.agents.resolve("agent1", None, &format!("sess_{i}")).unwrap().major == 3)
+            .count();
+        assert!((50..150).contains(&on_v3), "{on_v3} of 1000 sessions on v3");
+        // A session's version is stable.
+        let first = kernel.agents.resolve("agent1", None, "sess_7");
+        assert_eq!(kernel.agents.resolve("agent1", None, "sess_7"), first);
+
+        kernel.set_agent_rollout("agent1", Vec::new()).unwrap();
+        assert_eq!(kernel.agents.resolve("agent1", None, "sess_7").unwrap().major, 3);
+
+        // Config is held to the same rules, and its rollouts apply.
+        let mut bindings = served(&[("agent1", &["2.0.0"])]);
+        bindings.rollouts.insert("agent1".into(), vec![rollout("^3", 10)]);
+        assert!(bindings.validate().is_err());
+        let config = crate::Config { agents: bindings, ..Default::default() };
+        assert!(Kernel::from_config(&config).is_err());
+
+        let mut bindings = served(&[("agent1", &["2.0.0", "3.0.0"])]);
+        bindings.rollouts.insert("agent1".into(), vec![rollout("^3", 0)]);
+        let kernel = Kernel::from_config(&crate::Config { agents: bindings, ..Default::default() }).unwrap();
+        assert_eq!(kernel.agents.resolve("agent1", None, "sess_7").unwrap().major, 2);
+    }
+
+    #[test]
+    fn unmatched_pin_waits_for_a_declared_version() {
+        let mut kernel = Kernel::new();
+        kernel.set_agent_bindings(served(&[("agent1", &["2.1.0"]), ("agent2", &["1.0.0"])])).unwrap();
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].agent_version = Some("^2".into());
+        let run_id = RunId::must("lapsed");
+        let _ = kernel.initialize_run(run_id.clone(), workflow, test_helpers::create_test_run(), false, None).unwrap();
+
+        // The declared version is withdrawn after the run started.
+        kernel.set_agent_bindings(served(&[("agent1", &["3.0.0"]), ("agent2", &["1.0.0"])])).unwrap();
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::WaitAgent { agent, requirement, .. } => {
+                assert_eq!(agent, "agent1");
+                assert_eq!(requirement, "^2");
+            }
+            other => panic!("expected WaitAgent, got {:?}", other),
+        }
+
+        kernel.set_agent_bindings(served(&[("agent1", &["2.2.0"]), ("agent2", &["1.0.0"])])).unwrap();
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        assert_eq!(context.agent_version.as_deref(), Some("2.2.0"));
+    }
+}
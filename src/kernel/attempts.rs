@@ -0,0 +1,316 @@
+//! Limits on failed interrupt resolutions.
+//!
+//! A client guessing answers can call `resolve_interrupt` in a loop. Each
+//! failed attempt on a pending interrupt — a response the interrupt's spec
+//! rejects, or (with `require_owner`) a responder who does not own the
+//! interrupt — is counted against that interrupt. Once an interrupt reaches
+//! the cap, every further attempt on it fails with `QuotaExceeded` without
+//! being checked; there is no window after which it reopens. The count goes
+//! away with the interrupt.
+//!
+//! Failures are not keyed on the responder: it is whatever the client puts
+//! in the response, so a cap on it could be dodged or aimed at someone
+//! else. Attempts on ids that are not pending are counted in the stats but
+//! tracked against nothing, so guessing cannot grow the guard's state.
+//! Failures on a live run are also appended to its
+//! `metadata["resolution_failures"]`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::Kernel;
+use crate::run::InterruptResponse;
+use crate::types::{Error, Result, RunId};
+
+/// Audit entries kept per run in `metadata["resolution_failures"]`.
+pub const MAX_AUDITED_FAILURES: usize = 50;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResolutionLimits {
+    /// Failures allowed against one interrupt. `None` = unlimited.
+    #[serde(default)]
+    pub max_failures_per_interrupt: Option<u32>,
+    /// Count a response whose `responder` is not the interrupt's current
+    /// owner as a failure. Responses without a `responder` are not checked.
+    #[serde(default)]
+    pub require_owner: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionFailure {
+    UnknownInterrupt,
+    WrongUser,
+    InvalidResponse,
+}
+
+/// Counters for security monitoring.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResolutionStats {
+    pub failures: HashMap<ResolutionFailure, u64>,
+    /// Interrupts that reached the cap.
+    pub capped: u64,
+    /// Attempts refused because the interrupt was at its cap.
+    pub rejected_at_cap: u64,
+    /// Pending interrupts at their cap right now.
+    pub capped_interrupts: usize,
+}
+
+impl ResolutionLimits {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_failures_per_interrupt == Some(0) {
+            return Err(Error::validation("resolution max_failures_per_interrupt must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Failure counts per pending interrupt id. Owned by `Kernel`.
+#[derive(Debug, Default)]
+pub struct ResolutionGuard {
+    pub(crate) limits: ResolutionLimits,
+    /// Failures per pending interrupt; entries for interrupts that are no
+    /// longer pending are dropped on the next attempt.
+    failures: HashMap<String, u32>,
+    failure_counts: HashMap<ResolutionFailure, u64>,
+    capped: u64,
+    rejected_at_cap: u64,
+}
+
+impl ResolutionGuard {
+    /// Guard enforcing `limits`, which must pass [`ResolutionLimits::validate`].
+    pub fn new(limits: ResolutionLimits) -> Result<Self> {
+        limits.validate()?;
+        Ok(Self { limits, ..Default::default() })
+    }
+
+    fn at_cap(&self, interrupt_id: &str) -> bool {
+        match (self.limits.max_failures_per_interrupt, self.failures.get(interrupt_id)) {
+            (Some(max), Some(count)) => *count >= max,
+            _ => false,
+        }
+    }
+
+    /// Refuse the attempt if the interrupt is at its cap.
+    fn admit(&mut self, interrupt_id: &str) -> Result<()> {
+        if !self.at_cap(interrupt_id) {
+            return Ok(());
+        }
+        self.rejected_at_cap += 1;
+        Err(Error::quota_exceeded(format!(
+            "Too many failed resolution attempts for interrupt {}", interrupt_id
+        )))
+    }
+
+    /// Count a failure against a pending interrupt. `tracked` is false for
+    /// ids that are not pending.
+    fn record(&mut self, interrupt_id: &str, failure: ResolutionFailure, tracked: bool) {
+        *self.failure_counts.entry(failure).or_default() += 1;
+        if !tracked {
+            return;
+        }
+        *self.failures.entry(interrupt_id.to_string()).or_default() += 1;
+        if self.at_cap(interrupt_id) {
+            tracing::warn!(interrupt_id, "resolution_cap_reached");
+            self.capped += 1;
+        }
+    }
+
+    pub fn stats(&self) -> ResolutionStats {
+        ResolutionStats {
+            failures: self.failure_counts.clone(),
+            capped: self.capped,
+            rejected_at_cap: self.rejected_at_cap,
+            capped_interrupts: self.failures.keys().filter(|id| self.at_cap(id)).count(),
+        }
+    }
+}
+
+impl Kernel {
+    /// Install limits on failed resolutions. Failure history is kept.
+    pub fn set_resolution_limits(&mut self, limits: ResolutionLimits) -> Result<()> {
+        limits.validate()?;
+        self.resolution_guard.limits = limits;
+        Ok(())
+    }
+
+    /// Run `attempt` (the resolution itself) unless the interrupt is at its
+    /// cap, and count it if it fails in a way that points at guessing or
+    /// misuse.
+    pub(crate) fn guard_resolution(
+        &mut self,
+        run_id: &RunId,
+        interrupt_id: &str,
+        response: &InterruptResponse,
+        attempt: impl FnOnce(&mut Kernel) -> Result<()>,
+    ) -> Result<()> {
+        let now = self.clock.now();
+        let owner = self.interrupts.get_pending(interrupt_id).map(|p| p.user_id.as_str().to_string());
+        let user = response.responder.clone()
+            .or_else(|| owner.clone())
+            .or_else(|| self.runs.get(run_id).map(|r| r.identity.user_id.as_str().to_string()))
+            .unwrap_or_default();
+        let interrupts = &self.interrupts;
+        self.resolution_guard.failures.retain(|id, _| interrupts.get_pending(id).is_some());
+        self.resolution_guard.admit(interrupt_id)?;
+
+        let wrong_user = self.resolution_guard.limits.require_owner
+            && matches!((&response.responder, &owner), (Some(r), Some(o)) if r != o);
+        let (failure, result) = if wrong_user {
+            (Some(ResolutionFailure::WrongUser), Err(Error::policy_violation(format!(
+                "{} does not own interrupt {}", user, interrupt_id
+            ))))
+        } else {
+            let known = owner.is_some();
+            let result = attempt(self);
+            let failure = match &result {
+                Err(_) if !known => Some(ResolutionFailure::UnknownInterrupt),
+                Err(Error::Validation { .. }) => Some(ResolutionFailure::InvalidResponse),
+                _ => None,
+            };
+            (failure, result)
+        };
+
+        if let Some(failure) = failure {
+            tracing::warn!(run_id = %run_id, interrupt_id, user = %user, failure = ?failure, "interrupt_resolution_failed");
+            self.resolution_guard.record(interrupt_id, failure, owner.is_some());
+            if let Some(run) = self.runs.edit(run_id) {
+                let entries = run.audit.metadata.entry("resolution_failures".to_string())
+                    .or_insert_with(|| serde_json::json!([]));
+                if let Some(list) = entries.as_array_mut() {
+                    list.push(serde_json::json!({
+                        "at": now,
+                        "interrupt_id": interrupt_id,
+                        "user": user,
+                        "failure": failure,
+                    }));
+                    if list.len() > MAX_AUDITED_FAILURES {
+                        list.remove(0);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub fn resolution_stats(&self) -> ResolutionStats {
+        self.resolution_guard.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+    use crate::kernel::ManualClock;
+    use crate::run::FlowInterrupt;
+    use chrono::Utc;
+
+    fn waiting(kernel: &mut Kernel, name: &str, interrupt: FlowInterrupt) -> (RunId, String) {
+        let run_id = RunId::must(name);
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+        let id = interrupt.id.as_str().to_string();
+        kernel.set_run_interrupt(&run_id, interrupt).unwrap();
+        (run_id, id)
+    }
+
+    fn answer(responder: &str) -> InterruptResponse {
+        InterruptResponse {
+            text: Some("ok".into()),
+            approved: None,
+            decision: None,
+            data: None,
+            responder: Some(responder.into()),
+            received_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn repeated_invalid_answers_cap_the_interrupt_for_good() {
+        let clock = std::sync::Arc::new(ManualClock::new(Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        kernel.set_resolution_limits(ResolutionLimits { max_failures_per_interrupt: Some(3), ..Default::default() }).unwrap();
+        let interrupt = FlowInterrupt::new().with_question("pin?".into())
+            .with_response_spec(crate::run::ResponseSpec { required_data: vec!["pin".into()], ..Default::default() });
+        let (run_id, interrupt_id) = waiting(&mut kernel, "guessed", interrupt);
+
+        for _ in 0..3 {
+            let err = kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer("mallory")).unwrap_err();
+            assert!(matches!(err, Error::Validation { .. }));
+        }
+        // Capped for every responder, whatever name the client sends.
+        let capped = kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer("alice")).unwrap_err();
+        assert!(matches!(capped, Error::QuotaExceeded(_)));
+
+        let stats = kernel.resolution_stats();
+        assert_eq!(stats.failures[&ResolutionFailure::InvalidResponse], 3);
+        assert_eq!((stats.capped, stats.rejected_at_cap, stats.capped_interrupts), (1, 1, 1));
+        let audit = &kernel.runs.get(&run_id).unwrap().audit.metadata["resolution_failures"];
+        assert_eq!(audit.as_array().unwrap().len(), 3);
+
+        // No window: time passing does not reopen it.
+        clock.advance(chrono::Duration::days(7));
+        let still = kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer("alice")).unwrap_err();
+        assert!(matches!(still, Error::QuotaExceeded(_)));
+        assert_eq!(kernel.resolution_stats().capped_interrupts, 1);
+    }
+
+    #[test]
+    fn guessed_ids_are_counted_but_tracked_against_nothing() {
+        let mut kernel = Kernel::new();
+        kernel.set_resolution_limits(ResolutionLimits { max_failures_per_interrupt: Some(1), ..Default::default() }).unwrap();
+        let (run_id, interrupt_id) = waiting(&mut kernel, "probed", FlowInterrupt::new().with_question("ship?".into()));
+
+        for n in 0..100 {
+            let err = kernel.resolve_run_interrupt(&run_id, &format!("guess-{}", n), answer("mallory")).unwrap_err();
+            assert!(matches!(err, Error::NotFound(_)));
+        }
+        assert_eq!(kernel.resolution_stats().failures[&ResolutionFailure::UnknownInterrupt], 100);
+        assert_eq!(kernel.resolution_stats().capped, 0);
+        assert!(kernel.resolution_guard.failures.is_empty());
+        kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer("mallory")).unwrap();
+    }
+
+    #[test]
+    fn counts_are_dropped_once_the_interrupt_is_gone() {
+        let mut kernel = Kernel::new();
+        kernel.set_resolution_limits(ResolutionLimits { max_failures_per_interrupt: Some(5), require_owner: true, ..Default::default() }).unwrap();
+        let (run_a, first) = waiting(&mut kernel, "first", FlowInterrupt::new().with_question("a?".into()));
+        let (run_b, second) = waiting(&mut kernel, "second", FlowInterrupt::new().with_question("b?".into()));
+        let owner = kernel.runs.get(&run_a).unwrap().identity.user_id.as_str().to_string();
+
+        assert!(kernel.resolve_run_interrupt(&run_a, &first, answer("eve")).is_err());
+        kernel.resolve_run_interrupt(&run_a, &first, answer(&owner)).unwrap();
+        assert!(kernel.resolve_run_interrupt(&run_b, &second, answer("eve")).is_err());
+        assert_eq!(kernel.resolution_guard.failures.len(), 1);
+        assert!(kernel.resolution_guard.failures.contains_key(&second));
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        assert!(ResolutionGuard::new(ResolutionLimits { max_failures_per_interrupt: Some(0), ..Default::default() }).is_err());
+        let mut config = crate::Config::default();
+        config.resolution_limits.max_failures_per_interrupt = Some(0);
+        assert!(Kernel::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn wrong_user_counts_against_the_interrupt() {
+        let mut kernel = Kernel::new();
+        kernel.set_resolution_limits(ResolutionLimits {
+            max_failures_per_interrupt: Some(2),
+            require_owner: true,
+            ..Default::default()
+        }).unwrap();
+        let (run_id, interrupt_id) = waiting(&mut kernel, "owned", FlowInterrupt::new().with_question("ship?".into()));
+        let owner = kernel.runs.get(&run_id).unwrap().identity.user_id.as_str().to_string();
+
+        assert!(kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer("eve")).is_err());
+        assert!(kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer("trent")).is_err());
+        let err = kernel.resolve_run_interrupt(&run_id, &interrupt_id, answer(&owner)).unwrap_err();
+        assert!(matches!(err, Error::QuotaExceeded(_)));
+        assert_eq!(kernel.resolution_stats().failures[&ResolutionFailure::WrongUser], 2);
+        assert_eq!(kernel.resolution_stats().capped_interrupts, 1);
+    }
+}
@@ -0,0 +1,217 @@
+//! Graceful cancellation.
+//!
+//! `KernelHandle::cancel_run` asks the kernel to cancel a run, then waits up
+//! to a grace period for its worker to acknowledge before the run is
+//! terminated with `UserCancelled`. Requesting a cancellation frees the
+//! run's stage-group permit at once. While it is pending, the next
+//! `get_next_instruction` answers `Terminate { UserCancelled }` with the
+//! reason. Either that poll or a final `process_agent_result` counts as
+//! the worker's acknowledgement and ends the wait early. The pending entry
+//! outlives the run: the actor terminates a run as soon as it hands out
+//! `Terminate`, and `finish_cancel` still reports that outcome. An entry
+//! nobody finishes, because the caller went away, is dropped by
+//! `cleanup_stale_sessions` once it is as old as a stale session.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use super::protocol::Instruction;
+use super::Kernel;
+use crate::run::TerminalReason;
+use crate::types::{Error, Result, RunId};
+
+/// A requested cancellation awaiting its grace period.
+#[derive(Debug)]
+pub struct PendingCancel {
+    pub reason: String,
+    pub requested_at: DateTime<Utc>,
+    pub acknowledged: bool,
+    /// Fired on acknowledgement to end the caller's grace wait.
+    ack_tx: Option<oneshot::Sender<()>>,
+}
+
+/// How a cancellation ended.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CancelOutcome {
+    pub run_id: RunId,
+    pub reason: String,
+    pub requested_at: DateTime<Utc>,
+    pub terminated_at: DateTime<Utc>,
+    /// Whether the worker saw the cancellation or sent a final result
+    /// within the grace period.
+    pub acknowledged: bool,
+}
+
+impl Kernel {
+    /// Start cancelling `run_id`: free its stage-group permit and make the
+    /// next instruction `Terminate { UserCancelled }`. `ack_tx` fires when
+    /// the worker acknowledges. Call [`Kernel::finish_cancel`] to terminate.
+    pub fn request_cancel(&mut self, run_id: &RunId, reason: &str, ack_tx: Option<oneshot::Sender<()>>) -> Result<()> {
+        let run = self.runs.get(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
+        if run.is_terminated() {
+            return Err(Error::validation(format!("Run {} is already terminated", run_id)));
+        }
+        if self.cancellations.contains_key(run_id) {
+            return Err(Error::validation(format!("Run {} is already being cancelled", run_id)));
+        }
+        let freed = self.stage_groups.forget(run_id);
+        tracing::info!(run_id = %run_id, reason, freed_permit = freed, "cancel_requested");
+        self.cancellations.insert(run_id.clone(), PendingCancel {
+            reason: reason.to_string(),
+            requested_at: self.clock.now(),
+            acknowledged: false,
+            ack_tx,
+        });
+        Ok(())
+    }
+
+    /// Note that the worker for `run_id` has seen its cancellation.
+    pub(crate) fn acknowledge_cancel(&mut self, run_id: &RunId) {
+        if let Some(pending) = self.cancellations.get_mut(run_id) {
+            pending.acknowledged = true;
+            if let Some(tx) = pending.ack_tx.take() {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    /// The cancellation instruction for `run_id`, if one is pending. Marks
+    /// the run terminated and the cancellation acknowledged.
+    pub(crate) fn cancel_instruction(&mut self, run_id: &RunId) -> Option<Instruction> {
+        let reason = self.cancellations.get(run_id)?.reason.clone();
+        self.acknowledge_cancel(run_id);
+        if let Some(run) = self.runs.edit(run_id) {
+            run.terminate_with(TerminalReason::UserCancelled, Some(reason.clone()));
+        }
+        Some(Instruction::terminate(TerminalReason::UserCancelled, reason))
+    }
+
+    /// Terminate a run whose cancellation was requested, recording the
+    /// reason and acknowledgement in `metadata["cancellation"]` first. A
+    /// run the acknowledging poll already terminated is reported from its
+    /// terminal record.
+    pub fn finish_cancel(&mut self, run_id: &RunId) -> Result<CancelOutcome> {
+        let pending = self.cancellations.remove(run_id)
+            .ok_or_else(|| Error::not_found(format!("No cancellation pending for run {}", run_id)))?;
+        let terminated_at = match self.runs.edit(run_id) {
+            Some(run) => {
+                run.terminate_with(TerminalReason::UserCancelled, Some(pending.reason.clone()));
+                run.audit.metadata.insert("cancellation".to_string(), serde_json::json!({
+                    "reason": pending.reason,
+                    "requested_at": pending.requested_at,
+                    "acknowledged": pending.acknowledged,
+                }));
+                self.terminate_run(run_id)?;
+                self.clock.now()
+            }
+            None => self.terminal_log.get(run_id).map_or_else(|| self.clock.now(), |r| r.terminated_at),
+        };
+        tracing::info!(run_id = %run_id, acknowledged = pending.acknowledged, "run_cancelled");
+        Ok(CancelOutcome {
+            run_id: run_id.clone(),
+            reason: pending.reason,
+            requested_at: pending.requested_at,
+            terminated_at,
+            acknowledged: pending.acknowledged,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+
+    fn started(kernel: &mut Kernel, name: &str) -> RunId {
+        let run_id = RunId::must(name);
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+        let _ = kernel.get_next_instruction(&run_id).unwrap();
+        run_id
+    }
+
+    #[test]
+    fn worker_poll_acknowledges_and_gets_terminate() {
+        let mut kernel = Kernel::new();
+        let run_id = started(&mut kernel, "cancel-poll");
+        kernel.define_stage_group("gpu", 1).unwrap();
+        assert!(kernel.stage_groups.acquire("gpu", &run_id).unwrap());
+        let (ack_tx, mut ack_rx) = oneshot::channel();
+        kernel.request_cancel(&run_id, "customer withdrew", Some(ack_tx)).unwrap();
+        assert_eq!(kernel.get_stage_group_stats("gpu").unwrap().in_use, 0);
+        assert!(kernel.request_cancel(&run_id, "again", None).is_err());
+
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::Terminate { reason, message, .. } => {
+                assert_eq!(reason, TerminalReason::UserCancelled);
+                assert_eq!(message.as_deref(), Some("customer withdrew"));
+            }
+            other => panic!("expected Terminate, got {:?}", other),
+        }
+        assert!(ack_rx.try_recv().is_ok());
+
+        let outcome = kernel.finish_cancel(&run_id).unwrap();
+        assert!(outcome.acknowledged);
+        assert!(!kernel.runs.contains_key(&run_id));
+        let record = &kernel.terminal_records(None, 1)[0];
+        assert_eq!(record.terminal_reason, Some(TerminalReason::UserCancelled));
+        assert_eq!(record.terminal_message.as_deref(), Some("customer withdrew"));
+    }
+
+    #[test]
+    fn abandoned_cancellation_is_dropped_with_stale_sessions() {
+        use crate::kernel::ManualClock;
+        use std::sync::Arc;
+
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        let run_id = started(&mut kernel, "cancel-abandoned");
+        // The caller's future is dropped before `finish_cancel`.
+        kernel.request_cancel(&run_id, "customer withdrew", None).unwrap();
+
+        clock.advance(chrono::TimeDelta::seconds(600));
+        assert_eq!(kernel.cleanup_stale_sessions(300), 1);
+        assert!(kernel.cancellations.is_empty());
+        assert!(kernel.finish_cancel(&run_id).is_err());
+    }
+
+    #[tokio::test]
+    async fn silent_worker_is_terminated_after_grace() {
+        let mut kernel = Kernel::new();
+        let run_id = started(&mut kernel, "cancel-silent");
+        let handle = crate::kernel::actor::spawn(kernel, tokio_util::sync::CancellationToken::new());
+
+        let outcome = handle.cancel_run(&run_id, "timeout budget", std::time::Duration::from_millis(20)).await.unwrap();
+        assert!(!outcome.acknowledged);
+        assert_eq!(outcome.reason, "timeout budget");
+        assert!(handle.get_session_state(&run_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn acknowledging_worker_ends_cancel_early() {
+        let mut kernel = Kernel::new();
+        let run_id = started(&mut kernel, "cancel-ack");
+        let handle = crate::kernel::actor::spawn(kernel, tokio_util::sync::CancellationToken::new());
+
+        let canceller = {
+            let handle = handle.clone();
+            let run_id = run_id.clone();
+            tokio::spawn(async move { handle.cancel_run(&run_id, "customer withdrew", std::time::Duration::from_secs(30)).await })
+        };
+        // The worker's next poll acknowledges; the actor terminates the run there.
+        while !matches!(
+            handle.get_next_instruction(&run_id).await.unwrap(),
+            Instruction::Terminate { reason: TerminalReason::UserCancelled, .. }
+        ) {
+            tokio::task::yield_now().await;
+        }
+
+        let outcome = tokio::time::timeout(std::time::Duration::from_secs(5), canceller).await.unwrap().unwrap().unwrap();
+        assert!(outcome.acknowledged);
+        assert_eq!(outcome.reason, "customer withdrew");
+        let records = handle.get_terminal_records(None, 1).await.unwrap();
+        assert_eq!(records[0].terminal_reason, Some(TerminalReason::UserCancelled));
+    }
+}
@@ -0,0 +1,230 @@
+//! Estimated-cost preflight for `RunAgent` dispatches.
+//!
+//! Every agent report is sampled per `(workflow, stage)`. Before a stage is
+//! dispatched, the median of its samples is priced with the stage's
+//! `model_role` and attached to the instruction as `cost_estimate`. When
+//! the estimate exceeds `CostPolicy.confirm_above`, the kernel raises a
+//! confirmation interrupt instead of dispatching; a response with
+//! `approved: false` cancels the run.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::protocol::Instruction;
+use super::types::ResourceUsage;
+use super::Kernel;
+use crate::run::{FlowInterrupt, ResponseSpec, TerminalReason};
+use crate::types::{Result, RunId};
+
+/// Price key used when a stage has no `model_role` or its role is unpriced.
+pub const DEFAULT_PRICE_KEY: &str = "default";
+
+/// Price per 1,000 tokens, in the deployment's currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_1k: f64,
+    #[serde(default)]
+    pub output_per_1k: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostPolicy {
+    /// `model_role` → price; `"default"` covers everything else.
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+    /// Ask for confirmation before dispatching a stage estimated above this.
+    #[serde(default)]
+    pub confirm_above: Option<f64>,
+}
+
+/// Expected usage of one stage execution, from past executions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub tokens_in: i64,
+    pub tokens_out: i64,
+    pub llm_calls: i32,
+    /// `None` when no price covers the stage's model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    /// Executions the estimate is based on.
+    pub samples: usize,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, tokens_in: i64, tokens_out: i64) -> f64 {
+        (tokens_in as f64 * self.input_per_1k + tokens_out as f64 * self.output_per_1k) / 1000.0
+    }
+}
+
+impl CostPolicy {
+    /// Price for `model_role`, falling back to `"default"`.
+    pub fn price(&self, model_role: Option<&str>) -> Option<&ModelPrice> {
+        model_role
+            .and_then(|role| self.prices.get(role))
+            .or_else(|| self.prices.get(DEFAULT_PRICE_KEY))
+    }
+
+    /// Price `median` for a stage running on `model_role`.
+    pub fn estimate(&self, model_role: Option<&str>, median: &ResourceUsage, samples: usize) -> CostEstimate {
+        let price = self.price(model_role);
+        CostEstimate {
+            tokens_in: median.tokens_in,
+            tokens_out: median.tokens_out,
+            llm_calls: median.llm_calls,
+            cost: price.map(|p| p.cost(median.tokens_in, median.tokens_out)),
+            samples,
+        }
+    }
+
+    /// Whether `estimate` needs confirmation before dispatch.
+    pub fn needs_confirmation(&self, estimate: &CostEstimate) -> bool {
+        matches!((self.confirm_above, estimate.cost), (Some(limit), Some(cost)) if cost > limit)
+    }
+}
+
+impl Kernel {
+    /// Estimate for the next execution of `stage` in `run_id`'s workflow.
+    /// `None` until the stage has been executed at least once.
+    pub fn estimate_stage_cost(&self, run_id: &RunId, stage: &str) -> Option<CostEstimate> {
+        let session = self.orchestrator.sessions.get(run_id)?;
+        let (median, samples) = self.resources.stage_median(&session.workflow.name, stage)?;
+        let model_role = self.orchestrator.get_stage_config(run_id, stage)
+            .and_then(|sc| sc.agent_config.model_role.as_deref());
+        Some(self.cost_policy.estimate(model_role, &median, samples))
+    }
+
+    /// Gate a `RunAgent` for the run's current stage on the confirmation
+    /// threshold. Returns the instruction to send instead — `WaitInterrupt`
+    /// while confirmation is needed, `Terminate` once it was declined — or
+    /// `None` to dispatch.
+    pub(crate) fn cost_preflight(&mut self, run_id: &RunId) -> Result<Option<Instruction>> {
+        if self.cost_policy.confirm_above.is_none() {
+            return Ok(None);
+        }
+        let Some(stage) = self.runs.get(run_id).map(|r| r.current_stage.clone()) else {
+            return Ok(None);
+        };
+        let answered = self.orchestrator.sessions.get_mut(run_id)
+            .and_then(|s| s.cost_confirmation.take())
+            .is_some_and(|pending| pending == stage);
+        if answered {
+            let declined = self.runs.get(run_id)
+                .and_then(|r| r.audit.metadata.get("_interrupt_response"))
+                .and_then(|resp| resp.get("approved"))
+                .and_then(|v| v.as_bool())
+                == Some(false);
+            if !declined {
+                return Ok(None);
+            }
+            let message = format!("Cost confirmation declined for stage {}", stage);
+            if let Some(run) = self.runs.edit(run_id) {
+                run.audit.metadata.remove("_interrupt_response");
+                run.terminate_with(TerminalReason::UserCancelled, Some(message.clone()));
+            }
+            return Ok(Some(Instruction::terminate(TerminalReason::UserCancelled, message)));
+        }
+
+        let Some(estimate) = self.estimate_stage_cost(run_id, stage.as_str()) else {
+            return Ok(None);
+        };
+        if !self.cost_policy.needs_confirmation(&estimate) {
+            return Ok(None);
+        }
+        let cost = estimate.cost.unwrap_or_default();
+        tracing::info!(run_id = %run_id, stage = %stage, cost, "cost_confirmation_requested");
+        let params = HashMap::from([
+            ("stage".to_string(), serde_json::json!(stage)),
+            ("cost".to_string(), serde_json::json!(format!("{:.2}", cost))),
+        ]);
+        let data = HashMap::from([
+            ("cost_preflight".to_string(), serde_json::json!(true)),
+            ("stage".to_string(), serde_json::json!(stage)),
+            ("estimate".to_string(), serde_json::to_value(&estimate).unwrap_or_default()),
+        ]);
+        let interrupt = FlowInterrupt::new_in(self.id_format)
+            .with_question(format!("Stage {} will likely cost {:.2}. Proceed?", stage, cost))
+            .with_message_key("interrupt.cost_confirmation", params)
+            .with_data(data)
+            .with_response_spec(ResponseSpec { require_approved: true, ..Default::default() });
+        self.set_run_interrupt(run_id, interrupt)?;
+        if let Some(session) = self.orchestrator.sessions.get_mut(run_id) {
+            session.cost_confirmation = Some(stage);
+        }
+        let pending = self.runs.get(run_id).and_then(|r| r.interrupts.interrupt.clone());
+        Ok(Some(Instruction::WaitInterrupt { interrupt: pending, poll_after_ms: None }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::metrics::AgentExecutionMetrics;
+    use crate::kernel::test_helpers;
+    use crate::run::InterruptResponse;
+
+    fn kernel_with_history() -> Kernel {
+        let mut kernel = Kernel::new();
+        kernel.set_cost_policy(CostPolicy {
+            prices: HashMap::from([(
+                DEFAULT_PRICE_KEY.to_string(),
+                ModelPrice { input_per_1k: 1.0, output_per_1k: 2.0 },
+            )]),
+            confirm_above: Some(1.5),
+        });
+        let run_id = RunId::must("history");
+        let _ = kernel.initialize_orchestration(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false).unwrap();
+        let _ = kernel.get_next_instruction(&run_id).unwrap();
+        let metrics = AgentExecutionMetrics { llm_calls: 1, tokens_in: Some(1000), tokens_out: Some(500), ..Default::default() };
+        kernel.process_agent_result(&run_id, "agent1", serde_json::json!({}), None, metrics, true, "", false, None).unwrap();
+        kernel
+    }
+
+    fn start(kernel: &mut Kernel, id: &str) -> RunId {
+        let run_id = RunId::must(id);
+        let _ = kernel.initialize_orchestration(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false).unwrap();
+        run_id
+    }
+
+    fn answer(kernel: &mut Kernel, run_id: &RunId, approved: bool) {
+        let interrupt = match kernel.get_next_instruction(run_id).unwrap() {
+            Instruction::WaitInterrupt { interrupt: Some(i), .. } => i,
+            other => panic!("expected WaitInterrupt, got {:?}", other),
+        };
+        assert_eq!(interrupt.data.as_ref().unwrap()["estimate"]["cost"], 2.0);
+        let response = InterruptResponse {
+            text: None,
+            approved: Some(approved),
+            decision: None,
+            data: None,
+            responder: None,
+            received_at: chrono::Utc::now(),
+        };
+        kernel.resolve_run_interrupt(run_id, interrupt.id.as_str(), response).unwrap();
+    }
+
+    #[test]
+    fn expensive_stage_waits_for_confirmation() {
+        let mut kernel = kernel_with_history();
+        let run_id = start(&mut kernel, "approved");
+        answer(&mut kernel, &run_id, true);
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => {
+                let estimate = context.cost_estimate.unwrap();
+                assert_eq!((estimate.tokens_in, estimate.cost, estimate.samples), (1000, Some(2.0), 1));
+            }
+            other => panic!("expected RunAgent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn declined_confirmation_cancels_run() {
+        let mut kernel = kernel_with_history();
+        let run_id = start(&mut kernel, "declined");
+        answer(&mut kernel, &run_id, false);
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::Terminate { reason, .. } => assert_eq!(reason, TerminalReason::UserCancelled),
+            other => panic!("expected Terminate, got {:?}", other),
+        }
+    }
+}
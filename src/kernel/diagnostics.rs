@@ -0,0 +1,204 @@
+//! Deployment self-test.
+//!
+//! `Kernel::run_diagnostics` drives a two-stage sample workflow through a
+//! scratch kernel configured like the live one (clock, default quota,
+//! bounds, admission and interrupt limits), so nothing the probes do can
+//! reach live runs or the records that outlive them. Tool circuit breakers
+//! and state integrity are read from the live kernel as they stand.
+
+use std::time::Instant;
+
+use serde::Serialize;
+
+use super::protocol::Instruction;
+use super::Kernel;
+use crate::run::Run;
+use crate::types::{Error, Result, RunId};
+use crate::workflow::Workflow;
+
+/// Outcome of one diagnostic check.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub ok: bool,
+    /// Error text for a failed check, or extra context for a passing one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub duration_us: u64,
+}
+
+/// Report returned by `Kernel::run_diagnostics`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    /// True when every check passed.
+    pub healthy: bool,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl Kernel {
+    /// Run the self-test and report each subsystem's result.
+    pub fn run_diagnostics(&self) -> DiagnosticsReport {
+        let broken = self.tools.health.get_circuit_broken_tools();
+        let integrity = self.check_integrity();
+        let mut scratch = self.scratch_kernel();
+        let checks = vec![
+            check("lifecycle", || lifecycle_check(&mut scratch)),
+            check("orchestration", || orchestration_check(&mut scratch)),
+            check("integrity", || {
+                if integrity.is_clean() {
+                    Ok(None)
+                } else {
+                    let kinds: Vec<String> = integrity.issues.iter().map(|i| format!("{:?}", i.kind)).collect();
+                    Err(Error::internal(format!("dangling state: {}", kinds.join(", "))))
+                }
+            }),
+            check("tool_health", || {
+                if broken.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(Error::internal(format!("circuit broken: {}", broken.join(", "))))
+                }
+            }),
+        ];
+        DiagnosticsReport {
+            healthy: checks.iter().all(|c| c.ok),
+            checks,
+        }
+    }
+
+    /// An empty kernel with the live clock and limits. Policies that judge
+    /// runs (input policy, screening, quarantine, cost preflight, agent
+    /// bindings) are left at their defaults: they would judge the sample
+    /// rather than the kernel.
+    fn scratch_kernel(&self) -> Kernel {
+        let mut scratch = Kernel::with_quota(Some(self.lifecycle.get_default_quota().clone()));
+        scratch.set_clock(self.clock.clone());
+        scratch.set_bounds_defaults(self.bounds_defaults());
+        scratch.set_interrupt_limits(self.interrupts.limits.clone());
+        scratch.lifecycle.max_active = self.lifecycle.max_active;
+        scratch.id_format = self.id_format;
+        scratch
+    }
+}
+
+fn check(name: &str, f: impl FnOnce() -> Result<Option<String>>) -> DiagnosticCheck {
+    let start = Instant::now();
+    let result = f();
+    let duration_us = start.elapsed().as_micros() as u64;
+    let (ok, detail) = match result {
+        Ok(detail) => (true, detail),
+        Err(e) => (false, Some(e.to_string())),
+    };
+    DiagnosticCheck { name: name.to_string(), ok, detail, duration_us }
+}
+
+fn sample_workflow() -> Result<Workflow> {
+    serde_json::from_value(serde_json::json!({
+        "name": "diagnostics",
+        "stages": [
+            {"name": "first", "agent": "first", "default_next": "second"},
+            {"name": "second", "agent": "second"},
+        ],
+        "max_iterations": 5,
+        "max_llm_calls": 5,
+        "max_agent_hops": 5,
+    }))
+    .map_err(|e| Error::internal(format!("sample workflow: {}", e)))
+}
+
+fn lifecycle_check(kernel: &mut Kernel) -> Result<Option<String>> {
+    let run_id = RunId::must("diag-lifecycle");
+    kernel.create_run(run_id.clone(), "diag".into(), "diag".into(), "diag".into(), None)?;
+    kernel.lifecycle.run(&run_id)?;
+    if kernel.lifecycle.run(&run_id).is_ok() {
+        return Err(Error::internal("Running → Running was accepted"));
+    }
+    kernel.terminate_run(&run_id)?;
+    if kernel.lifecycle.get(&run_id).is_some() {
+        return Err(Error::internal("terminated run was not removed"));
+    }
+    Ok(None)
+}
+
+fn orchestration_check(kernel: &mut Kernel) -> Result<Option<String>> {
+    let run_id = RunId::must("diag-orchestration");
+    let _ = kernel.initialize_run(run_id.clone(), sample_workflow()?, Run::new("diag", "diag", "ping", None), false, None)?;
+    let mut visited = Vec::new();
+    loop {
+        match kernel.get_next_instruction(&run_id)? {
+            Instruction::RunAgent { agent, .. } => {
+                kernel.process_agent_result(
+                    &run_id, agent.as_str(), serde_json::json!({}), None,
+                    Default::default(), true, "", false, None,
+                )?;
+                visited.push(agent.to_string());
+            }
+            Instruction::Terminate { .. } => break,
+            other => {
+                return Err(Error::internal(format!("unexpected instruction: {:?}", other)));
+            }
+        }
+        if visited.len() > 2 {
+            return Err(Error::internal("sample workflow did not terminate"));
+        }
+    }
+    kernel.terminate_run(&run_id)?;
+    if visited != ["first", "second"] {
+        return Err(Error::internal(format!("visited {:?}, expected [first, second]", visited)));
+    }
+    Ok(Some(format!("visited {}", visited.join(" → "))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_pass_on_a_fresh_kernel() {
+        let kernel = Kernel::new();
+        let report = kernel.run_diagnostics();
+        assert!(report.healthy, "{:?}", report.checks);
+        let names: Vec<_> = report.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["lifecycle", "orchestration", "integrity", "tool_health"]);
+        assert!(kernel.runs.is_empty());
+    }
+
+    #[test]
+    fn diagnostics_leave_live_state_untouched() {
+        let mut kernel = Kernel::new();
+        let live = RunId::must("live");
+        let _ = kernel.initialize_run(
+            live.clone(), crate::kernel::test_helpers::create_test_workflow(),
+            crate::kernel::test_helpers::create_test_run(), false, None,
+        ).unwrap();
+
+        // The live run holds the only slot; the scratch kernel has its own.
+        kernel.set_max_active_runs(Some(1)).unwrap();
+        // Quarantine judges agents, not the kernel, and is not copied.
+        kernel.set_agent_quarantine("first", Some("manual".into())).unwrap();
+        let revision = kernel.runs[&live].revision;
+        let report = kernel.run_diagnostics();
+        assert!(report.healthy, "{:?}", report.checks);
+
+        let ids: Vec<_> = kernel.runs.keys().map(|id| id.as_str()).collect();
+        assert_eq!(ids, ["live"]);
+        assert_eq!(kernel.runs[&live].revision, revision);
+        assert_eq!(kernel.lifecycle.count(), 1);
+        assert!(kernel.terminal_records(None, 100).is_empty());
+        assert!(kernel.recommend_quota("diagnostics", 0.0).is_err());
+        assert!(kernel.resources.get_user_usage("diag").is_none());
+    }
+
+    #[test]
+    fn scratch_kernel_shares_the_live_clock_and_limits() {
+        let clock = std::sync::Arc::new(crate::kernel::ManualClock::new(chrono::Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        kernel.set_max_active_runs(Some(3)).unwrap();
+        let scratch = kernel.scratch_kernel();
+        assert_eq!(scratch.lifecycle.max_active, Some(3));
+        clock.advance(chrono::TimeDelta::seconds(30));
+        assert_eq!(scratch.clock.now(), kernel.clock.now());
+        assert_eq!(scratch.bounds_defaults(), kernel.bounds_defaults());
+    }
+}
@@ -6,9 +6,10 @@ use tracing::instrument;
 
 use crate::agent::policy::ContextOverflow;
 use crate::run::{Run, FlowInterrupt};
-use crate::types::{Error, RunId, RequestId, Result, SessionId, UserId};
+use crate::types::{Error, InterruptId, RunId, RequestId, Result, SessionId, UserId};
 
-use super::merge_state_field;
+use super::{append_same_as, merge_state_field};
+use super::field_mask;
 use super::orchestrator;
 use super::{Kernel, RunStatus, RemainingBudget, ResourceQuota, SystemStatus};
 
@@ -24,13 +25,143 @@ impl Kernel {
         mut run: Run,
         force: bool,
     ) -> Result<orchestrator::RunSnapshot> {
+        self.admit_active_run(&run_id)?;
+        if let Some(&limit) = self.orchestrator.concurrency_limits.get(&workflow.name) {
+            let active = self.active_runs_for_workflow(&workflow.name, &run_id);
+            if active >= limit {
+                return Err(Error::quota_exceeded(format!(
+                    "Workflow '{}' is at its concurrency limit ({} active runs)",
+                    workflow.name, limit
+                )));
+            }
+        }
+        self.check_stage_groups(&workflow)?;
+        self.check_agent_bindings(&workflow)?;
+        self.input_policy.apply(&mut run)?;
+        // Timestamps read by dwell and summary checks come from the
+        // kernel clock, not from wherever the run was built.
+        let now = self.clock.now();
+        run.received_at = now;
+        run.audit.created_at = now;
         let state = self.orchestrator
             .initialize_session(run_id.clone(), workflow, &mut run, force)?;
-        self.runs.insert(run_id, run);
+        #[cfg(feature = "screening")]
+        let raw_input = (!self.screener.is_empty()).then(|| run.raw_input.clone());
+        self.runs.insert(run_id.clone(), run);
+        #[cfg(feature = "screening")]
+        if let Some(raw_input) = raw_input {
+            self.screen_run(&run_id, "raw_input", &[raw_input.as_str()])?;
+        }
 
         Ok(state)
     }
 
+    /// Cap concurrently active runs of the workflow named `workflow_name`.
+    /// Starting a run beyond the cap fails with `Error::QuotaExceeded`.
+    /// `None` removes the cap. Lowering it never affects runs already started.
+    pub fn set_workflow_concurrency_limit(&mut self, workflow_name: &str, limit: Option<usize>) -> Result<()> {
+        match limit {
+            Some(0) => {
+                return Err(Error::validation("workflow concurrency limit must be positive"));
+            }
+            Some(limit) => {
+                self.orchestrator.concurrency_limits.insert(workflow_name.to_string(), limit);
+            }
+            None => {
+                self.orchestrator.concurrency_limits.remove(workflow_name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cap live runs kernel-wide. Creating a run beyond the cap fails with
+    /// `Error::QuotaExceeded` and `SystemStatus.overloaded` reads true.
+    /// `None` removes the cap; runs already admitted are never affected.
+    pub fn set_max_active_runs(&mut self, max: Option<usize>) -> Result<()> {
+        if max == Some(0) {
+            return Err(Error::validation("max active runs must be positive"));
+        }
+        self.lifecycle.max_active = max;
+        Ok(())
+    }
+
+    /// Live runs counted against `max_active_runs`: every run record, plus
+    /// live runs initialized without one.
+    pub(crate) fn active_run_count(&self) -> usize {
+        let unrecorded = self.runs.iter()
+            .filter(|(id, run)| !run.is_terminated() && self.lifecycle.get(id).is_none())
+            .count();
+        self.lifecycle.count() + unrecorded
+    }
+
+    /// Shed a new run once `max_active_runs` is reached. A run that already
+    /// has a record, or a live entry in `runs`, was admitted and passes.
+    pub(crate) fn admit_active_run(&self, run_id: &RunId) -> Result<()> {
+        let Some(max) = self.lifecycle.max_active else { return Ok(()) };
+        let admitted = self.lifecycle.get(run_id).is_some()
+            || self.runs.get(run_id).is_some_and(|run| !run.is_terminated());
+        let active = self.active_run_count();
+        if admitted || active < max {
+            return Ok(());
+        }
+        tracing::warn!(run_id = %run_id, active, "run_shed");
+        Err(Error::quota_exceeded(format!(
+            "Kernel at capacity ({} active runs); run {} rejected",
+            active, run_id
+        )))
+    }
+
+    /// Non-terminated runs with a session on `workflow_name`, not counting
+    /// `exclude` (a forced re-initialization replaces that session).
+    fn active_runs_for_workflow(&self, workflow_name: &str, exclude: &RunId) -> usize {
+        self.orchestrator.sessions.iter()
+            .filter(|(id, session)| *id != exclude && session.workflow.name == workflow_name)
+            .filter(|(id, _)| self.runs.get(*id).is_some_and(|run| !run.is_terminated()))
+            .count()
+    }
+
+    /// Registers a run record (if the run has none yet) and seeds its
+    /// orchestration session as one step. `quota` applies only when the
+    /// record is created here; `None` uses the kernel default. If session
+    /// setup fails, a record created here is removed again so no
+    /// half-initialized run is left behind; a pre-existing record is left
+    /// untouched.
+    #[instrument(skip(self, workflow, run, quota), fields(run_id = %run_id))]
+    pub fn initialize_run(
+        &mut self,
+        run_id: RunId,
+        workflow: orchestrator::Workflow,
+        run: Run,
+        force: bool,
+        quota: Option<ResourceQuota>,
+    ) -> Result<(super::RunRecord, orchestrator::RunSnapshot)> {
+        self.admit_active_run(&run_id)?;
+        let created = self.lifecycle.get(&run_id).is_none();
+        let mut record = self.lifecycle.create(
+            run_id.clone(),
+            run.identity.request_id.clone(),
+            run.identity.user_id.clone(),
+            run.identity.session_id.clone(),
+            quota,
+        )?;
+        if created && run.identity.external_ref.is_some() {
+            record.external_ref = run.identity.external_ref.clone();
+            if let Some(stored) = self.lifecycle.records.get_mut(&run_id) {
+                stored.external_ref = record.external_ref.clone();
+            }
+        }
+
+        match self.initialize_orchestration(run_id.clone(), workflow, run, force) {
+            Ok(state) => Ok((record, state)),
+            Err(e) => {
+                if created {
+                    self.lifecycle.records.remove(&run_id);
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// Fetches and enriches the next instruction for `run_id`. The
     /// orchestrator may mutate the run on its way to a `Terminate`
     /// (bounds, errors); enrichment then layers in agent context, stage
@@ -41,9 +172,38 @@ impl Kernel {
         &mut self,
         run_id: &RunId,
     ) -> Result<orchestrator::Instruction> {
-        let run = self.runs.get_mut(run_id)
+        if let Some(cancelled) = self.cancel_instruction(run_id) {
+            return Ok(cancelled);
+        }
+        let run = self.runs.get(run_id)
             .ok_or_else(|| Error::not_found(format!("Run not found for run_id: {}", run_id)))?;
-        let mut instruction = self.orchestrator.get_next_instruction(run_id, run)?;
+        let (mut instruction, changes) = self.orchestrator.next_instruction(run_id, run)?;
+        if !changes.is_empty() {
+            if let Some(run) = self.runs.edit(run_id) {
+                changes.apply(run);
+            }
+        }
+        let mut agent_version = None;
+        if let orchestrator::Instruction::RunAgent { agent, .. } = &instruction {
+            let agent = agent.clone();
+            if let Some(diverted) = self.quarantine_gate(run_id, &agent)? {
+                return Ok(self.with_poll_hint(diverted));
+            }
+            agent_version = match self.resolve_agent_version(run_id, &agent)? {
+                Ok(version) => version,
+                Err(wait) => return Ok(wait),
+            };
+        }
+        if matches!(instruction, orchestrator::Instruction::RunAgent { .. }) {
+            if let Some(held) = self.cost_preflight(run_id)? {
+                instruction = held;
+            }
+        }
+        if matches!(instruction, orchestrator::Instruction::RunAgent { .. }) {
+            if let Some(queued) = self.stage_group_gate(run_id)? {
+                return Ok(queued);
+            }
+        }
 
         match &mut instruction {
             orchestrator::Instruction::RunAgent { agent: _, context }=> {
@@ -54,9 +214,17 @@ impl Kernel {
                     context.context_overflow = overflow;
                 }
 
-                if let Some(env) = self.runs.get_mut(run_id) {
-                    context.interrupt_response = env.audit.metadata.remove("_interrupt_response");
+                if self.runs.get(run_id).is_some_and(|r| r.audit.metadata.contains_key("_interrupt_response")) {
+                    context.interrupt_response = self.runs.edit(run_id)
+                        .and_then(|run| run.audit.metadata.remove("_interrupt_response"));
+                }
+                let dispatch_id = self.id_format.generate();
+                self.orchestrator.record_dispatch(run_id, &dispatch_id);
+                context.dispatch_id = Some(dispatch_id);
+                if let Some(session) = self.orchestrator.sessions.get_mut(run_id) {
+                    session.dispatched_version = agent_version.clone();
                 }
+                context.agent_version = agent_version;
 
                 let stage_name = self.runs.get(run_id)
                     .map(|e| e.current_stage.clone())
@@ -68,10 +236,11 @@ impl Kernel {
                 }
 
                 context.response_format = self.orchestrator.get_stage_response_format(run_id, stage_name.as_str());
+                context.cost_estimate = self.estimate_stage_cost(run_id, stage_name.as_str());
             }
             orchestrator::Instruction::Terminate { context, .. } => {
                 if let Some(run) = self.runs.get(run_id) {
-                    let total_duration_ms = (chrono::Utc::now() - run.audit.created_at)
+                    let total_duration_ms = (self.clock.now() - run.audit.created_at)
                         .num_milliseconds();
                     context.agent_context = Some(serde_json::json!({
                         "outputs": &run.outputs,
@@ -99,13 +268,41 @@ impl Kernel {
             }
         }
 
-        Ok(instruction)
+        Ok(self.with_poll_hint(instruction))
+    }
+
+    /// Fill in `WaitInterrupt.poll_after_ms`: a quarter of the recent
+    /// median resolution time, stretched by up to 2× as live runs approach
+    /// `max_active_runs`, never past the interrupt's expiry, and clamped to
+    /// `MIN_POLL_MS..=MAX_POLL_MS`.
+    fn with_poll_hint(&self, mut instruction: orchestrator::Instruction) -> orchestrator::Instruction {
+        use super::interrupts::{DEFAULT_POLL_MS, MAX_POLL_MS, MIN_POLL_MS};
+        if let orchestrator::Instruction::WaitInterrupt { interrupt, poll_after_ms } = &mut instruction {
+            let base = self.interrupts.median_resolution_ms()
+                .map_or(DEFAULT_POLL_MS, |ms| ms.unsigned_abs() / 4);
+            let load = self.lifecycle.max_active
+                .map_or(0.0, |max| (self.active_run_count() as f64 / max as f64).min(1.0));
+            let mut hint = (base as f64 * (1.0 + load)) as u64;
+            if let Some(expires_at) = interrupt.as_ref().and_then(|i| i.expires_at) {
+                let until_expiry = (expires_at - self.clock.now()).num_milliseconds().max(0);
+                hint = hint.min(until_expiry.unsigned_abs());
+            }
+            *poll_after_ms = Some(hint.clamp(MIN_POLL_MS, MAX_POLL_MS));
+        }
+        instruction
     }
 
     /// Merges an agent's output into the run, reports it to the
     /// orchestrator, and applies the metrics delta to the run record. The
     /// caller pulls the next instruction separately — the split is what
     /// keeps fork/parallel paths deadlock-free.
+    ///
+    /// `dispatch_id` is the token from the `RunAgent` being answered. A
+    /// report repeating a recently seen token is acknowledged and ignored,
+    /// so a worker retrying after a lost response can't double-count. A
+    /// token the run never issued, or one long evicted, is rejected. The
+    /// token only counts as reported once the result has been applied, so a
+    /// report that fails can be retried with the same token.
     #[allow(clippy::too_many_arguments)]
     #[instrument(skip(self, output, metrics), fields(run_id = %run_id))]
     pub fn process_agent_result(
@@ -118,16 +315,63 @@ impl Kernel {
         success: bool,
         error_message: &str,
         break_loop: bool,
+        dispatch_id: Option<&str>,
+    ) -> Result<()> {
+        if let Some(token) = dispatch_id {
+            if !self.orchestrator.check_dispatch_report(run_id, token)? {
+                tracing::info!(dispatch_id = token, "duplicate_agent_result_ignored");
+                return Ok(());
+            }
+        }
+        self.admit_checkpoint(run_id)?;
+        self.apply_agent_result(
+            run_id, agent_name, output, metadata_updates, metrics, success, error_message, break_loop, dispatch_id,
+        )?;
+        if let Some(token) = dispatch_id {
+            self.orchestrator.record_dispatch_report(run_id, token);
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn apply_agent_result(
+        &mut self,
+        run_id: &RunId,
+        agent_name: &str,
+        output: serde_json::Value,
+        metadata_updates: Option<HashMap<String, serde_json::Value>>,
+        metrics: orchestrator::AgentExecutionMetrics,
+        success: bool,
+        error_message: &str,
+        break_loop: bool,
+        dispatch_id: Option<&str>,
     ) -> Result<()> {
+        // Every lookup that can refuse the report runs before anything is
+        // recorded, so a refused report can be retried with the same token.
+        let current_stage = self.runs.get(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?
+            .current_stage.clone();
+        let stage = self.orchestrator.get_stage_config(run_id, current_stage.as_str())
+            .ok_or_else(|| Error::state_transition(format!(
+                "Current stage '{}' not found in workflow for run {}",
+                current_stage, run_id
+            )))?;
+        let checkpoint = stage.checkpoint;
+        let output_schema = stage.output_schema.clone();
+
         // Pull scalars now so we can move `metrics` into the orchestrator below.
         let llm_calls = metrics.llm_calls;
         let tool_calls = metrics.tool_calls;
         let tokens_in = metrics.tokens_in.unwrap_or(0);
         let tokens_out = metrics.tokens_out.unwrap_or(0);
         let duration_ms = metrics.duration_ms;
+        let model = metrics.model.clone();
 
+        let mut tools: Vec<String> = Vec::new();
         for tool_result in &metrics.tool_results {
-            self.tools.health.record_execution(&tool_result.name, tool_result.success, tool_result.latency_ms, tool_result.error_type.clone());
+            if !tools.contains(&tool_result.name) {
+                tools.push(tool_result.name.clone());
+            }
         }
 
         // Lift state_schema + output_key out before the &mut run borrow.
@@ -135,12 +379,80 @@ impl Kernel {
         let output_key = self.orchestrator.get_stage_output_key(run_id, agent_name)
             .unwrap_or_else(|| agent_name.to_string());
 
+        let output = if self.post_processors.is_empty() {
+            output
+        } else {
+            self.post_processors.apply(current_stage.as_str(), &tools, output)
+        };
+
+        // Enforce the stage's output contract. A violation turns a reported
+        // success into a failure so `error_next` / retry paths take over.
+        let schema_errors = output_schema.as_ref()
+            .filter(|_| success)
+            .map(|schema| crate::workflow::output_schema::validate(schema, &output))
+            .unwrap_or_default();
+        let schema_failure_message;
+        let (success, error_message) = if schema_errors.is_empty() {
+            (success, error_message)
+        } else {
+            tracing::warn!(agent = agent_name, errors = ?schema_errors, "agent_output_schema_violation");
+            schema_failure_message = format!(
+                "output failed schema validation: {}",
+                schema_errors.join("; ")
+            );
+            (false, schema_failure_message.as_str())
+        };
+
+        #[cfg(feature = "screening")]
+        let screen_texts = (!self.screener.is_empty()).then(|| super::screening::string_leaves(&output));
+        #[cfg(feature = "screening")]
+        if let Some(texts) = &screen_texts {
+            self.admit_screening(run_id, texts)?;
+        }
+        // Checkpoint admission ran in `process_agent_result`; from here on
+        // nothing refuses the report.
+
+        self.acknowledge_cancel(run_id);
+        self.release_stage_group(run_id);
+        let agent_version = self.orchestrator.sessions.get_mut(run_id).and_then(|s| s.dispatched_version.take());
+        for tool_result in &metrics.tool_results {
+            self.tools.health.record_execution(&tool_result.name, tool_result.success, tool_result.latency_ms, tool_result.error_type.clone());
+        }
+
+        // Screen before anything of the output reaches the run. A flagged
+        // output is dropped: the run is terminated, or paused and the stage
+        // dispatched again once the interrupt is resolved.
+        #[cfg(feature = "screening")]
+        if let Some(texts) = screen_texts {
+            if self.screen_run(run_id, &format!("outputs.{}", agent_name), &texts)? {
+                if let Some(uid) = self.lifecycle.get(run_id).map(|p| p.user_id.as_str().to_string()) {
+                    self.record_user_usage(&uid, llm_calls, tool_calls, tokens_in, tokens_out);
+                }
+                return Ok(());
+            }
+        }
+
+        self.record_agent_outcome(agent_name, success);
+        if let Some(session) = self.orchestrator.sessions.get(run_id) {
+            self.resources.record_stage_usage(&session.workflow.name, current_stage.as_str(), super::ResourceUsage {
+                llm_calls,
+                tool_calls,
+                tokens_in,
+                tokens_out,
+                ..Default::default()
+            });
+        }
+        let retry_stage = !schema_errors.is_empty()
+            && self.orchestrator.claim_output_retry(run_id, current_stage.as_str());
         {
-            let run = self.runs.get_mut(run_id)
+            let run = self.runs.edit(run_id)
                 .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
 
+            // Validated above: an output that violates the schema never
+            // reaches `outputs` or `state`. A retried stage records nothing
+            // of it; a final violation records only the failure.
             let mut agent_output: std::collections::HashMap<crate::types::OutputKey, serde_json::Value> = std::collections::HashMap::new();
-            if let serde_json::Value::Object(output_map) = output {
+            if let (true, serde_json::Value::Object(output_map)) = (schema_errors.is_empty(), output) {
                 for (key, value) in output_map {
                     agent_output.insert(key.as_str().into(), value);
                 }
@@ -150,31 +462,54 @@ impl Kernel {
                 if !error_message.is_empty() {
                     agent_output.insert("error".into(), serde_json::Value::String(error_message.to_string()));
                 }
-                run.audit.metadata.insert(
-                    "last_agent_failure".to_string(),
-                    serde_json::json!({
-                        "agent_name": agent_name,
-                        "error": error_message,
-                    }),
-                );
-            }
-            run.outputs.insert(agent_name.into(), agent_output);
-
-            let mut state_matched = false;
-            for field in &state_schema {
-                if field.key == output_key {
-                    let output_value = serde_json::Value::Object(
-                        run.outputs.get(agent_name)
-                            .map(|m| m.iter().map(|(k, v)| (k.as_str().to_string(), v.clone())).collect())
-                            .unwrap_or_default()
-                    );
-                    merge_state_field(&mut run.state, &field.key, output_value, field.merge);
-                    state_matched = true;
-                    break;
+                let mut failure = serde_json::json!({
+                    "agent_name": agent_name,
+                    "error": error_message,
+                });
+                if !schema_errors.is_empty() {
+                    failure["schema_errors"] = serde_json::json!(schema_errors);
                 }
+                run.audit.metadata.insert("last_agent_failure".to_string(), failure);
             }
-            if !state_schema.is_empty() && !state_matched {
-                tracing::debug!(output_key = %output_key, "output_key has no matching state_schema entry");
+            if !retry_stage {
+                let content_hash = crate::run::OutputProvenance::fingerprint(&agent_output);
+                let unchanged = run.outputs_provenance.get(agent_name)
+                    .is_some_and(|p| p.content_hash == content_hash);
+                run.outputs_provenance.insert(agent_name.into(), crate::run::OutputProvenance {
+                    agent: agent_name.to_string(),
+                    stage: current_stage.to_string(),
+                    model: model.clone(),
+                    dispatch_id: dispatch_id.map(str::to_string),
+                    iteration: run.iteration,
+                    recorded_at: self.clock.now(),
+                    content_hash,
+                });
+                run.outputs.insert(agent_name.into(), agent_output);
+
+                let mut state_matched = false;
+                for field in state_schema.iter().filter(|_| schema_errors.is_empty()) {
+                    if field.key == output_key {
+                        let output_value = serde_json::Value::Object(
+                            run.outputs.get(agent_name)
+                                .map(|m| m.iter().map(|(k, v)| (k.as_str().to_string(), v.clone())).collect())
+                                .unwrap_or_default()
+                        );
+                        if field.merge == crate::workflow::MergeStrategy::AppendDedup
+                            && unchanged
+                            && append_same_as(&mut run.state, &field.key)
+                        {
+                            run.metrics.outputs_deduplicated += 1;
+                            run.metrics.dedup_bytes_saved += serde_json::to_vec(&output_value).map_or(0, |v| v.len() as i64);
+                        } else {
+                            merge_state_field(&mut run.state, &field.key, output_value, field.merge);
+                        }
+                        state_matched = true;
+                        break;
+                    }
+                }
+                if !state_schema.is_empty() && !state_matched && schema_errors.is_empty() {
+                    tracing::debug!(output_key = %output_key, "output_key has no matching state_schema entry");
+                }
             }
 
             if let Some(meta_updates) = metadata_updates {
@@ -184,11 +519,27 @@ impl Kernel {
             }
 
             let effective_failed = !success;
-            self.orchestrator.report_agent_result(run_id, agent_name, metrics, run, effective_failed, break_loop)?;
+            if retry_stage {
+                // Hand the errors back to the agent and run the stage again.
+                run.push_loop_feedback(crate::run::LoopFeedback {
+                    severity: crate::run::FeedbackSeverity::Error,
+                    code: "output_schema".to_string(),
+                    message: format!("Output from {} violates the stage output_schema", agent_name),
+                    stage: current_stage.to_string(),
+                    iteration: run.iteration,
+                    details: schema_errors.clone(),
+                    repeats: 1,
+                });
+                self.orchestrator.retry_current_stage(run_id, metrics, run)?;
+            } else {
+                run.remove_loop_feedback("output_schema", current_stage.as_str());
+                self.orchestrator.report_agent_result(run_id, agent_name, metrics, run, effective_failed, break_loop)?;
+            }
 
-            let now = chrono::Utc::now();
+            let now = self.clock.now();
             run.audit.processing_history.push(crate::run::ProcessingRecord {
                 agent: agent_name.to_string(),
+                agent_version,
                 stage_order: run
                     .stage_order
                     .iter()
@@ -204,9 +555,15 @@ impl Kernel {
                 tool_calls,
                 tokens_in,
                 tokens_out,
+                model,
+                tools,
             });
         }
 
+        if checkpoint && !retry_stage {
+            self.raise_checkpoint(run_id, current_stage.as_str(), agent_name)?;
+        }
+
         if let Some(uid) = self.lifecycle.get(run_id).map(|p| p.user_id.as_str().to_string()) {
             self.record_user_usage(&uid, llm_calls, tool_calls, tokens_in, tokens_out);
         }
@@ -214,6 +571,42 @@ impl Kernel {
         Ok(())
     }
 
+    /// Refuse a result for a `checkpoint` stage, before anything is recorded,
+    /// when the `InterruptLimits` would not admit its checkpoint. Otherwise
+    /// the output would be merged and routed with no checkpoint to hold it.
+    fn admit_checkpoint(&mut self, run_id: &RunId) -> Result<()> {
+        let Some(run) = self.runs.get(run_id).filter(|r| !r.is_terminated()) else {
+            return Ok(());
+        };
+        let checkpoint = self.orchestrator.get_stage_config(run_id, run.current_stage.as_str())
+            .is_some_and(|sc| sc.checkpoint);
+        if !checkpoint {
+            return Ok(());
+        }
+        self.interrupts.admit_kind(super::interrupts::InterruptKind::Message, &run.identity.session_id)
+    }
+
+    /// Suspend a live run after a `checkpoint` stage with an interrupt that
+    /// carries the stage's output for review.
+    fn raise_checkpoint(&mut self, run_id: &RunId, stage: &str, agent_name: &str) -> Result<()> {
+        let Some(run) = self.runs.get(run_id) else { return Ok(()) };
+        if run.is_terminated() {
+            return Ok(());
+        }
+        let output = run.outputs.get(agent_name)
+            .map(|o| serde_json::to_value(o).unwrap_or_default())
+            .unwrap_or_default();
+        let data = HashMap::from([
+            ("checkpoint".to_string(), serde_json::Value::Bool(true)),
+            ("stage".to_string(), serde_json::Value::String(stage.to_string())),
+            ("output".to_string(), output),
+        ]);
+        let interrupt = FlowInterrupt::new_in(self.id_format)
+            .with_message(format!("Checkpoint after stage '{}'", stage))
+            .with_data(data);
+        self.set_run_interrupt(run_id, interrupt).map(|_| ())
+    }
+
     /// Get orchestration session state.
     pub fn get_orchestration_state(
         &self,
@@ -221,7 +614,31 @@ impl Kernel {
     ) -> Result<orchestrator::RunSnapshot> {
         let run = self.runs.get(run_id)
             .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
-        self.orchestrator.get_session_state(run_id, run)
+        let mut snapshot = self.orchestrator.get_session_state(run_id, run)?;
+        if let Some(record) = self.lifecycle.get(run_id) {
+            snapshot.extensions = record.extensions.clone();
+        }
+        snapshot.heartbeat = self.orchestrator.sessions.get(run_id).and_then(|s| s.heartbeat.clone());
+        Ok(snapshot)
+    }
+
+    /// Store (or, with `None`, remove) an embedder extension on this run.
+    /// See [`super::RunRecord::insert_extension`] for the bounds.
+    pub fn set_run_extension(
+        &mut self,
+        run_id: &RunId,
+        key: &str,
+        extension: Option<super::RunExtension>,
+    ) -> Result<()> {
+        let record = self.lifecycle.get_mut(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run {} not found", run_id)))?;
+        match extension {
+            Some(ext) => record.insert_extension(key, ext),
+            None => {
+                record.extensions.remove(key);
+                Ok(())
+            }
+        }
     }
 
     /// Reads the run and stage config, packs them into the JSON shape
@@ -232,18 +649,10 @@ impl Kernel {
         run_id: &RunId,
     ) -> Option<(serde_json::Value, Option<i64>, Option<ContextOverflow>)> {
         let run = self.runs.get(run_id)?;
+        let stage_name = run.current_stage.clone();
+        let stage_config = self.orchestrator.get_stage_config(run_id, stage_name.as_str());
 
-        let mut template_vars = serde_json::Map::new();
-        for (agent_name, output) in &run.outputs {
-            for (key, value) in output {
-                template_vars.insert(format!("{}_{}", agent_name, key), value.clone());
-            }
-        }
-        for (key, value) in &run.audit.metadata {
-            template_vars.insert(key.clone(), value.clone());
-        }
-
-        let agent_context = serde_json::json!({
+        let mut agent_context = serde_json::json!({
             "envelope_id": run.identity.envelope_id.as_str(),
             "request_id": run.identity.request_id.as_str(),
             "user_id": run.identity.user_id.as_str(),
@@ -252,7 +661,6 @@ impl Kernel {
             "outputs": &run.outputs,
             "state": &run.state,
             "metadata": &run.audit.metadata,
-            "template_vars": serde_json::Value::Object(template_vars),
             "llm_call_count": run.metrics.llm_calls,
             "agent_hop_count": run.metrics.agent_hops,
             "tokens_in": run.metrics.tokens_in,
@@ -260,9 +668,46 @@ impl Kernel {
             "circuit_broken_tools": self.tools.health.get_circuit_broken_tools(),
         });
 
-        let stage_name = run.current_stage.clone();
-        let (max_context_tokens, context_overflow) = self.orchestrator
-            .get_stage_config(run_id, stage_name.as_str())
+        // Masks apply before template vars are derived so a hidden field
+        // can't leak back in through its flattened alias.
+        if let Some(sc) = stage_config {
+            field_mask::apply(&mut agent_context, sc.visible_fields.as_deref(), &sc.hidden_fields);
+        }
+
+        let inputs: serde_json::Map<String, serde_json::Value> = stage_config
+            .map(|sc| {
+                sc.inputs
+                    .iter()
+                    .map(|(name, path)| {
+                        let value = field_mask::lookup(&agent_context, path).cloned().unwrap_or_default();
+                        (name.clone(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut template_vars = serde_json::Map::new();
+        if let Some(outputs) = agent_context.get("outputs").and_then(|v| v.as_object()) {
+            for (agent_name, output) in outputs {
+                for (key, value) in output.as_object().into_iter().flatten() {
+                    template_vars.insert(format!("{}_{}", agent_name, key), value.clone());
+                }
+            }
+        }
+        if let Some(metadata) = agent_context.get("metadata").and_then(|v| v.as_object()) {
+            for (key, value) in metadata {
+                template_vars.insert(key.clone(), value.clone());
+            }
+        }
+        for (name, value) in &inputs {
+            template_vars.insert(name.clone(), value.clone());
+        }
+        if let Some(obj) = agent_context.as_object_mut() {
+            obj.insert("template_vars".to_string(), serde_json::Value::Object(template_vars));
+            obj.insert("inputs".to_string(), serde_json::Value::Object(inputs));
+        }
+
+        let (max_context_tokens, context_overflow) = stage_config
             .map(|sc| {
                 let overflow = if sc.max_context_tokens.is_some() {
                     Some(sc.context_overflow)
@@ -286,11 +731,12 @@ impl Kernel {
         session_id: SessionId,
         quota: Option<ResourceQuota>,
     ) -> Result<super::RunRecord> {
+        self.admit_active_run(&run_id)?;
         self.lifecycle.create(run_id, request_id, user_id, session_id, quota)
     }
 
     /// Check whether the run has exceeded its quota. Reads live counters from
-    /// `Run.metrics` + `Run.iteration`, the wall-clock elapsed from
+    /// `Run.metrics` + `Run.iteration`, the kernel-clock time elapsed since
     /// `RunRecord.started_at`, and bounds from `RunRecord.quota` — one source
     /// of truth per dimension.
     pub fn check_quota(&self, run_id: &RunId) -> Result<()> {
@@ -308,10 +754,10 @@ impl Kernel {
         Ok(())
     }
 
-    /// Snapshot of usage built from `Run.metrics` + elapsed wall-clock. The
+    /// Snapshot of usage built from `Run.metrics` + elapsed kernel time. The
     /// kernel doesn't store this — it's derived on demand by `check_quota` and
     /// `get_remaining_budget`.
-    fn usage_from_run(&self, run_id: &RunId, record: &super::RunRecord) -> super::ResourceUsage {
+    pub(crate) fn usage_from_run(&self, run_id: &RunId, record: &super::RunRecord) -> super::ResourceUsage {
         let run = self.runs.get(run_id);
         super::ResourceUsage {
             llm_calls: run.map_or(0, |r| r.metrics.llm_calls),
@@ -320,7 +766,7 @@ impl Kernel {
             iterations: run.map_or(0, |r| r.iteration),
             tokens_in: run.map_or(0, |r| r.metrics.tokens_in),
             tokens_out: run.map_or(0, |r| r.metrics.tokens_out),
-            elapsed_seconds: record.elapsed_seconds(),
+            elapsed_seconds: record.elapsed_seconds(self.clock.now()),
         }
     }
 
@@ -340,10 +786,34 @@ impl Kernel {
 
     /// Set a tool-confirmation interrupt on a run. The workflow loop
     /// suspends the stage; the consumer resolves via `resolve_run_interrupt`.
-    pub fn set_run_interrupt(&mut self, run_id: &RunId, interrupt: FlowInterrupt) -> Result<()> {
+    /// An interrupt identical to one still pending for the same request is
+    /// coalesced: that one is kept and its id returned. Fails with
+    /// `QuotaExceeded` past the `InterruptLimits`. `created_at` is stamped
+    /// from the kernel clock.
+    pub fn set_run_interrupt(&mut self, run_id: &RunId, mut interrupt: FlowInterrupt) -> Result<InterruptId> {
+        interrupt.created_at = self.clock.now();
+
+        if let Some(run) = self.runs.get(run_id) {
+            if let Some(existing) = self.interrupts.find_duplicate(&interrupt, &run.identity.request_id).cloned() {
+                tracing::debug!(run_id = %run_id, interrupt_id = %existing.id, "interrupt_coalesced");
+                let existing_id = existing.id.clone();
+                if run.interrupts.interrupt.as_ref().map(|i| &i.id) != Some(&existing_id) {
+                    if let Some(record) = self.lifecycle.get_mut(run_id) {
+                        record.pending_interrupt = Some(existing_id.clone());
+                    }
+                    self.interrupts.notify(run_id, &existing);
+                    if let Some(run) = self.runs.edit(run_id) {
+                        run.set_interrupt(existing);
+                    }
+                }
+                return Ok(existing_id);
+            }
+        }
+
         // Register in interrupt manager (so resolve_interrupt can find it by ID)
         let interrupt_id = interrupt.id.clone();
         if let Some(run) = self.runs.get(run_id) {
+            self.interrupts.admit(&interrupt, &run.identity.session_id)?;
             self.interrupts.register_flow_interrupt(
                 interrupt.clone(),
                 &run.identity.request_id,
@@ -355,31 +825,63 @@ impl Kernel {
 
         // Mark on the run record so resolve_interrupt can see it.
         if let Some(record) = self.lifecycle.get_mut(run_id) {
-            record.pending_interrupt = Some(interrupt_id);
+            record.pending_interrupt = Some(interrupt_id.clone());
         }
 
         // Set on run (get_next_instruction will see it → WaitInterrupt)
-        let run = self.runs.get_mut(run_id)
+        let run = self.runs.edit(run_id)
             .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
+        self.interrupts.notify(run_id, &interrupt);
         run.set_interrupt(interrupt);
-        Ok(())
+        Ok(interrupt_id)
+    }
+
+    /// Reply on `tx` with the run's pending interrupt, now if there is one,
+    /// otherwise as soon as `set_run_interrupt` raises one. The wait is
+    /// cancelled (sender dropped) if the run terminates first.
+    pub fn wait_for_interrupt(&mut self, run_id: &RunId, tx: tokio::sync::oneshot::Sender<Result<FlowInterrupt>>) {
+        let Some(run) = self.runs.get(run_id) else {
+            let _ = tx.send(Err(Error::not_found(format!("Run not found: {}", run_id))));
+            return;
+        };
+        match run.interrupts.interrupt.as_ref() {
+            Some(interrupt) => {
+                let _ = tx.send(Ok(interrupt.clone()));
+            }
+            None => self.interrupts.watch(run_id, tx),
+        }
     }
 
-    /// Resolve a pending interrupt and stash the response for the next agent dispatch.
+    /// Resolve a pending interrupt and stash the response for the next agent
+    /// dispatch. A response that fails the interrupt's `ResponseSpec` is
+    /// rejected with a validation error whose source is a `ResponseViolation`,
+    /// and the interrupt stays pending.
     pub fn resolve_run_interrupt(
         &mut self,
         run_id: &RunId,
         interrupt_id: &str,
         response: crate::run::InterruptResponse,
     ) -> Result<()> {
+        let guarded = response.clone();
+        self.guard_resolution(run_id, interrupt_id, &guarded, |kernel| kernel.apply_resolution(run_id, interrupt_id, response))
+    }
+
+    fn apply_resolution(
+        &mut self,
+        run_id: &RunId,
+        interrupt_id: &str,
+        response: crate::run::InterruptResponse,
+    ) -> Result<()> {
+        self.interrupts.check_response(interrupt_id, &response)?;
         let response_json = serde_json::to_value(&response).unwrap_or_default();
+        let recorded = response.clone();
         if !self.interrupts.resolve(interrupt_id, response) {
             return Err(Error::not_found(format!("Interrupt {} not found", interrupt_id)));
         }
 
-        if let Some(run) = self.runs.get_mut(run_id) {
+        if let Some(run) = self.runs.edit(run_id) {
             run.audit.metadata.insert("_interrupt_response".to_string(), response_json);
-            run.clear_interrupt();
+            run.resolve_interrupt(&recorded);
         }
         if let Some(record) = self.lifecycle.get_mut(run_id) {
             record.pending_interrupt = None;
@@ -387,32 +889,149 @@ impl Kernel {
         Ok(())
     }
 
+    /// Hand a run's pending interrupt to `to_user`. The hand-off is appended
+    /// to `FlowInterrupt.delegations` (and the history entry); the run's own
+    /// user is left as is, so the original requester stays on record. The
+    /// new owner is reported by `FlowInterrupt::delegated_owner`.
+    pub fn delegate_interrupt(
+        &mut self,
+        run_id: &RunId,
+        interrupt_id: &str,
+        to_user: &str,
+        note: Option<String>,
+    ) -> Result<FlowInterrupt> {
+        let to = UserId::from_string(to_user.to_string())
+            .map_err(|e| Error::validation(format!("Invalid delegate user: {}", e)))?;
+        let run = self.runs.edit(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
+        let interrupt = run.interrupts.interrupt.as_ref()
+            .filter(|i| i.id.as_str() == interrupt_id)
+            .ok_or_else(|| Error::not_found(format!("Interrupt {} not pending on run {}", interrupt_id, run_id)))?;
+        let from = interrupt.delegated_owner().unwrap_or(&run.identity.user_id).clone();
+        if from == to {
+            return Err(Error::validation(format!("Interrupt {} is already owned by {}", interrupt_id, to)));
+        }
+        let delegation = crate::run::InterruptDelegation { from, to, note, at: self.clock.now() };
+        tracing::info!(
+            run_id = %run_id,
+            interrupt_id,
+            from = %delegation.from,
+            to = %delegation.to,
+            "interrupt_delegated"
+        );
+        run.delegate_interrupt(interrupt_id, delegation.clone());
+        self.interrupts.delegate(interrupt_id, delegation);
+        run.interrupts.interrupt.clone()
+            .ok_or_else(|| Error::internal(format!("Interrupt {} vanished during delegation", interrupt_id)))
+    }
+
+    /// Record `payload` under `metadata["signals"][signal_name]` and, if the
+    /// run is waiting on an interrupt keyed to that signal, resolve it with
+    /// the payload. Returns whether a waiting interrupt was woken.
+    pub fn signal_run(
+        &mut self,
+        run_id: &RunId,
+        signal_name: &str,
+        payload: serde_json::Value,
+    ) -> Result<bool> {
+        let run = self.runs.edit(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
+
+        let signals = run.audit.metadata
+            .entry("signals".to_string())
+            .or_insert_with(|| serde_json::json!({}));
+        if !signals.is_object() {
+            *signals = serde_json::json!({});
+        }
+        if let Some(map) = signals.as_object_mut() {
+            map.insert(signal_name.to_string(), payload.clone());
+        }
+
+        let waiting_id = run.interrupts.interrupt.as_ref()
+            .filter(|i| i.await_signal.as_deref() == Some(signal_name))
+            .map(|i| i.id.as_str().to_string());
+        let Some(interrupt_id) = waiting_id else {
+            return Ok(false);
+        };
+
+        let response = crate::run::InterruptResponse {
+            text: None,
+            approved: None,
+            decision: Some(signal_name.to_string()),
+            data: Some(HashMap::from([("payload".to_string(), payload)])),
+            responder: None,
+            received_at: self.clock.now(),
+        };
+        self.resolve_run_interrupt(run_id, &interrupt_id, response)?;
+        Ok(true)
+    }
+
     /// Terminate a run and remove it from the kernel.
     pub fn terminate_run(&mut self, run_id: &RunId) -> Result<()> {
+        // Read usage and the terminal record while the lifecycle record
+        // exists, but log neither unless the termination goes through.
+        let workflow_usage = match (self.lifecycle.get(run_id), self.orchestrator.sessions.get(run_id)) {
+            (Some(record), Some(session)) => Some((session.workflow.name.clone(), self.usage_from_run(run_id, record))),
+            _ => None,
+        };
+        let reason = self.runs.get(run_id)
+            .map(|r| r.termination.as_ref().map_or(crate::run::TerminalReason::Completed, |t| t.reason));
+        let terminal = self.terminal_record(run_id, reason, None);
         self.lifecycle.terminate(run_id)?;
-        if let Some(run) = self.runs.get_mut(run_id) {
-            run.complete("Run terminated");
+        if let Some((workflow, usage)) = workflow_usage {
+            self.resources.record_workflow_usage(&workflow, usage);
+        }
+        if let Some(record) = terminal {
+            self.terminal_log.push(record);
+        }
+        let now = self.clock.now();
+        if let Some(run) = self.runs.edit(run_id) {
+            run.complete("Run terminated", now);
+        }
+        // Pending interrupts would otherwise count against `InterruptLimits`
+        // and match coalescing for good.
+        if let Some(run) = self.runs.remove(run_id) {
+            self.interrupts.take_for_request(&run.identity.request_id);
         }
-        self.runs.remove(run_id);
         self.orchestrator.cleanup_session(run_id);
+        self.interrupts.drop_watchers(run_id);
+        // A pending cancellation is left for `finish_cancel` to report.
+        self.stage_groups.forget(run_id);
         Ok(())
     }
 
-    /// Cleanup stale orchestration sessions and their runs.
-    /// Returns the count of sessions removed.
+    /// Cleanup stale orchestration sessions with their runs and records,
+    /// and cancellations left unfinished for as long. Returns the count of
+    /// sessions removed.
     pub fn cleanup_stale_sessions(&mut self, max_age_seconds: i64) -> usize {
+        let started = std::time::Instant::now();
         let removed = self.orchestrator.cleanup_stale_sessions(max_age_seconds);
         let count = removed.len();
         for run_id in &removed {
-            self.runs.remove(run_id);
+            self.record_terminal(run_id, None, Some("Stale session cleaned up".to_string()));
+            if let Some(run) = self.runs.remove(run_id) {
+                self.interrupts.take_for_request(&run.identity.request_id);
+            }
+            let _ = self.lifecycle.terminate(run_id);
+            self.stage_groups.forget(run_id);
+            self.interrupts.drop_watchers(run_id);
         }
+        // Cancellations nobody finished: the run is gone and the request is
+        // as old as a stale session.
+        let cutoff = self.clock.now() - chrono::TimeDelta::seconds(max_age_seconds);
+        let runs = &self.runs;
+        self.cancellations.retain(|run_id, pending| runs.contains_key(run_id) || pending.requested_at > cutoff);
+        self.perf.record("cleanup_stale_sessions", started.elapsed());
         count
     }
 
     /// Cleanup stale user usage entries.
     pub fn cleanup_stale_user_usage(&mut self, max_entries: usize) -> usize {
+        let started = std::time::Instant::now();
         let active_user_ids = self.lifecycle.get_active_user_ids();
-        self.resources.cleanup_stale_users(&active_user_ids, max_entries)
+        let removed = self.resources.cleanup_stale_users(&active_user_ids, max_entries);
+        self.perf.record("cleanup_stale_user_usage", started.elapsed());
+        removed
     }
 
     /// Get a full system status snapshot.
@@ -429,25 +1048,330 @@ impl Kernel {
             runs_total: total,
             runs_by_state: by_state,
             active_orchestration_sessions: orchestrator_sessions,
+            max_active_runs: self.lifecycle.max_active,
+            overloaded: self.lifecycle.max_active.is_some_and(|max| self.active_run_count() >= max),
+        }
+    }
+
+    /// Move unused LLM-call / tool-call / agent-hop budget from `from` to
+    /// `to`. Both runs must belong to the same user and session, and no more
+    /// than [`MAX_QUOTA_TRANSFER_FRACTION`](super::MAX_QUOTA_TRANSFER_FRACTION)
+    /// of the donor's remaining budget may move per dimension. Neither run
+    /// may be terminated, and a dimension either run leaves unset (`0`, no
+    /// limit) can't be moved. Adjusts `RunRecord.quota` and, where the run
+    /// is loaded, `Run.limits`; the transfer is appended to both runs'
+    /// `metadata["quota_transfers"]`.
+    pub fn transfer_quota(
+        &mut self,
+        from: &RunId,
+        to: &RunId,
+        amounts: super::QuotaTransfer,
+    ) -> Result<()> {
+        if from == to {
+            return Err(Error::validation("cannot transfer quota to the same run"));
+        }
+        if amounts.llm_calls < 0 || amounts.tool_calls < 0 || amounts.agent_hops < 0 {
+            return Err(Error::validation("quota transfer amounts must be non-negative"));
+        }
+        if amounts == super::QuotaTransfer::default() {
+            return Err(Error::validation("quota transfer moves nothing"));
+        }
+
+        let donor = self.lifecycle.get(from)
+            .ok_or_else(|| Error::not_found(format!("Run {} not found", from)))?;
+        let recipient = self.lifecycle.get(to)
+            .ok_or_else(|| Error::not_found(format!("Run {} not found", to)))?;
+        if donor.user_id != recipient.user_id || donor.session_id != recipient.session_id {
+            return Err(Error::policy_violation(format!(
+                "Runs {} and {} do not share a user and session",
+                from, to
+            )));
+        }
+        if let Some(run_id) = [from, to]
+            .into_iter()
+            .find(|id| self.runs.get(*id).is_some_and(|r| r.is_terminated()))
+        {
+            return Err(Error::validation(format!("Run {} is already terminated", run_id)));
+        }
+
+        let usage = self.usage_from_run(from, donor);
+        let left = donor.quota.remaining(&usage);
+        let checks = [
+            ("llm_calls", amounts.llm_calls, donor.quota.max_llm_calls, recipient.quota.max_llm_calls, left.llm_calls_remaining),
+            ("tool_calls", amounts.tool_calls, donor.quota.max_tool_calls, recipient.quota.max_tool_calls, left.tool_calls_remaining),
+            ("agent_hops", amounts.agent_hops, donor.quota.max_agent_hops, recipient.quota.max_agent_hops, left.agent_hops_remaining),
+        ];
+        for (name, amount, donor_limit, recipient_limit, remaining) in checks {
+            if amount > 0 && (donor_limit == 0 || recipient_limit == 0) {
+                return Err(Error::validation(format!(
+                    "Cannot transfer {} between runs {} and {}: unset (unlimited) on one of them",
+                    name, from, to
+                )));
+            }
+            let allowed = (remaining as f64 * super::MAX_QUOTA_TRANSFER_FRACTION) as i32;
+            if amount > allowed {
+                return Err(Error::quota_exceeded(format!(
+                    "Cannot transfer {} {} from run {}: at most {} of {} remaining may move",
+                    amount, name, from, allowed, remaining
+                )));
+            }
+        }
+
+        for (run_id, sign) in [(from, -1), (to, 1)] {
+            let shift = |limit: i32, amount: i32| if sign < 0 { limit.saturating_sub(amount) } else { limit.saturating_add(amount) };
+            if let Some(record) = self.lifecycle.get_mut(run_id) {
+                record.quota.max_llm_calls = shift(record.quota.max_llm_calls, amounts.llm_calls);
+                record.quota.max_tool_calls = shift(record.quota.max_tool_calls, amounts.tool_calls);
+                record.quota.max_agent_hops = shift(record.quota.max_agent_hops, amounts.agent_hops);
+            }
+            if let Some(run) = self.runs.edit(run_id) {
+                run.limits.max_llm_calls = shift(run.limits.max_llm_calls, amounts.llm_calls);
+                run.limits.max_agent_hops = shift(run.limits.max_agent_hops, amounts.agent_hops);
+                let entry = serde_json::json!({
+                    "from": from.as_str(),
+                    "to": to.as_str(),
+                    "llm_calls": amounts.llm_calls,
+                    "tool_calls": amounts.tool_calls,
+                    "agent_hops": amounts.agent_hops,
+                    "at": self.clock.now(),
+                });
+                let log = run.audit.metadata
+                    .entry("quota_transfers".to_string())
+                    .or_insert_with(|| serde_json::json!([]));
+                match log.as_array_mut() {
+                    Some(arr) => arr.push(entry),
+                    None => *log = serde_json::json!([entry]),
+                }
+            }
         }
+        tracing::info!(from = %from, to = %to, ?amounts, "quota_transferred");
+        Ok(())
+    }
+
+    /// Suggest a quota for `workflow_name` from the usage of its recently
+    /// terminated runs: p95 per dimension plus `headroom` (e.g. `0.2` for
+    /// 20%). Dimensions without samples fall back to the kernel default.
+    pub fn recommend_quota(&self, workflow_name: &str, headroom: f64) -> Result<super::QuotaRecommendation> {
+        self.resources
+            .recommend_quota(workflow_name, headroom, self.lifecycle.get_default_quota())
+            .ok_or_else(|| Error::not_found(format!("No usage samples for workflow {}", workflow_name)))
+    }
+
+    /// State `run_id` is in and when it entered it.
+    pub fn dwell(&self, run_id: &RunId) -> Option<(super::DwellState, chrono::DateTime<chrono::Utc>)> {
+        let record = self.lifecycle.get(run_id)?;
+        match record.state {
+            RunStatus::Ready => Some((super::DwellState::Ready, record.created_at)),
+            RunStatus::Running => {
+                let waiting_since = self.runs.get(run_id)
+                    .filter(|run| run.interrupts.is_pending())
+                    .and_then(|run| run.interrupts.interrupt.as_ref())
+                    .map(|i| i.created_at);
+                let heartbeat_at = self.orchestrator.sessions.get(run_id)
+                    .and_then(|s| s.heartbeat.as_ref())
+                    .map(|h| h.at);
+                let started = record.started_at.unwrap_or(record.created_at);
+                match waiting_since {
+                    Some(since) => Some((super::DwellState::Waiting, since)),
+                    None => Some((super::DwellState::Running, heartbeat_at.map_or(started, |at| at.max(started)))),
+                }
+            }
+            RunStatus::Terminated => None,
+        }
+    }
+
+    /// Liveness report from the worker running dispatch `dispatch_id`.
+    /// Restarts the run's `Running` dwell (so `find_stuck_runs` sees it as
+    /// alive) and keeps `progress` for `get_session_state`. Fails for a
+    /// dispatch that was already reported or superseded.
+    pub fn report_heartbeat(
+        &mut self,
+        run_id: &RunId,
+        dispatch_id: &str,
+        progress: Option<serde_json::Value>,
+    ) -> Result<super::AgentHeartbeat> {
+        if let Some(progress) = &progress {
+            let size = serde_json::to_vec(progress).map(|v| v.len()).unwrap_or(usize::MAX);
+            if size > super::MAX_HEARTBEAT_PROGRESS_BYTES {
+                return Err(Error::validation(format!(
+                    "Heartbeat progress is {} bytes (max {})",
+                    size, super::MAX_HEARTBEAT_PROGRESS_BYTES
+                )));
+            }
+        }
+        let now = self.clock.now();
+        let session = self.orchestrator.sessions.get_mut(run_id)
+            .ok_or_else(|| Error::not_found(format!("Unknown process: {}", run_id)))?;
+        if session.current_dispatch.as_deref() != Some(dispatch_id) {
+            return Err(Error::validation(format!(
+                "Dispatch {} is not outstanding for run {}",
+                dispatch_id, run_id
+            )));
+        }
+        let count = session.heartbeat.as_ref().map_or(0, |h| h.count) + 1;
+        let heartbeat = super::AgentHeartbeat {
+            dispatch_id: dispatch_id.to_string(),
+            progress,
+            at: now,
+            count,
+        };
+        session.heartbeat = Some(heartbeat.clone());
+        session.last_activity_at = now;
+        Ok(heartbeat)
+    }
+
+    /// Runs that have dwelt in their current state past `thresholds`,
+    /// longest first. Each one is also logged as `run_stuck`.
+    pub fn find_stuck_runs(&self, thresholds: &super::StuckThresholds) -> Vec<super::StuckRun> {
+        let now = self.clock.now();
+        let mut stuck: Vec<super::StuckRun> = self.lifecycle.records.keys()
+            .filter_map(|run_id| {
+                let (state, since) = self.dwell(run_id)?;
+                let limit = match state {
+                    super::DwellState::Ready => thresholds.ready,
+                    super::DwellState::Running => thresholds.running,
+                    super::DwellState::Waiting => thresholds.waiting,
+                }?;
+                let dwell = (now - since).to_std().ok()?;
+                (dwell > limit).then(|| super::StuckRun {
+                    run_id: run_id.clone(),
+                    state,
+                    since,
+                    dwell_seconds: dwell.as_secs_f64(),
+                })
+            })
+            .collect();
+        stuck.sort_by(|a, b| b.dwell_seconds.total_cmp(&a.dwell_seconds));
+        for run in &stuck {
+            tracing::warn!(run_id = %run.run_id, state = ?run.state, dwell_seconds = run.dwell_seconds, "run_stuck");
+        }
+        stuck
+    }
+
+    /// Live runs matching `query`, oldest first, one page at a time.
+    pub fn search_runs(&self, query: &super::RunQuery) -> super::RunSearchPage {
+        let mut matches: Vec<&super::RunRecord> = self.lifecycle.records.values()
+            .filter(|record| self.run_matches(record, query))
+            .collect();
+        matches.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.run_id.as_str().cmp(b.run_id.as_str())));
+        let total = matches.len();
+        let limit = if query.limit == 0 { super::DEFAULT_SEARCH_LIMIT } else { query.limit };
+        let runs: Vec<super::RunRecord> = matches.into_iter().skip(query.offset).take(limit).cloned().collect();
+        let end = query.offset.saturating_add(runs.len());
+        super::RunSearchPage {
+            runs,
+            total,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+
+    /// Apply a partial update to a live run on behalf of `caller` (a caller
+    /// type such as `"frontend"`), checked by `Run::apply_updates` against
+    /// the caller's `UpdatePolicy` and `base_revisions`. With
+    /// `expected_revision`, fails with `Error::Conflict` unless the run is
+    /// still at that `Run.revision`. Returns the new revision of each field
+    /// written.
+    pub fn update_run(
+        &mut self,
+        run_id: &RunId,
+        caller: &str,
+        updates: HashMap<String, serde_json::Value>,
+        base_revisions: &HashMap<String, u64>,
+        expected_revision: Option<u64>,
+    ) -> Result<HashMap<String, u64>> {
+        let current = self.runs.get(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?
+            .revision;
+        if let Some(expected) = expected_revision.filter(|&e| e != current) {
+            return Err(Error::conflict(
+                format!("Run {} is at revision {}, not {}", run_id, current, expected),
+                current,
+            ));
+        }
+        let policy = self.update_policies.get(caller);
+        let fields = self.runs[run_id].check_updates(&updates, policy, base_revisions)?;
+        if fields.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let run = self.runs.edit(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
+        run.apply_updates(updates, policy, base_revisions)
+    }
+
+    /// Merge `patch` into `metadata` of every live run matching `query`
+    /// (`offset` and `limit` are ignored), as `caller` through
+    /// `apply_updates`. Every match is checked first, so if any one
+    /// rejects the patch (or has already terminated) no run is written. With `dry_run` nothing is written. Returns the number of
+    /// runs patched, or that would be.
+    pub fn annotate_runs(
+        &mut self,
+        query: &super::RunQuery,
+        caller: &str,
+        patch: HashMap<String, serde_json::Value>,
+        dry_run: bool,
+    ) -> Result<usize> {
+        if patch.is_empty() {
+            return Err(Error::validation("Metadata patch must not be empty"));
+        }
+        if let Some(key) = patch.keys().find(|k| super::ANNOTATION_RESERVED_KEYS.contains(&k.as_str())) {
+            return Err(Error::validation(format!("Metadata key '{}' is kernel-managed", key)));
+        }
+        let matched: Vec<RunId> = self.lifecycle.records.values()
+            .filter(|record| self.runs.contains_key(&record.run_id) && self.run_matches(record, query))
+            .map(|record| record.run_id.clone())
+            .collect();
+        let updates = HashMap::from([("metadata".to_string(), serde_json::Value::Object(patch.into_iter().collect()))]);
+        let policy = self.update_policies.get(caller);
+        for run_id in &matched {
+            let run = &self.runs[run_id];
+            if run.is_terminated() {
+                return Err(Error::validation(format!("Run {} is already terminated", run_id)));
+            }
+            run.check_updates(&updates, policy, &HashMap::new())?;
+        }
+        let count = matched.len();
+        if dry_run {
+            return Ok(count);
+        }
+        for run_id in &matched {
+            if let Some(run) = self.runs.edit(run_id) {
+                run.apply_updates(updates.clone(), policy, &HashMap::new())?;
+            }
+        }
+        tracing::info!(runs = count, "runs_annotated");
+        Ok(count)
+    }
+
+    fn run_matches(&self, record: &super::RunRecord, query: &super::RunQuery) -> bool {
+        if query.user_id.as_ref().is_some_and(|u| u != &record.user_id)
+            || query.session_id.as_ref().is_some_and(|s| s != &record.session_id)
+            || query.external_ref.as_ref().is_some_and(|r| Some(r) != record.external_ref.as_ref())
+            || query.state.is_some_and(|s| s != record.state)
+            || query.created_after.is_some_and(|t| record.created_at <= t)
+            || query.created_before.is_some_and(|t| record.created_at >= t)
+        {
+            return false;
+        }
+        if let Some(workflow) = &query.workflow {
+            let name = self.orchestrator.sessions.get(&record.run_id).map(|s| s.workflow.name.as_str());
+            if name != Some(workflow.as_str()) {
+                return false;
+            }
+        }
+        let needs_run = !query.metadata.is_empty() || query.has_output_from.is_some() || query.terminal_reason.is_some();
+        if !needs_run {
+            return true;
+        }
+        let Some(run) = self.runs.get(&record.run_id) else { return false };
+        query.metadata.iter().all(|(k, v)| run.audit.metadata.get(k) == Some(v))
+            && query.has_output_from.as_deref().map_or(true, |agent| run.outputs.contains_key(agent))
+            && query.terminal_reason.map_or(true, |reason| run.terminal_reason() == Some(reason))
     }
 
     /// Get remaining resource budget for a run.
     pub fn get_remaining_budget(&self, run_id: &RunId) -> Option<RemainingBudget> {
         let record = self.lifecycle.get(run_id)?;
         let usage = self.usage_from_run(run_id, record);
-        Some(RemainingBudget {
-            llm_calls_remaining: (record.quota.max_llm_calls - usage.llm_calls).max(0),
-            iterations_remaining: (record.quota.max_iterations - usage.iterations).max(0),
-            agent_hops_remaining: (record.quota.max_agent_hops - usage.agent_hops).max(0),
-            tokens_in_remaining: (record.quota.max_input_tokens as i64 - usage.tokens_in).max(0),
-            tokens_out_remaining: (record.quota.max_output_tokens as i64 - usage.tokens_out).max(0),
-            time_remaining_seconds: if record.quota.timeout_seconds > 0 {
-                (record.quota.timeout_seconds as f64 - usage.elapsed_seconds).max(0.0)
-            } else {
-                f64::MAX
-            },
-        })
+        Some(record.quota.remaining(&usage))
     }
 }
-
@@ -0,0 +1,155 @@
+//! Per-stage field masks over the agent context.
+//!
+//! Paths are dot-separated and rooted at the agent context object, e.g.
+//! `outputs.search`, `metadata.api_key`, `raw_input`. `visible_fields`
+//! restricts the envelope data sections (`raw_input`, `outputs`, `state`,
+//! `metadata`) to the listed paths; identity and counter fields always pass.
+//! `hidden_fields` then removes paths anywhere in the context. Stage
+//! `inputs` resolve against the masked context with [`lookup`].
+
+use serde_json::{Map, Value};
+
+/// Top-level sections of the agent context that `visible_fields` governs.
+const DATA_SECTIONS: &[&str] = &["raw_input", "outputs", "state", "metadata"];
+
+/// Apply `visible` then `hidden` to `context` in place.
+pub(crate) fn apply(context: &mut Value, visible: Option<&[String]>, hidden: &[String]) {
+    let Value::Object(root) = context else { return };
+
+    if let Some(visible) = visible {
+        let paths: Vec<Vec<&str>> = visible.iter().map(|p| p.split('.').collect()).collect();
+        for section in DATA_SECTIONS {
+            let Some(value) = root.remove(*section) else { continue };
+            let subpaths: Vec<&[&str]> = paths
+                .iter()
+                .filter(|p| p.first() == Some(section))
+                .map(|p| &p[1..])
+                .collect();
+            if let Some(kept) = retain(value, &subpaths) {
+                root.insert((*section).to_string(), kept);
+            }
+        }
+    }
+
+    for path in hidden {
+        let segments: Vec<&str> = path.split('.').collect();
+        remove(root, &segments);
+    }
+}
+
+/// Keep only the parts of `value` reachable by `paths`. An empty path keeps
+/// the whole value; no paths keeps nothing.
+fn retain(value: Value, paths: &[&[&str]]) -> Option<Value> {
+    if paths.is_empty() {
+        return None;
+    }
+    if paths.iter().any(|p| p.is_empty()) {
+        return Some(value);
+    }
+    let Value::Object(map) = value else { return None };
+    let mut kept = Map::new();
+    for (key, child) in map {
+        let child_paths: Vec<&[&str]> = paths
+            .iter()
+            .filter(|p| p[0] == key)
+            .map(|p| &p[1..])
+            .collect();
+        if let Some(child) = retain(child, &child_paths) {
+            kept.insert(key, child);
+        }
+    }
+    Some(Value::Object(kept))
+}
+
+/// Value at dotted `path` in `context`. Numeric segments index arrays.
+pub(crate) fn lookup<'a>(context: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(context, |value, segment| match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// Whether `path` starts in one of the data sections stage inputs may bind.
+pub(crate) fn is_data_path(path: &str) -> bool {
+    path.split('.').next().is_some_and(|head| DATA_SECTIONS.contains(&head))
+}
+
+fn remove(map: &mut Map<String, Value>, path: &[&str]) {
+    match path {
+        [] => {}
+        [last] => {
+            map.remove(*last);
+        }
+        [head, rest @ ..] => {
+            if let Some(Value::Object(child)) = map.get_mut(*head) {
+                remove(child, rest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> Value {
+        json!({
+            "envelope_id": "env_1",
+            "raw_input": "hello",
+            "outputs": {
+                "search": {"results": [1, 2]},
+                "fetch": {"body": "...", "auth_header": "Bearer x"},
+            },
+            "state": {"notes": "n"},
+            "metadata": {"api_key": "secret", "locale": "en"},
+            "llm_call_count": 3,
+        })
+    }
+
+    #[test]
+    fn visible_fields_restrict_data_sections_only() {
+        let mut ctx = context();
+        let visible = vec!["outputs.search".to_string(), "metadata.locale".to_string()];
+        apply(&mut ctx, Some(&visible), &[]);
+
+        assert_eq!(ctx["outputs"], json!({"search": {"results": [1, 2]}}));
+        assert_eq!(ctx["metadata"], json!({"locale": "en"}));
+        assert!(ctx.get("raw_input").is_none());
+        assert!(ctx.get("state").is_none());
+        assert_eq!(ctx["envelope_id"], "env_1");
+        assert_eq!(ctx["llm_call_count"], 3);
+    }
+
+    #[test]
+    fn hidden_fields_remove_nested_paths() {
+        let mut ctx = context();
+        let hidden = vec!["metadata.api_key".to_string(), "outputs.fetch.auth_header".to_string()];
+        apply(&mut ctx, None, &hidden);
+
+        assert_eq!(ctx["metadata"], json!({"locale": "en"}));
+        assert_eq!(ctx["outputs"]["fetch"], json!({"body": "..."}));
+        assert_eq!(ctx["raw_input"], "hello");
+    }
+
+    #[test]
+    fn hidden_applies_after_visible() {
+        let mut ctx = context();
+        let visible = vec!["outputs".to_string()];
+        let hidden = vec!["outputs.fetch".to_string()];
+        apply(&mut ctx, Some(&visible), &hidden);
+
+        assert_eq!(ctx["outputs"], json!({"search": {"results": [1, 2]}}));
+    }
+
+    #[test]
+    fn lookup_follows_objects_and_array_indices() {
+        let ctx = context();
+        assert_eq!(lookup(&ctx, "outputs.search.results.1"), Some(&json!(2)));
+        assert_eq!(lookup(&ctx, "metadata.locale"), Some(&json!("en")));
+        assert_eq!(lookup(&ctx, "outputs.missing"), None);
+        assert!(is_data_path("state.notes"));
+        assert!(!is_data_path("envelope_id"));
+    }
+}
@@ -4,7 +4,7 @@
 use crate::agent::metrics::AgentExecutionMetrics;
 use crate::run::Run;
 use crate::kernel::protocol::{Instruction, RunSnapshot};
-use crate::kernel::{RunRecord, SystemStatus};
+use crate::kernel::{AgentHeartbeat, CancelOutcome, AgentQuarantine, CleanupStats, DiagnosticsReport, InterruptStats, KernelPerf, PipelineValidation, PostProcessorStats, ResolutionStats, ServerInfo, StuckRun, StuckThresholds, QuotaRecommendation, QuotaTransfer, ResourceQuota, RunExtension, RunQuery, RunRecord, RunSearchPage, StageGroupStats, SystemStatus, TerminalRecord};
 use crate::workflow::Workflow;
 use crate::types::{RunId, RequestId, Result, SessionId, UserId};
 use std::collections::HashMap;
@@ -21,6 +21,15 @@ pub(crate) enum KernelCommand {
         force: bool,
         resp_tx: oneshot::Sender<Result<RunSnapshot>>,
     },
+    /// Create the run record with an explicit quota and initialize the
+    /// session in one step.
+    StartRun {
+        run_id: RunId,
+        workflow: Box<Workflow>,
+        run: Box<Run>,
+        quota: Option<ResourceQuota>,
+        resp_tx: oneshot::Sender<Result<(RunRecord, RunSnapshot)>>,
+    },
     /// Get the next instruction for a run.
     GetNextInstruction {
         run_id: RunId,
@@ -36,8 +45,16 @@ pub(crate) enum KernelCommand {
         success: bool,
         error_message: String,
         break_loop: bool,
+        dispatch_id: Option<String>,
         resp_tx: oneshot::Sender<Result<()>>,
     },
+    /// Liveness report for an outstanding `RunAgent`.
+    ReportHeartbeat {
+        run_id: RunId,
+        dispatch_id: String,
+        progress: Option<serde_json::Value>,
+        resp_tx: oneshot::Sender<Result<AgentHeartbeat>>,
+    },
     /// Get orchestration session state.
     GetSessionState {
         run_id: RunId,
@@ -56,10 +73,109 @@ pub(crate) enum KernelCommand {
         run_id: RunId,
         resp_tx: oneshot::Sender<Result<()>>,
     },
+    /// Start cancelling a run; `ack_tx` fires when its worker acknowledges.
+    RequestCancel {
+        run_id: RunId,
+        reason: String,
+        ack_tx: oneshot::Sender<()>,
+        resp_tx: oneshot::Sender<Result<()>>,
+    },
+    /// Terminate a run whose cancellation grace period is over.
+    FinishCancel {
+        run_id: RunId,
+        resp_tx: oneshot::Sender<Result<CancelOutcome>>,
+    },
     /// Get system status.
     GetSystemStatus {
         resp_tx: oneshot::Sender<SystemStatus>,
     },
+    /// Runs dwelling in one state past the given thresholds.
+    FindStuckRuns {
+        thresholds: StuckThresholds,
+        resp_tx: oneshot::Sender<Result<Vec<StuckRun>>>,
+    },
+    /// Page of live runs matching a query.
+    SearchRuns {
+        query: RunQuery,
+        resp_tx: oneshot::Sender<Result<RunSearchPage>>,
+    },
+    /// Strict partial update of a live run.
+    UpdateRun {
+        run_id: RunId,
+        caller: String,
+        updates: HashMap<String, serde_json::Value>,
+        base_revisions: HashMap<String, u64>,
+        expected_revision: Option<u64>,
+        resp_tx: oneshot::Sender<Result<HashMap<String, u64>>>,
+    },
+    /// Merge a metadata patch into every run matching a query.
+    AnnotateRuns {
+        query: RunQuery,
+        caller: String,
+        patch: HashMap<String, serde_json::Value>,
+        dry_run: bool,
+        resp_tx: oneshot::Sender<Result<usize>>,
+    },
+    /// Pending and suppressed interrupt counts.
+    GetInterruptStats {
+        resp_tx: oneshot::Sender<Result<InterruptStats>>,
+    },
+    /// Failed interrupt-resolution counts and caps.
+    GetResolutionStats {
+        resp_tx: oneshot::Sender<Result<ResolutionStats>>,
+    },
+    /// Counters for each output post-processor.
+    GetPostProcessorStats {
+        resp_tx: oneshot::Sender<Result<Vec<PostProcessorStats>>>,
+    },
+    /// Records of terminated runs, newest first.
+    GetTerminalRecords {
+        user_id: Option<String>,
+        limit: usize,
+        resp_tx: oneshot::Sender<Result<Vec<TerminalRecord>>>,
+    },
+    /// Records of terminated runs after a sequence number, oldest first.
+    GetTerminalRecordsSince {
+        after_seq: u64,
+        limit: usize,
+        resp_tx: oneshot::Sender<Result<Vec<TerminalRecord>>>,
+    },
+    /// Quarantine or release an agent.
+    SetAgentQuarantine {
+        agent: String,
+        reason: Option<String>,
+        resp_tx: oneshot::Sender<Result<()>>,
+    },
+    /// Currently quarantined agents.
+    ListQuarantinedAgents {
+        resp_tx: oneshot::Sender<Result<Vec<AgentQuarantine>>>,
+    },
+    /// One cleanup and orphan-repair pass.
+    RunMaintenance {
+        resp_tx: oneshot::Sender<Result<CleanupStats>>,
+    },
+    /// Self-test on a scratch kernel configured like the live one.
+    RunDiagnostics {
+        resp_tx: oneshot::Sender<Result<DiagnosticsReport>>,
+    },
+    /// Dry-run workflow checks; creates nothing.
+    ValidatePipeline {
+        workflow: Box<Workflow>,
+        resp_tx: oneshot::Sender<Result<PipelineValidation>>,
+    },
+    /// Per-command latency counters.
+    GetKernelPerf {
+        resp_tx: oneshot::Sender<KernelPerf>,
+    },
+    /// Build, uptime, features and limits.
+    GetServerInfo {
+        resp_tx: oneshot::Sender<ServerInfo>,
+    },
+    /// Reply with the run's interrupt once one is pending.
+    WaitForInterrupt {
+        run_id: RunId,
+        resp_tx: oneshot::Sender<Result<crate::run::FlowInterrupt>>,
+    },
     /// Resolve a pending interrupt.
     ResolveInterrupt {
         run_id: RunId,
@@ -67,12 +183,40 @@ pub(crate) enum KernelCommand {
         response: crate::run::InterruptResponse,
         resp_tx: oneshot::Sender<Result<()>>,
     },
+    /// Hand a pending interrupt to another user.
+    DelegateInterrupt {
+        run_id: RunId,
+        interrupt_id: String,
+        to_user: String,
+        note: Option<String>,
+        resp_tx: oneshot::Sender<Result<crate::run::FlowInterrupt>>,
+    },
     /// Set an interrupt without a lifecycle transition (tool-confirmation gate).
     SetRunInterrupt {
         run_id: RunId,
-        interrupt: crate::run::FlowInterrupt,
+        interrupt: Box<crate::run::FlowInterrupt>,
+        resp_tx: oneshot::Sender<Result<crate::types::InterruptId>>,
+    },
+    /// Set or clear a workflow's concurrent-run cap.
+    SetWorkflowConcurrencyLimit {
+        workflow_name: String,
+        limit: Option<usize>,
         resp_tx: oneshot::Sender<Result<()>>,
     },
+    /// Store or remove an embedder extension on a run.
+    SetRunExtension {
+        run_id: RunId,
+        key: String,
+        extension: Option<RunExtension>,
+        resp_tx: oneshot::Sender<Result<()>>,
+    },
+    /// Deliver a named signal to a run.
+    SignalRun {
+        run_id: RunId,
+        signal_name: String,
+        payload: serde_json::Value,
+        resp_tx: oneshot::Sender<Result<bool>>,
+    },
 
     /// Single-tool or full-system health snapshot.
     GetToolHealth {
@@ -80,6 +224,32 @@ pub(crate) enum KernelCommand {
         resp_tx: oneshot::Sender<Result<serde_json::Value>>,
     },
 
+    /// Move unused budget between sibling runs.
+    TransferQuota {
+        from: RunId,
+        to: RunId,
+        amounts: QuotaTransfer,
+        resp_tx: oneshot::Sender<Result<()>>,
+    },
+
+    /// Define or resize a stage concurrency group.
+    DefineStageGroup {
+        name: String,
+        capacity: usize,
+        resp_tx: oneshot::Sender<Result<()>>,
+    },
+    /// Contention metrics for a stage concurrency group.
+    GetStageGroupStats {
+        name: String,
+        resp_tx: oneshot::Sender<Result<StageGroupStats>>,
+    },
+    /// Quota suggested from a workflow's recent usage.
+    RecommendQuota {
+        workflow_name: String,
+        headroom: f64,
+        resp_tx: oneshot::Sender<Result<QuotaRecommendation>>,
+    },
+
     RegisterRoutingFn {
         name: String,
         routing_fn: std::sync::Arc<dyn crate::kernel::routing::RoutingFn>,
@@ -87,27 +257,61 @@ pub(crate) enum KernelCommand {
     },
 }
 
+impl KernelCommand {
+    /// Variant name, used in `Debug` output and perf counters.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::InitializeSession { .. } => "InitializeSession",
+            Self::StartRun { .. } => "StartRun",
+            Self::GetNextInstruction { .. } => "GetNextInstruction",
+            Self::ProcessAgentResult { .. } => "ProcessAgentResult",
+            Self::ReportHeartbeat { .. } => "ReportHeartbeat",
+            Self::GetSessionState { .. } => "GetSessionState",
+            Self::CreateRun { .. } => "CreateRun",
+            Self::TerminateRun { .. } => "TerminateRun",
+            Self::RequestCancel { .. } => "RequestCancel",
+            Self::FinishCancel { .. } => "FinishCancel",
+            Self::GetSystemStatus { .. } => "GetSystemStatus",
+            Self::SearchRuns { .. } => "SearchRuns",
+            Self::UpdateRun { .. } => "UpdateRun",
+            Self::AnnotateRuns { .. } => "AnnotateRuns",
+            Self::GetInterruptStats { .. } => "GetInterruptStats",
+            Self::GetResolutionStats { .. } => "GetResolutionStats",
+            Self::GetPostProcessorStats { .. } => "GetPostProcessorStats",
+            Self::GetTerminalRecords { .. } => "GetTerminalRecords",
+            Self::GetTerminalRecordsSince { .. } => "GetTerminalRecordsSince",
+            Self::SetAgentQuarantine { .. } => "SetAgentQuarantine",
+            Self::ListQuarantinedAgents { .. } => "ListQuarantinedAgents",
+            Self::RunMaintenance { .. } => "RunMaintenance",
+            Self::RunDiagnostics { .. } => "RunDiagnostics",
+            Self::ValidatePipeline { .. } => "ValidatePipeline",
+            Self::GetKernelPerf { .. } => "GetKernelPerf",
+            Self::GetServerInfo { .. } => "GetServerInfo",
+            Self::FindStuckRuns { .. } => "FindStuckRuns",
+            Self::WaitForInterrupt { .. } => "WaitForInterrupt",
+            Self::ResolveInterrupt { .. } => "ResolveInterrupt",
+            Self::DelegateInterrupt { .. } => "DelegateInterrupt",
+            Self::SetRunInterrupt { .. } => "SetRunInterrupt",
+            Self::SetWorkflowConcurrencyLimit { .. } => "SetWorkflowConcurrencyLimit",
+            Self::SetRunExtension { .. } => "SetRunExtension",
+            Self::SignalRun { .. } => "SignalRun",
+            Self::GetToolHealth { .. } => "GetToolHealth",
+            Self::TransferQuota { .. } => "TransferQuota",
+            Self::DefineStageGroup { .. } => "DefineStageGroup",
+            Self::GetStageGroupStats { .. } => "GetStageGroupStats",
+            Self::RecommendQuota { .. } => "RecommendQuota",
+            Self::RegisterRoutingFn { .. } => "RegisterRoutingFn",
+        }
+    }
+}
+
 impl std::fmt::Debug for KernelCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::RegisterRoutingFn { name, .. } => {
                 f.debug_struct("RegisterRoutingFn").field("name", name).finish()
             }
-            other => {
-                write!(f, "KernelCommand::{}", match other {
-                    Self::InitializeSession { .. } => "InitializeSession",
-                    Self::GetNextInstruction { .. } => "GetNextInstruction",
-                    Self::ProcessAgentResult { .. } => "ProcessAgentResult",
-                    Self::GetSessionState { .. } => "GetSessionState",
-                    Self::CreateRun { .. } => "CreateRun",
-                    Self::TerminateRun { .. } => "TerminateRun",
-                    Self::GetSystemStatus { .. } => "GetSystemStatus",
-                    Self::ResolveInterrupt { .. } => "ResolveInterrupt",
-                    Self::SetRunInterrupt { .. } => "SetRunInterrupt",
-                    Self::GetToolHealth { .. } => "GetToolHealth",
-                    Self::RegisterRoutingFn { .. } => unreachable!(),
-                })
-            }
+            other => write!(f, "KernelCommand::{}", other.name()),
         }
     }
 }
@@ -116,6 +320,9 @@ impl std::fmt::Debug for KernelCommand {
 #[derive(Clone, Debug)]
 pub struct KernelHandle {
     tx: mpsc::Sender<KernelCommand>,
+    /// The kernel's clock, so callers wait against the same time the
+    /// kernel computed deadlines from.
+    clock: crate::kernel::SharedClock,
 }
 
 /// Send a KernelCommand and await the oneshot response.
@@ -137,8 +344,14 @@ impl KernelHandle {
     /// Create a new handle from a channel sender. `pub(crate)` because the
     /// channel half is internal; consumers obtain a `KernelHandle` via
     /// [`kernel::actor::spawn`](crate::kernel::actor::spawn).
-    pub(crate) fn new(tx: mpsc::Sender<KernelCommand>) -> Self {
-        Self { tx }
+    pub(crate) fn new(tx: mpsc::Sender<KernelCommand>, clock: crate::kernel::SharedClock) -> Self {
+        Self { tx, clock }
+    }
+
+    /// Current time on the kernel's clock. Compare `WaitWindow.until` and
+    /// interrupt expiries against this rather than `Utc::now()`.
+    pub fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.clock.now()
     }
 
     /// Register a named routing function on the kernel's orchestrator.
@@ -178,6 +391,24 @@ impl KernelHandle {
         })
     }
 
+    /// Create the run record and initialize its workflow session atomically.
+    /// Identity and raw input come from `run`; `quota = None` applies the
+    /// kernel default. Nothing is left registered if either step fails.
+    pub async fn start_run(
+        &self,
+        run_id: RunId,
+        workflow: Workflow,
+        run: Run,
+        quota: Option<ResourceQuota>,
+    ) -> Result<(RunRecord, RunSnapshot)> {
+        kernel_request!(self, StartRun {
+            run_id: run_id,
+            workflow: Box::new(workflow),
+            run: Box::new(run),
+            quota: quota,
+        })
+    }
+
     /// Get the next instruction for a run.
     pub async fn get_next_instruction(&self, run_id: &RunId) -> Result<Instruction> {
         kernel_request!(self, GetNextInstruction {
@@ -197,6 +428,7 @@ impl KernelHandle {
         success: bool,
         error_message: &str,
         break_loop: bool,
+        dispatch_id: Option<&str>,
     ) -> Result<()> {
         kernel_request!(self, ProcessAgentResult {
             run_id: run_id.clone(),
@@ -207,13 +439,23 @@ impl KernelHandle {
             success: success,
             error_message: error_message.to_string(),
             break_loop: break_loop,
+            dispatch_id: dispatch_id.map(str::to_string),
         })
     }
 
-    /// Get orchestration session state.
-    pub async fn get_session_state(&self, run_id: &RunId) -> Result<RunSnapshot> {
-        kernel_request!(self, GetSessionState {
+    /// Tell the kernel the agent for `dispatch_id` is still working, with an
+    /// optional progress hint. Call it periodically during long executions
+    /// so the run isn't reported stuck.
+    pub async fn report_heartbeat(
+        &self,
+        run_id: &RunId,
+        dispatch_id: &str,
+        progress: Option<serde_json::Value>,
+    ) -> Result<AgentHeartbeat> {
+        kernel_request!(self, ReportHeartbeat {
             run_id: run_id.clone(),
+            dispatch_id: dispatch_id.to_string(),
+            progress: progress,
         })
     }
 
@@ -240,18 +482,66 @@ impl KernelHandle {
         })
     }
 
+    /// Cancel a run gracefully. Its stage-group permit is freed and its worker is
+    /// told to stop on its next poll; after the worker acknowledges, or
+    /// `grace` passes, the run is terminated with `UserCancelled`.
+    pub async fn cancel_run(&self, run_id: &RunId, reason: &str, grace: std::time::Duration) -> Result<CancelOutcome> {
+        let (ack_tx, ack_rx) = oneshot::channel();
+        kernel_request!(self, RequestCancel {
+            run_id: run_id.clone(),
+            reason: reason.to_string(),
+            ack_tx: ack_tx,
+        })?;
+        // Either outcome ends the wait; `acknowledged` on the outcome says which.
+        let _ = tokio::time::timeout(grace, ack_rx).await;
+        kernel_request!(self, FinishCancel {
+            run_id: run_id.clone(),
+        })
+    }
+
     /// Set a pending interrupt on a run without a lifecycle transition.
     ///
     /// Used by the worker workflow loop for tool confirmation gates. Does NOT
-    /// change lifecycle state (run stays in its current state).
+    /// change lifecycle state (run stays in its current state). Returns the
+    /// id to resolve, which is an earlier interrupt's when this one
+    /// duplicates it.
     pub async fn set_run_interrupt(
         &self,
         run_id: &RunId,
         interrupt: crate::run::FlowInterrupt,
-    ) -> Result<()> {
+    ) -> Result<crate::types::InterruptId> {
         kernel_request!(self, SetRunInterrupt {
             run_id: run_id.clone(),
-            interrupt: interrupt,
+            interrupt: Box::new(interrupt),
+        })
+    }
+
+    /// Cap concurrently active runs of a workflow (by `Workflow.name`).
+    /// `start_run` / `initialize_session` beyond the cap fail with
+    /// `Error::QuotaExceeded`. `None` removes the cap.
+    pub async fn set_workflow_concurrency_limit(
+        &self,
+        workflow_name: impl Into<String>,
+        limit: Option<usize>,
+    ) -> Result<()> {
+        kernel_request!(self, SetWorkflowConcurrencyLimit {
+            workflow_name: workflow_name.into(),
+            limit: limit,
+        })
+    }
+
+    /// Store `extension` under `key` on this run, or remove the key with
+    /// `None`. Extensions appear in `get_session_state` snapshots.
+    pub async fn set_run_extension(
+        &self,
+        run_id: &RunId,
+        key: &str,
+        extension: Option<RunExtension>,
+    ) -> Result<()> {
+        kernel_request!(self, SetRunExtension {
+            run_id: run_id.clone(),
+            key: key.to_string(),
+            extension: extension,
         })
     }
 
@@ -269,6 +559,243 @@ impl KernelHandle {
         })
     }
 
+    /// Hand a run's pending interrupt to `to_user`, with an optional note.
+    /// The chain is kept on `FlowInterrupt.delegations`; the run's user is
+    /// unchanged. Returns the updated interrupt.
+    pub async fn delegate_interrupt(
+        &self,
+        run_id: &RunId,
+        interrupt_id: &str,
+        to_user: &str,
+        note: Option<String>,
+    ) -> Result<crate::run::FlowInterrupt> {
+        kernel_request!(self, DelegateInterrupt {
+            run_id: run_id.clone(),
+            interrupt_id: interrupt_id.to_string(),
+            to_user: to_user.to_string(),
+            note: note,
+        })
+    }
+
+    /// Deliver `signal_name` with `payload` to a run. The payload is kept in
+    /// `metadata["signals"]`; if the run is waiting on an interrupt built with
+    /// `FlowInterrupt::with_await_signal(signal_name)`, that interrupt is
+    /// resolved and the run resumes. Returns whether a wait was resolved.
+    pub async fn signal_run(
+        &self,
+        run_id: &RunId,
+        signal_name: &str,
+        payload: serde_json::Value,
+    ) -> Result<bool> {
+        kernel_request!(self, SignalRun {
+            run_id: run_id.clone(),
+            signal_name: signal_name.to_string(),
+            payload: payload,
+        })
+    }
+
+    /// Move unused LLM-call / tool-call / agent-hop budget from `from` to a
+    /// sibling run `to` in the same user session. See
+    /// [`Kernel::transfer_quota`](crate::kernel::Kernel::transfer_quota) for
+    /// the guardrails.
+    pub async fn transfer_quota(&self, from: &RunId, to: &RunId, amounts: QuotaTransfer) -> Result<()> {
+        kernel_request!(self, TransferQuota {
+            from: from.clone(),
+            to: to.clone(),
+            amounts: amounts,
+        })
+    }
+
+    /// Define a stage concurrency group with `capacity` permits, or
+    /// resize it.
+    pub async fn define_stage_group(&self, name: &str, capacity: usize) -> Result<()> {
+        kernel_request!(self, DefineStageGroup {
+            name: name.to_string(),
+            capacity: capacity,
+        })
+    }
+
+    /// Read-only view of this kernel for dashboards and analysts.
+    pub fn observer(&self) -> KernelObserver {
+        KernelObserver { tx: self.tx.clone() }
+    }
+
+    /// Get orchestration session state.
+    pub async fn get_session_state(&self, run_id: &RunId) -> Result<RunSnapshot> {
+        self.observer().get_session_state(run_id).await
+    }
+
+    /// `Some(name)` returns that tool's health report; `None` returns the
+    /// full-system report.
+    pub async fn get_tool_health(&self, tool_name: Option<&str>) -> Result<serde_json::Value> {
+        self.observer().get_tool_health(tool_name).await
+    }
+
+    /// Capacity, usage and contention counters for concurrency group `name`.
+    pub async fn get_stage_group_stats(&self, name: &str) -> Result<StageGroupStats> {
+        self.observer().get_stage_group_stats(name).await
+    }
+
+    /// Quota for `workflow_name` from the p95 of its recent runs' usage
+    /// plus `headroom`.
+    pub async fn recommend_quota(&self, workflow_name: &str, headroom: f64) -> Result<QuotaRecommendation> {
+        self.observer().recommend_quota(workflow_name, headroom).await
+    }
+
+    /// Get system status.
+    pub async fn get_system_status(&self) -> SystemStatus {
+        self.observer().get_system_status().await
+    }
+
+    /// Run the kernel self-test (see [`Kernel::run_diagnostics`](crate::kernel::Kernel::run_diagnostics)).
+    pub async fn run_diagnostics(&self) -> Result<DiagnosticsReport> {
+        self.observer().run_diagnostics().await
+    }
+
+    /// Clean up stale state and repair orphans now (see [`Kernel::run_maintenance`](crate::kernel::Kernel::run_maintenance)).
+    pub async fn run_maintenance(&self) -> Result<CleanupStats> {
+        kernel_request!(self, RunMaintenance {})
+    }
+
+    /// Check a workflow without starting it (see [`Kernel::validate_pipeline`](crate::kernel::Kernel::validate_pipeline)).
+    pub async fn validate_pipeline(&self, workflow: Workflow) -> Result<PipelineValidation> {
+        self.observer().validate_pipeline(workflow).await
+    }
+
+    /// Runs stuck in Ready, Running or Waiting past `thresholds`.
+    pub async fn find_stuck_runs(&self, thresholds: StuckThresholds) -> Result<Vec<StuckRun>> {
+        self.observer().find_stuck_runs(thresholds).await
+    }
+
+    /// Command latency percentiles and queue depth (see [`KernelPerf`]).
+    pub async fn get_kernel_perf(&self) -> Result<KernelPerf> {
+        self.observer().get_kernel_perf().await
+    }
+
+    /// Version, git hash, uptime, enabled features and limits (see [`ServerInfo`]).
+    pub async fn get_server_info(&self) -> Result<ServerInfo> {
+        self.observer().get_server_info().await
+    }
+
+    /// Pending interrupts, and those suppressed by `InterruptLimits`.
+    pub async fn interrupt_stats(&self) -> Result<InterruptStats> {
+        self.observer().interrupt_stats().await
+    }
+
+    /// Failed interrupt resolutions by kind, and interrupts capped by `ResolutionLimits`.
+    pub async fn resolution_stats(&self) -> Result<ResolutionStats> {
+        self.observer().resolution_stats().await
+    }
+
+    /// Applied / changed / failed counts for each output post-processor.
+    pub async fn post_processor_stats(&self) -> Result<Vec<PostProcessorStats>> {
+        self.observer().post_processor_stats().await
+    }
+
+    /// Up to `limit` terminated runs, newest first, optionally one user's.
+    /// Kept after the runs themselves are cleaned up.
+    pub async fn get_terminal_records(&self, user_id: Option<&str>, limit: usize) -> Result<Vec<TerminalRecord>> {
+        self.observer().get_terminal_records(user_id, limit).await
+    }
+
+    /// Up to `limit` terminated runs recorded after `after_seq`, oldest
+    /// first. Pass the last `seq` seen to page through them and persist.
+    pub async fn get_terminal_records_since(&self, after_seq: u64, limit: usize) -> Result<Vec<TerminalRecord>> {
+        self.observer().get_terminal_records_since(after_seq, limit).await
+    }
+
+    /// Page of live runs matching `query` (see [`RunQuery`]).
+    pub async fn search_runs(&self, query: RunQuery) -> Result<RunSearchPage> {
+        self.observer().search_runs(query).await
+    }
+
+    /// Apply `updates` (`raw_input`, `metadata`, `outputs`, or catch-all
+    /// metadata keys) to a live run as `caller`. Rejected whole, with an
+    /// `UpdateRejection` as the error source, if a key is outside the
+    /// caller's `UpdatePolicy`, a value is malformed, or a field in
+    /// `base_revisions` has since been written. With `expected_revision`,
+    /// fails with `Error::Conflict` (carrying the current revision) unless
+    /// the run is still at the `RunSnapshot.revision` the caller read.
+    /// Returns the new revision of each field written.
+    pub async fn update_run(
+        &self,
+        run_id: &RunId,
+        caller: &str,
+        updates: HashMap<String, serde_json::Value>,
+        base_revisions: HashMap<String, u64>,
+        expected_revision: Option<u64>,
+    ) -> Result<HashMap<String, u64>> {
+        kernel_request!(self, UpdateRun {
+            run_id: run_id.clone(),
+            caller: caller.to_string(),
+            updates: updates,
+            base_revisions: base_revisions,
+            expected_revision: expected_revision,
+        })
+    }
+
+    /// Merge `patch` into `metadata` of every live run matching `query`
+    /// (e.g. tag runs with `{"incident": "INC-1234"}` after the fact), as
+    /// `caller` under its `UpdatePolicy`. Kernel-managed keys are refused.
+    /// With `dry_run`, only counts. Returns the number of runs patched.
+    pub async fn annotate_runs(
+        &self,
+        query: RunQuery,
+        caller: &str,
+        patch: HashMap<String, serde_json::Value>,
+        dry_run: bool,
+    ) -> Result<usize> {
+        kernel_request!(self, AnnotateRuns {
+            query: query,
+            caller: caller.to_string(),
+            patch: patch,
+            dry_run: dry_run,
+        })
+    }
+
+    /// Bar `agent` from dispatch with `reason`, or release it with `None`.
+    /// Stages bound to a quarantined agent go to `error_next`, or pause on
+    /// an agent-review interrupt.
+    pub async fn set_agent_quarantine(&self, agent: &str, reason: Option<String>) -> Result<()> {
+        kernel_request!(self, SetAgentQuarantine {
+            agent: agent.to_string(),
+            reason: reason,
+        })
+    }
+
+    /// Currently quarantined agents, by name.
+    pub async fn list_quarantined_agents(&self) -> Result<Vec<AgentQuarantine>> {
+        self.observer().list_quarantined_agents().await
+    }
+
+    /// Long-poll for the run's pending interrupt (see
+    /// [`KernelObserver::wait_for_interrupt`]).
+    pub async fn wait_for_interrupt(
+        &self,
+        run_id: &RunId,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<Option<crate::run::FlowInterrupt>> {
+        self.observer().wait_for_interrupt(run_id, timeout).await
+    }
+}
+
+/// Query-only handle to the kernel actor. Obtained from
+/// [`KernelHandle::observer`]; exposes no method that mutates kernel state,
+/// so it can be handed to dashboards or analysis code without risk to live
+/// runs.
+#[derive(Clone, Debug)]
+pub struct KernelObserver {
+    tx: mpsc::Sender<KernelCommand>,
+}
+
+impl KernelObserver {
+    /// Get orchestration session state.
+    pub async fn get_session_state(&self, run_id: &RunId) -> Result<RunSnapshot> {
+        kernel_request!(self, GetSessionState {
+            run_id: run_id.clone(),
+        })
+    }
+
     /// `Some(name)` returns that tool's health report; `None` returns the
     /// full-system report.
     pub async fn get_tool_health(&self, tool_name: Option<&str>) -> Result<serde_json::Value> {
@@ -277,6 +804,22 @@ impl KernelHandle {
         })
     }
 
+    /// Capacity, usage and contention counters for concurrency group `name`.
+    pub async fn get_stage_group_stats(&self, name: &str) -> Result<StageGroupStats> {
+        kernel_request!(self, GetStageGroupStats {
+            name: name.to_string(),
+        })
+    }
+
+    /// Quota for `workflow_name` from the p95 of its recent runs' usage
+    /// plus `headroom`.
+    pub async fn recommend_quota(&self, workflow_name: &str, headroom: f64) -> Result<QuotaRecommendation> {
+        kernel_request!(self, RecommendQuota {
+            workflow_name: workflow_name.to_string(),
+            headroom: headroom,
+        })
+    }
+
     /// Get system status.
     pub async fn get_system_status(&self) -> SystemStatus {
         let (resp_tx, resp_rx) = oneshot::channel();
@@ -286,16 +829,132 @@ impl KernelHandle {
             .await
             .is_err()
         {
-            return SystemStatus {
-                runs_total: 0,
-                runs_by_state: Default::default(),
-                active_orchestration_sessions: 0,
-            };
+            return SystemStatus::default();
         }
-        resp_rx.await.unwrap_or(SystemStatus {
-            runs_total: 0,
-            runs_by_state: Default::default(),
-            active_orchestration_sessions: 0,
+        resp_rx.await.unwrap_or_default()
+    }
+
+    /// Run the kernel self-test (see [`Kernel::run_diagnostics`](crate::kernel::Kernel::run_diagnostics)).
+    pub async fn run_diagnostics(&self) -> Result<DiagnosticsReport> {
+        kernel_request!(self, RunDiagnostics {})
+    }
+
+    /// Check a workflow without starting it (see [`Kernel::validate_pipeline`](crate::kernel::Kernel::validate_pipeline)).
+    pub async fn validate_pipeline(&self, workflow: Workflow) -> Result<PipelineValidation> {
+        kernel_request!(self, ValidatePipeline { workflow: Box::new(workflow) })
+    }
+
+    /// Command latency percentiles and queue depth (see [`KernelPerf`]).
+    pub async fn get_kernel_perf(&self) -> Result<KernelPerf> {
+        let queue_depth = self.tx.max_capacity() - self.tx.capacity();
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.tx
+            .send(KernelCommand::GetKernelPerf { resp_tx })
+            .await
+            .map_err(|_| crate::types::Error::internal("Kernel actor unavailable"))?;
+        let mut perf = resp_rx
+            .await
+            .map_err(|_| crate::types::Error::internal("Kernel actor dropped response"))?;
+        perf.queue_depth = queue_depth;
+        perf.queue_capacity = self.tx.max_capacity();
+        Ok(perf)
+    }
+
+    /// Version, git hash, uptime, enabled features and limits (see [`ServerInfo`]).
+    pub async fn get_server_info(&self) -> Result<ServerInfo> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.tx
+            .send(KernelCommand::GetServerInfo { resp_tx })
+            .await
+            .map_err(|_| crate::types::Error::internal("Kernel actor unavailable"))?;
+        let mut info = resp_rx
+            .await
+            .map_err(|_| crate::types::Error::internal("Kernel actor dropped response"))?;
+        info.limits.command_queue_capacity = self.tx.max_capacity();
+        Ok(info)
+    }
+
+    /// Runs stuck in Ready, Running or Waiting past `thresholds`.
+    pub async fn find_stuck_runs(&self, thresholds: StuckThresholds) -> Result<Vec<StuckRun>> {
+        kernel_request!(self, FindStuckRuns {
+            thresholds: thresholds,
+        })
+    }
+
+    /// Pending interrupts, and those suppressed by `InterruptLimits`.
+    pub async fn interrupt_stats(&self) -> Result<InterruptStats> {
+        kernel_request!(self, GetInterruptStats {})
+    }
+
+    /// Failed interrupt resolutions by kind, and interrupts capped by `ResolutionLimits`.
+    pub async fn resolution_stats(&self) -> Result<ResolutionStats> {
+        kernel_request!(self, GetResolutionStats {})
+    }
+
+    /// Applied / changed / failed counts for each output post-processor.
+    pub async fn post_processor_stats(&self) -> Result<Vec<PostProcessorStats>> {
+        kernel_request!(self, GetPostProcessorStats {})
+    }
+
+    /// Up to `limit` terminated runs, newest first, optionally one user's.
+    pub async fn get_terminal_records(&self, user_id: Option<&str>, limit: usize) -> Result<Vec<TerminalRecord>> {
+        kernel_request!(self, GetTerminalRecords {
+            user_id: user_id.map(str::to_string),
+            limit: limit,
+        })
+    }
+
+    /// Up to `limit` terminated runs recorded after `after_seq`, oldest first.
+    pub async fn get_terminal_records_since(&self, after_seq: u64, limit: usize) -> Result<Vec<TerminalRecord>> {
+        kernel_request!(self, GetTerminalRecordsSince {
+            after_seq: after_seq,
+            limit: limit,
+        })
+    }
+
+    /// Currently quarantined agents, by name.
+    pub async fn list_quarantined_agents(&self) -> Result<Vec<AgentQuarantine>> {
+        kernel_request!(self, ListQuarantinedAgents {})
+    }
+
+    /// Page of live runs matching `query` (see [`RunQuery`]).
+    pub async fn search_runs(&self, query: RunQuery) -> Result<RunSearchPage> {
+        kernel_request!(self, SearchRuns {
+            query: query,
         })
     }
+
+    /// The run's pending interrupt, waiting for one to be raised if there
+    /// is none yet. With `timeout = Some(d)` returns `Ok(None)` after `d`
+    /// without one. Fails with `Error::Cancelled` if the run terminates
+    /// while waiting.
+    pub async fn wait_for_interrupt(
+        &self,
+        run_id: &RunId,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<Option<crate::run::FlowInterrupt>> {
+        let (resp_tx, mut resp_rx) = oneshot::channel();
+        self.tx
+            .send(KernelCommand::WaitForInterrupt {
+                run_id: run_id.clone(),
+                resp_tx,
+            })
+            .await
+            .map_err(|_| crate::types::Error::internal("Kernel actor unavailable"))?;
+
+        let cancelled = || crate::types::Error::cancelled(format!("Interrupt wait on {} cancelled", run_id));
+        let Some(limit) = timeout else {
+            return resp_rx.await.map_err(|_| cancelled())?.map(Some);
+        };
+        match tokio::time::timeout(limit, &mut resp_rx).await {
+            Ok(reply) => reply.map_err(|_| cancelled())?.map(Some),
+            Err(_elapsed) => {
+                resp_rx.close();
+                match resp_rx.try_recv() {
+                    Ok(reply) => reply.map(Some),
+                    Err(_) => Ok(None),
+                }
+            }
+        }
+    }
 }
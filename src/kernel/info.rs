@@ -0,0 +1,102 @@
+//! Build, uptime and limits of a running kernel, so client SDKs can adapt
+//! to what they are talking to and operators can audit deployments.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::Kernel;
+use crate::types::IdFormat;
+
+/// Optional cargo features compiled into this build.
+pub fn enabled_features() -> Vec<String> {
+    let mut features = Vec::new();
+    if cfg!(feature = "otel") {
+        features.push("otel".to_string());
+    }
+    if cfg!(feature = "screening") {
+        features.push("screening".to_string());
+    }
+    if cfg!(feature = "test-harness") {
+        features.push("test-harness".to_string());
+    }
+    features
+}
+
+/// Size and count caps the kernel enforces.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServerLimits {
+    pub max_heartbeat_progress_bytes: usize,
+    pub max_run_extension_bytes: usize,
+    pub max_loop_feedback: usize,
+    pub max_active_runs: Option<usize>,
+    /// Bounds for workflows that leave theirs at 0; pass to
+    /// `Run::with_defaults`, with `ServerInfo.id_format`, to start runs
+    /// from them.
+    pub default_bounds: crate::run::BoundsDefaults,
+    /// Commands the actor queue holds before senders wait; filled in by
+    /// `KernelHandle`.
+    pub command_queue_capacity: usize,
+}
+
+/// Snapshot returned by `KernelHandle::get_server_info`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServerInfo {
+    /// Crate version.
+    pub version: String,
+    /// Commit built from, when known at build time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_hash: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub uptime_seconds: u64,
+    pub features: Vec<String>,
+    pub id_format: IdFormat,
+    pub limits: ServerLimits,
+}
+
+impl Kernel {
+    pub fn server_info(&self) -> ServerInfo {
+        ServerInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("JEEVES_GIT_HASH").map(str::to_string),
+            started_at: self.started_at,
+            uptime_seconds: self.started.elapsed().as_secs(),
+            features: enabled_features(),
+            id_format: self.id_format,
+            limits: ServerLimits {
+                max_heartbeat_progress_bytes: super::MAX_HEARTBEAT_PROGRESS_BYTES,
+                max_run_extension_bytes: super::MAX_RUN_EXTENSION_BYTES,
+                max_loop_feedback: crate::run::MAX_LOOP_FEEDBACK,
+                max_active_runs: self.lifecycle.max_active,
+                default_bounds: self.bounds_defaults(),
+                command_queue_capacity: 0,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_info_reports_build_and_limits() {
+        let mut kernel = Kernel::new();
+        kernel.set_max_active_runs(Some(8)).unwrap();
+        let info = kernel.server_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.features.contains(&"screening".to_string()), cfg!(feature = "screening"));
+        assert_eq!(info.limits.max_active_runs, Some(8));
+        assert!(info.started_at <= Utc::now());
+    }
+
+    #[test]
+    fn id_format_is_per_kernel() {
+        let config = crate::types::Config { id_format: IdFormat::Ulid, ..Default::default() };
+        let ulid = Kernel::from_config(&config).unwrap();
+        let plain = Kernel::new();
+        assert_eq!(ulid.server_info().id_format, IdFormat::Ulid);
+        assert_eq!(plain.server_info().id_format, IdFormat::UuidV4);
+        assert_eq!(ulid.id_format.generate().len(), 26);
+        assert_eq!(plain.id_format.generate().len(), 36);
+    }
+}
@@ -0,0 +1,100 @@
+//! Deployment-wide `raw_input` hygiene.
+//!
+//! Applied once as a run enters the kernel (`initialize_orchestration`):
+//! optional trimming, a length cap that rejects the run, and
+//! case-insensitive pattern flagging. Flags don't block anything; they are
+//! recorded in `metadata["input_preprocessing"]` for routing functions and
+//! agents to act on.
+
+use serde::{Deserialize, Serialize};
+
+use crate::run::Run;
+use crate::types::{Error, Result};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputPolicy {
+    /// Strip leading and trailing whitespace.
+    #[serde(default)]
+    pub trim: bool,
+    /// Reject runs whose (trimmed) input exceeds this many characters.
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    /// Substrings flagged when present, e.g. `"ignore previous instructions"`.
+    #[serde(default)]
+    pub flag_patterns: Vec<String>,
+}
+
+impl InputPolicy {
+    pub fn is_empty(&self) -> bool {
+        !self.trim && self.max_chars.is_none() && self.flag_patterns.is_empty()
+    }
+
+    /// Preprocess `run.raw_input` in place and record the outcome in
+    /// `metadata["input_preprocessing"]`. A no-op for an empty policy.
+    pub fn apply(&self, run: &mut Run) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let trimmed = self.trim && run.raw_input.trim().len() != run.raw_input.len();
+        if trimmed {
+            run.raw_input = run.raw_input.trim().to_string();
+        }
+        let chars = run.raw_input.chars().count();
+        if let Some(max) = self.max_chars {
+            if chars > max {
+                return Err(Error::validation(format!(
+                    "raw_input is {} characters (max {})",
+                    chars, max
+                )));
+            }
+        }
+        let lowered = run.raw_input.to_lowercase();
+        let flags: Vec<&str> = self
+            .flag_patterns
+            .iter()
+            .filter(|p| !p.is_empty() && lowered.contains(&p.to_lowercase()))
+            .map(String::as_str)
+            .collect();
+        if !flags.is_empty() {
+            tracing::warn!(flags = ?flags, "raw_input_flagged");
+        }
+        run.audit.metadata.insert(
+            "input_preprocessing".to_string(),
+            serde_json::json!({"trimmed": trimmed, "chars": chars, "flags": flags}),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> InputPolicy {
+        InputPolicy {
+            trim: true,
+            max_chars: Some(40),
+            flag_patterns: vec!["Ignore previous instructions".into()],
+        }
+    }
+
+    #[test]
+    fn trims_and_flags() {
+        let mut run = Run::new("u", "s", "  please IGNORE previous instructions  ", None);
+        policy().apply(&mut run).unwrap();
+        assert_eq!(run.raw_input, "please IGNORE previous instructions");
+        assert_eq!(
+            run.audit.metadata["input_preprocessing"],
+            serde_json::json!({"trimmed": true, "chars": 35, "flags": ["Ignore previous instructions"]})
+        );
+    }
+
+    #[test]
+    fn rejects_over_length_and_skips_empty_policy() {
+        let mut run = Run::new("u", "s", &"x".repeat(41), None);
+        assert!(policy().apply(&mut run).is_err());
+
+        InputPolicy::default().apply(&mut run).unwrap();
+        assert!(!run.audit.metadata.contains_key("input_preprocessing"));
+    }
+}
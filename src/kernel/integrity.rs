@@ -0,0 +1,199 @@
+//! Cross-subsystem consistency checks.
+//!
+//! A run's state is spread over the run store, the lifecycle registry, the
+//! orchestrator's sessions, the interrupt service and stage groups.
+//! A host that rebuilds a kernel from its own storage, or a cleanup path
+//! that misses one of them, can leave references to runs that are gone, or
+//! a run whose session is gone. A lifecycle record with no run is not one
+//! of them: `create_run` makes such records on purpose and `initialize_run`
+//! adopts them later, so they count as live runs here.
+//! `Kernel::check_integrity` lists the issues; `Kernel::reconcile` drops
+//! them. The actor reconciles once when it starts, `run_maintenance`
+//! reconciles on every pass, and `run_diagnostics` reports the live check.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::Kernel;
+use crate::types::RunId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityIssueKind {
+    /// Run with no orchestration session; it can never be dispatched.
+    RunWithoutSession,
+    /// Orchestration session with no run.
+    SessionWithoutRun,
+    /// Pending interrupt whose request belongs to no live run.
+    InterruptWithoutRun,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntegrityIssue {
+    pub kind: IntegrityIssueKind,
+    /// The run the dangling state belongs to; unknown for an interrupt,
+    /// which only carries its request id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<RunId>,
+    /// Interrupt id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntegrityReport {
+    pub checked_at: DateTime<Utc>,
+    pub issues: Vec<IntegrityIssue>,
+    /// Whether `issues` were repaired (`reconcile`) or only found.
+    pub repaired: bool,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Kernel {
+    /// Find state that references runs which are gone, without changing it.
+    pub fn check_integrity(&self) -> IntegrityReport {
+        let issue = |kind, run_id: &RunId, detail: Option<String>| IntegrityIssue { kind, run_id: Some(run_id.clone()), detail };
+        let mut issues = Vec::new();
+
+        for run_id in self.runs.keys() {
+            if !self.orchestrator.sessions.contains_key(run_id) {
+                issues.push(issue(IntegrityIssueKind::RunWithoutSession, run_id, None));
+            }
+        }
+        for run_id in self.orchestrator.sessions.keys() {
+            if !self.runs.contains_key(run_id) {
+                issues.push(issue(IntegrityIssueKind::SessionWithoutRun, run_id, None));
+            }
+        }
+        // Record-only runs (`create_run` before `initialize_run`) are live.
+        for pending in self.interrupts.pending() {
+            let owned = self.runs.values().any(|r| r.identity.request_id == pending.request_id)
+                || self.lifecycle.records.values().any(|r| r.request_id == pending.request_id);
+            if !owned {
+                issues.push(IntegrityIssue {
+                    kind: IntegrityIssueKind::InterruptWithoutRun,
+                    run_id: None,
+                    detail: Some(pending.interrupt.id.to_string()),
+                });
+            }
+        }
+
+        issues.sort_by(|a, b| {
+            let key = |i: &IntegrityIssue| (i.run_id.as_ref().map(|r| r.as_str().to_string()), i.detail.clone());
+            key(a).cmp(&key(b))
+        });
+        IntegrityReport { checked_at: self.clock.now(), issues, repaired: false }
+    }
+
+    /// Drop every dangling reference `check_integrity` finds. Logs
+    /// `integrity_report` when anything was repaired.
+    pub fn reconcile(&mut self) -> IntegrityReport {
+        let issues = self.check_integrity().issues;
+        for issue in &issues {
+            self.repair(issue);
+        }
+        let report = IntegrityReport { checked_at: self.clock.now(), issues, repaired: true };
+        if !report.is_clean() {
+            tracing::warn!(
+                issues = report.issues.len(),
+                report = %serde_json::to_string(&report).unwrap_or_default(),
+                "integrity_report"
+            );
+        }
+        report
+    }
+
+    fn repair(&mut self, issue: &IntegrityIssue) {
+        match (issue.kind, &issue.run_id) {
+            (IntegrityIssueKind::InterruptWithoutRun, _) => {
+                if let Some(interrupt_id) = &issue.detail {
+                    self.interrupts.discard(interrupt_id);
+                }
+            }
+            (_, None) => {}
+            (IntegrityIssueKind::RunWithoutSession, Some(run_id)) => {
+                // Its interrupts and permits would only be flagged
+                // on the next pass; sweep them with the run.
+                self.record_terminal(run_id, None, Some("Orphaned run cleaned up".to_string()));
+                if let Some(run) = self.runs.remove(run_id) {
+                    self.interrupts.take_for_request(&run.identity.request_id);
+                }
+                let _ = self.lifecycle.terminate(run_id);
+                self.stage_groups.forget(run_id);
+                self.interrupts.drop_watchers(run_id);
+            }
+            (IntegrityIssueKind::SessionWithoutRun, Some(run_id)) => {
+                self.orchestrator.cleanup_session(run_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+
+    #[test]
+    fn reconcile_drops_dangling_state() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("dangling");
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+        // Runs created without a lifecycle record are legitimate.
+        let _ = kernel.initialize_orchestration(RunId::must("bare"), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false).unwrap();
+        assert!(kernel.check_integrity().is_clean());
+
+        // Simulate a host restoring sessions but not the run.
+        kernel.runs.remove(&run_id);
+        kernel.lifecycle.records.remove(&run_id);
+        let found = kernel.check_integrity();
+        assert!(!found.repaired);
+        let kinds: Vec<_> = found.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(kinds, vec![IntegrityIssueKind::SessionWithoutRun]);
+        assert!(!kernel.run_diagnostics().healthy);
+
+        let report = kernel.reconcile();
+        assert_eq!(report.issues.len(), 1);
+        assert!(kernel.check_integrity().is_clean());
+        assert!(kernel.runs.contains_key(&RunId::must("bare")));
+    }
+
+    #[test]
+    fn record_only_run_is_left_alone() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("reserved");
+        let run = test_helpers::create_test_run();
+        kernel.create_run(run_id.clone(), run.identity.request_id.clone(), run.identity.user_id.clone(), run.identity.session_id.clone(), None).unwrap();
+
+        assert!(kernel.reconcile().is_clean());
+        assert!(kernel.lifecycle.get(&run_id).is_some());
+        // `initialize_run` still adopts the record.
+        assert!(kernel.initialize_run(run_id, test_helpers::create_test_workflow(), run, false, None).is_ok());
+    }
+
+    #[test]
+    fn run_without_session_is_dropped_with_its_dependents() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("sessionless");
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+        kernel.define_stage_group("gpu", 1).unwrap();
+        assert!(kernel.stage_groups.acquire("gpu", &run_id).unwrap());
+        kernel.set_run_interrupt(&run_id, crate::run::FlowInterrupt::new()).unwrap();
+        kernel.orchestrator.cleanup_session(&run_id);
+
+        let report = kernel.reconcile();
+        assert_eq!(report.issues.iter().map(|i| i.kind).collect::<Vec<_>>(), vec![IntegrityIssueKind::RunWithoutSession]);
+        assert!(!kernel.runs.contains_key(&run_id));
+        assert!(kernel.lifecycle.get(&run_id).is_none());
+        assert_eq!(kernel.terminal_records(None, 1)[0].terminal_message.as_deref(), Some("Orphaned run cleaned up"));
+        // Dependents went in the same pass, not the next one.
+        assert_eq!(kernel.get_stage_group_stats("gpu").unwrap().in_use, 0);
+        assert_eq!(kernel.interrupts.pending_count(), 0);
+        assert!(kernel.check_integrity().is_clean());
+    }
+}
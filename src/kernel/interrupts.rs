@@ -5,31 +5,137 @@
 //! confirmation and to thread the response back into the next agent dispatch.
 
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use tokio::sync::oneshot;
 
-use crate::run::{FlowInterrupt, InterruptResponse};
-use crate::types::{EnvelopeId, InterruptId, RequestId, SessionId, UserId};
+use super::clock::{self, SharedClock};
+use crate::run::{FlowInterrupt, InterruptDelegation, InterruptResponse, ResponseSpec};
+use crate::types::{EnvelopeId, InterruptId, RequestId, RunId, SessionId, UserId};
+
+/// Resolution times kept for `poll_after_ms` hints.
+pub const RESOLUTION_SAMPLE_WINDOW: usize = 50;
+/// Hint used before any interrupt has been resolved.
+pub const DEFAULT_POLL_MS: u64 = 1_000;
+pub const MIN_POLL_MS: u64 = 250;
+pub const MAX_POLL_MS: u64 = 30_000;
+
+/// What an interrupt asks for, read off its fields: an `await_signal`
+/// makes it a `Signal`, else a `question` a `Question`, else a `Message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptKind {
+    Question,
+    Message,
+    Signal,
+}
+
+impl InterruptKind {
+    pub fn of(interrupt: &FlowInterrupt) -> Self {
+        if interrupt.await_signal.is_some() {
+            Self::Signal
+        } else if interrupt.question.is_some() {
+            Self::Question
+        } else {
+            Self::Message
+        }
+    }
+}
+
+/// The spec a response to `interrupt` is checked against: its own
+/// `response_spec`, else `require_response` for a question.
+pub fn response_spec(interrupt: &FlowInterrupt) -> ResponseSpec {
+    match &interrupt.response_spec {
+        Some(spec) => spec.clone(),
+        None => ResponseSpec {
+            require_response: InterruptKind::of(interrupt) == InterruptKind::Question,
+            ..ResponseSpec::default()
+        },
+    }
+}
+
+/// Hash of what an interrupt asks: kind, question, message, awaited signal
+/// and data. Two interrupts with the same hash are duplicates.
+pub fn content_hash(interrupt: &FlowInterrupt) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    InterruptKind::of(interrupt).hash(&mut hasher);
+    interrupt.question.hash(&mut hasher);
+    interrupt.message.hash(&mut hasher);
+    interrupt.await_signal.hash(&mut hasher);
+    let data: Option<BTreeMap<&String, String>> = interrupt.data.as_ref()
+        .map(|d| d.iter().map(|(k, v)| (k, v.to_string())).collect());
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Caps on pending interrupts. Raising one past a cap fails with
+/// `QuotaExceeded` and counts as suppressed. `None` / absent = no cap.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InterruptLimits {
+    /// Across the whole kernel.
+    #[serde(default)]
+    pub max_pending: Option<usize>,
+    /// Per session.
+    #[serde(default)]
+    pub max_pending_per_session: Option<usize>,
+    /// Per session, for one kind; checked alongside `max_pending_per_session`.
+    #[serde(default)]
+    pub max_pending_per_session_by_kind: HashMap<InterruptKind, usize>,
+}
+
+/// Pending and suppressed interrupt counts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InterruptStats {
+    pub pending: usize,
+    /// Interrupts rejected by `InterruptLimits`, by kind.
+    pub suppressed: HashMap<InterruptKind, u64>,
+}
 
 /// Lightweight bookkeeping for a pending interrupt.
 #[derive(Debug, Clone)]
 pub struct PendingInterrupt {
     pub interrupt: FlowInterrupt,
     pub request_id: RequestId,
+    /// Current owner: the run's user until the interrupt is delegated.
     pub user_id: UserId,
     pub session_id: SessionId,
     pub envelope_id: EnvelopeId,
     pub registered_at: DateTime<Utc>,
+    /// [`content_hash`] of `interrupt`.
+    pub content_hash: u64,
 }
 
 /// Lightweight registry: pending interrupts by id + resolved responses.
 ///
-/// Held by `Kernel` and accessed via `&mut self`. No state machine, no TTL,
-/// no kind discrimination — `FlowInterrupt` self-describes via its
-/// `message` / `question` / `data` fields.
-#[derive(Debug, Default)]
+/// Held by `Kernel` and accessed via `&mut self`. No state machine, no TTL.
+/// `FlowInterrupt` self-describes via its `message` / `question` / `data`
+/// fields; [`InterruptKind`] is derived from them only for the limits.
+#[derive(Debug)]
 pub struct InterruptService {
     pending: HashMap<InterruptId, PendingInterrupt>,
     resolved: HashMap<InterruptId, InterruptResponse>,
+    /// Milliseconds from registration to resolution, most recent last.
+    resolution_ms: VecDeque<i64>,
+    /// Long-poll callers parked until their run raises an interrupt.
+    watchers: HashMap<RunId, Vec<oneshot::Sender<crate::types::Result<FlowInterrupt>>>>,
+    pub(crate) limits: InterruptLimits,
+    suppressed: HashMap<InterruptKind, u64>,
+    clock: SharedClock,
+}
+
+impl Default for InterruptService {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+            resolved: HashMap::new(),
+            resolution_ms: VecDeque::new(),
+            watchers: HashMap::new(),
+            limits: InterruptLimits::default(),
+            suppressed: HashMap::new(),
+            clock: clock::system(),
+        }
+    }
 }
 
 impl InterruptService {
@@ -37,6 +143,10 @@ impl InterruptService {
         Self::default()
     }
 
+    pub(crate) fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
     /// Register a `FlowInterrupt` so it can be looked up + resolved by id.
     pub fn register_flow_interrupt(
         &mut self,
@@ -50,24 +160,72 @@ impl InterruptService {
         self.pending.insert(
             id,
             PendingInterrupt {
+                content_hash: content_hash(&interrupt),
                 interrupt,
                 request_id: request_id.clone(),
                 user_id: user_id.clone(),
                 session_id: session_id.clone(),
                 envelope_id: envelope_id.clone(),
-                registered_at: Utc::now(),
+                registered_at: self.clock.now(),
             },
         );
     }
 
+    /// A pending interrupt for `request_id` with the same content as
+    /// `interrupt`, if one exists.
+    pub fn find_duplicate(&self, interrupt: &FlowInterrupt, request_id: &RequestId) -> Option<&FlowInterrupt> {
+        let hash = content_hash(interrupt);
+        self.pending.values()
+            .filter(|p| &p.request_id == request_id && p.content_hash == hash)
+            .max_by_key(|p| p.registered_at)
+            .map(|p| &p.interrupt)
+    }
+
+    /// Check `interrupt` against the limits before it is registered for
+    /// `session_id`. A rejection is counted as suppressed.
+    pub fn admit(&mut self, interrupt: &FlowInterrupt, session_id: &SessionId) -> crate::types::Result<()> {
+        self.admit_kind(InterruptKind::of(interrupt), session_id)
+    }
+
+    /// As [`admit`](Self::admit), for an interrupt of `kind` not built yet.
+    pub fn admit_kind(&mut self, kind: InterruptKind, session_id: &SessionId) -> crate::types::Result<()> {
+        let in_session = || self.pending.values().filter(|p| &p.session_id == session_id);
+        let exceeded = if self.limits.max_pending.is_some_and(|max| self.pending.len() >= max) {
+            Some(format!("{} interrupts pending", self.pending.len()))
+        } else if self.limits.max_pending_per_session.is_some_and(|max| in_session().count() >= max) {
+            Some(format!("{} interrupts pending in session {}", in_session().count(), session_id))
+        } else {
+            self.limits.max_pending_per_session_by_kind.get(&kind)
+                .filter(|&&max| in_session().filter(|p| InterruptKind::of(&p.interrupt) == kind).count() >= max)
+                .map(|max| format!("{} {:?} interrupts pending in session {}", max, kind, session_id))
+        };
+        match exceeded {
+            Some(reason) => {
+                *self.suppressed.entry(kind).or_default() += 1;
+                tracing::warn!(session_id = %session_id, kind = ?kind, reason = %reason, "interrupt_suppressed");
+                Err(crate::types::Error::quota_exceeded(format!("Interrupt limit reached: {}", reason)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn stats(&self) -> InterruptStats {
+        InterruptStats { pending: self.pending.len(), suppressed: self.suppressed.clone() }
+    }
+
     /// Resolve a pending interrupt with the consumer's response.
-    /// Returns true if `interrupt_id` was registered.
+    /// Returns true if `interrupt_id` was registered. The response is not
+    /// checked here; see [`InterruptService::check_response`].
     pub fn resolve(
         &mut self,
         interrupt_id: &str,
         response: InterruptResponse,
     ) -> bool {
-        if self.pending.remove(interrupt_id).is_some() {
+        if let Some(pending) = self.pending.remove(interrupt_id) {
+            if self.resolution_ms.len() == RESOLUTION_SAMPLE_WINDOW {
+                self.resolution_ms.pop_front();
+            }
+            self.resolution_ms.push_back((self.clock.now() - pending.registered_at).num_milliseconds().max(0));
             self.resolved.insert(InterruptId::must(interrupt_id), response);
             true
         } else {
@@ -75,6 +233,32 @@ impl InterruptService {
         }
     }
 
+    /// Check `response` against the pending interrupt's [`response_spec`].
+    /// Unknown ids pass; `resolve` reports them.
+    pub fn check_response(&self, interrupt_id: &str, response: &InterruptResponse) -> crate::types::Result<()> {
+        let Some(pending) = self.pending.get(interrupt_id) else {
+            return Ok(());
+        };
+        match response_spec(&pending.interrupt).check(response) {
+            Some(violation) => Err(crate::types::Error::validation_with_source(
+                format!("Response to interrupt {} rejected: {}", interrupt_id, violation),
+                violation,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Hand a pending interrupt to `delegation.to`. Returns true if
+    /// `interrupt_id` was pending.
+    pub fn delegate(&mut self, interrupt_id: &str, delegation: InterruptDelegation) -> bool {
+        let Some(pending) = self.pending.get_mut(interrupt_id) else {
+            return false;
+        };
+        pending.user_id = delegation.to.clone();
+        pending.interrupt.delegations.push(delegation);
+        true
+    }
+
     /// Look up a pending interrupt by id.
     pub fn get_pending(&self, interrupt_id: &str) -> Option<&PendingInterrupt> {
         self.pending.get(interrupt_id)
@@ -85,10 +269,63 @@ impl InterruptService {
         self.resolved.get(interrupt_id)
     }
 
+    /// Median time recent interrupts took to resolve, if any were.
+    pub fn median_resolution_ms(&self) -> Option<i64> {
+        let mut samples: Vec<i64> = self.resolution_ms.iter().copied().collect();
+        samples.sort_unstable();
+        samples.get(samples.len() / 2).copied()
+    }
+
     /// Number of currently pending interrupts.
     pub fn pending_count(&self) -> usize {
         self.pending.len()
     }
+
+    /// All pending interrupts, in no particular order.
+    pub fn pending(&self) -> impl Iterator<Item = &PendingInterrupt> {
+        self.pending.values()
+    }
+
+    /// Drop a pending interrupt without recording a response.
+    pub fn discard(&mut self, interrupt_id: &str) -> bool {
+        self.pending.remove(interrupt_id).is_some()
+    }
+
+    /// Remove and return the pending interrupts of `request_id`, for a run
+    /// being dropped.
+    pub fn take_for_request(&mut self, request_id: &RequestId) -> Vec<PendingInterrupt> {
+        let ids: Vec<InterruptId> = self.pending.values()
+            .filter(|p| &p.request_id == request_id)
+            .map(|p| p.interrupt.id.clone())
+            .collect();
+        ids.iter().filter_map(|id| self.pending.remove(id)).collect()
+    }
+
+    /// Park `tx` until `run_id` raises an interrupt. Watchers whose caller
+    /// already gave up are pruned here.
+    pub fn watch(&mut self, run_id: &RunId, tx: oneshot::Sender<crate::types::Result<FlowInterrupt>>) {
+        let watchers = self.watchers.entry(run_id.clone()).or_default();
+        watchers.retain(|w| !w.is_closed());
+        watchers.push(tx);
+    }
+
+    /// Hand `interrupt` to every watcher on `run_id`.
+    pub fn notify(&mut self, run_id: &RunId, interrupt: &FlowInterrupt) {
+        for tx in self.watchers.remove(run_id).unwrap_or_default() {
+            let _ = tx.send(Ok(interrupt.clone()));
+        }
+    }
+
+    /// Drop the watchers on a run that is going away; their callers see
+    /// the wait cancelled.
+    pub fn drop_watchers(&mut self, run_id: &RunId) {
+        self.watchers.remove(run_id);
+    }
+
+    /// Number of parked watchers on `run_id`.
+    pub fn watcher_count(&self, run_id: &RunId) -> usize {
+        self.watchers.get(run_id).map_or(0, |w| w.iter().filter(|tx| !tx.is_closed()).count())
+    }
 }
 
 #[cfg(test)]
@@ -105,10 +342,22 @@ mod tests {
             approved: Some(true),
             decision: None,
             data: None,
+            responder: None,
             received_at: chrono::Utc::now(),
         }
     }
 
+    #[test]
+    fn median_resolution_tracks_resolved_interrupts() {
+        let mut svc = InterruptService::new();
+        assert_eq!(svc.median_resolution_ms(), None);
+        let interrupt = FlowInterrupt::new();
+        let id = interrupt.id.as_str().to_string();
+        svc.register_flow_interrupt(interrupt, &RequestId::must("r"), &UserId::must("u"), &SessionId::must("s"), &EnvelopeId::must("e"));
+        assert!(svc.resolve(&id, make_response()));
+        assert!(svc.median_resolution_ms().is_some_and(|ms| ms >= 0));
+    }
+
     #[test]
     fn register_and_resolve_round_trip() {
         let mut svc = InterruptService::new();
@@ -130,6 +379,68 @@ mod tests {
         assert!(svc.get_response(id.as_str()).is_some());
     }
 
+    #[test]
+    fn watchers_are_notified_or_dropped() {
+        let mut svc = InterruptService::new();
+        let (run_a, run_b) = (RunId::must("a"), RunId::must("b"));
+        let (tx_a, mut rx_a) = oneshot::channel();
+        let (tx_b, mut rx_b) = oneshot::channel();
+        svc.watch(&run_a, tx_a);
+        svc.watch(&run_b, tx_b);
+        assert_eq!(svc.watcher_count(&run_a), 1);
+
+        let interrupt = make_interrupt();
+        svc.notify(&run_a, &interrupt);
+        assert_eq!(rx_a.try_recv().unwrap().unwrap().id, interrupt.id);
+        assert_eq!(svc.watcher_count(&run_a), 0);
+
+        svc.drop_watchers(&run_b);
+        assert!(rx_b.try_recv().is_err());
+    }
+
+    #[test]
+    fn duplicates_are_found_per_request() {
+        let mut svc = InterruptService::new();
+        let data = HashMap::from([("a".to_string(), serde_json::json!(1)), ("b".to_string(), serde_json::json!([2]))]);
+        let original = FlowInterrupt::new().with_question("which file?".into()).with_data(data.clone());
+        let id = original.id.clone();
+        svc.register_flow_interrupt(original, &RequestId::must("r1"), &UserId::must("u"), &SessionId::must("s"), &EnvelopeId::must("e"));
+
+        let again = FlowInterrupt::new().with_question("which file?".into()).with_data(data);
+        assert_eq!(svc.find_duplicate(&again, &RequestId::must("r1")).map(|i| i.id.clone()), Some(id));
+        assert!(svc.find_duplicate(&again, &RequestId::must("r2")).is_none());
+        let other = FlowInterrupt::new().with_question("which file?".into());
+        assert!(svc.find_duplicate(&other, &RequestId::must("r1")).is_none());
+    }
+
+    #[test]
+    fn limits_reject_and_count_suppressed() {
+        let mut svc = InterruptService::new();
+        svc.limits = InterruptLimits {
+            max_pending: Some(3),
+            max_pending_per_session: Some(2),
+            max_pending_per_session_by_kind: HashMap::from([(InterruptKind::Question, 1)]),
+        };
+        let register = |svc: &mut InterruptService, interrupt: FlowInterrupt, session: &str| {
+            svc.admit(&interrupt, &SessionId::must(session))?;
+            svc.register_flow_interrupt(interrupt, &RequestId::must("r"), &UserId::must("u"), &SessionId::must(session), &EnvelopeId::must("e"));
+            Ok::<_, crate::types::Error>(())
+        };
+        let question = || FlowInterrupt::new().with_question("which?".into());
+
+        register(&mut svc, question(), "s1").unwrap();
+        assert!(register(&mut svc, question(), "s1").is_err());
+        register(&mut svc, make_interrupt(), "s1").unwrap();
+        assert!(register(&mut svc, make_interrupt(), "s1").is_err());
+        register(&mut svc, make_interrupt(), "s2").unwrap();
+        assert!(register(&mut svc, make_interrupt(), "s3").is_err());
+
+        let stats = svc.stats();
+        assert_eq!(stats.pending, 3);
+        assert_eq!(stats.suppressed.get(&InterruptKind::Question), Some(&1));
+        assert_eq!(stats.suppressed.get(&InterruptKind::Message), Some(&2));
+    }
+
     #[test]
     fn resolve_unknown_returns_false() {
         let mut svc = InterruptService::new();
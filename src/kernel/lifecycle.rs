@@ -7,6 +7,7 @@
 
 use std::collections::HashMap;
 
+use super::clock::{self, SharedClock};
 use crate::types::{Error, RunId, RequestId, Result, SessionId, UserId};
 
 pub use super::types::{RunRecord, RunStatus, ResourceQuota};
@@ -18,6 +19,10 @@ pub use super::types::{RunRecord, RunStatus, ResourceQuota};
 pub struct RunRegistry {
     default_quota: ResourceQuota,
     pub(crate) records: HashMap<RunId, RunRecord>,
+    /// Live-run cap, enforced by the kernel where runs are admitted.
+    pub(crate) max_active: Option<usize>,
+    /// Stamps record creation, start and completion.
+    clock: SharedClock,
 }
 
 impl RunRegistry {
@@ -25,9 +30,15 @@ impl RunRegistry {
         Self {
             default_quota: default_quota.unwrap_or_default(),
             records: HashMap::new(),
+            max_active: None,
+            clock: clock::system(),
         }
     }
 
+    pub(crate) fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
     /// Create a new run record in `Ready` state. If a record already exists
     /// for the run_id, returns the existing one unchanged.
     pub fn create(
@@ -41,23 +52,27 @@ impl RunRegistry {
         if let Some(existing) = self.records.get(&run_id) {
             return Ok(existing.clone());
         }
+        if let Some(ref quota) = quota {
+            quota.validate()?;
+        }
         let mut record = RunRecord::new(run_id.clone(), request_id, user_id, session_id);
+        record.created_at = self.clock.now();
         record.quota = quota.unwrap_or_else(|| self.default_quota.clone());
         self.records.insert(run_id, record.clone());
         Ok(record)
     }
 
+    /// States `state` may move to; see `RUN_STATUS_TRANSITIONS`.
+    pub fn allowed_transitions(&self, state: RunStatus) -> Vec<RunStatus> {
+        state.allowed_transitions()
+    }
+
     /// Transition `Ready → Running`.
     pub fn run(&mut self, run_id: &RunId) -> Result<()> {
         let record = self.records.get_mut(run_id)
             .ok_or_else(|| Error::not_found(format!("unknown run_id: {}", run_id)))?;
-        if record.state != RunStatus::Ready {
-            return Err(Error::state_transition(format!(
-                "cannot run {}: state is {:?}, expected Ready",
-                run_id, record.state
-            )));
-        }
-        record.start();
+        check_transition(run_id, record.state, RunStatus::Running)?;
+        record.start(self.clock.now());
         Ok(())
     }
 
@@ -66,7 +81,8 @@ impl RunRegistry {
     pub fn terminate(&mut self, run_id: &RunId) -> Result<()> {
         if let Some(record) = self.records.get_mut(run_id) {
             if !record.state.is_terminal() {
-                record.complete();
+                check_transition(run_id, record.state, RunStatus::Terminated)?;
+                record.complete(self.clock.now());
             }
         }
         self.records.remove(run_id);
@@ -119,6 +135,17 @@ impl Default for RunRegistry {
     }
 }
 
+/// Reject an edge missing from `RUN_STATUS_TRANSITIONS`, naming it.
+fn check_transition(run_id: &RunId, from: RunStatus, to: RunStatus) -> Result<()> {
+    if from.can_transition_to(to) {
+        return Ok(());
+    }
+    Err(Error::state_transition(format!(
+        "run {}: transition {:?} → {:?} is not allowed (allowed from {:?}: {:?})",
+        run_id, from, to, from, from.allowed_transitions()
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,6 +197,21 @@ mod tests {
         assert!(lm.run(&run_id).is_err(), "cannot run a Running run");
     }
 
+    #[test]
+    fn transition_matrix_and_edge_errors() {
+        let lm = RunRegistry::default();
+        assert_eq!(lm.allowed_transitions(RunStatus::Ready), vec![RunStatus::Running, RunStatus::Terminated]);
+        assert_eq!(lm.allowed_transitions(RunStatus::Running), vec![RunStatus::Terminated]);
+        assert!(lm.allowed_transitions(RunStatus::Terminated).is_empty());
+
+        let mut lm = RunRegistry::default();
+        let run_id = RunId::must("p1");
+        submit(&mut lm, "p1");
+        lm.run(&run_id).unwrap();
+        let err = lm.run(&run_id).unwrap_err().to_string();
+        assert!(err.contains("Running → Running"), "{}", err);
+    }
+
     #[test]
     fn terminate_idempotent_on_missing() {
         let mut lm = RunRegistry::default();
@@ -0,0 +1,91 @@
+//! Housekeeping, run on the host's schedule.
+//!
+//! `Kernel::run_maintenance` runs the cleanup passes a long-lived kernel
+//! needs: stale sessions, stale per-user usage entries, and then a
+//! [`reconcile`](Kernel::reconcile) that repairs orphans the first two (or
+//! any partial failure) left behind. The kernel runs no background tick;
+//! hosts call it on their own schedule through `KernelHandle::run_maintenance`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::integrity::IntegrityIssueKind;
+use super::Kernel;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MaintenancePolicy {
+    /// Sessions idle longer than this are dropped. `None` skips the pass.
+    #[serde(default)]
+    pub session_max_age_seconds: Option<i64>,
+    /// Cap on per-user usage entries of users with no live run.
+    #[serde(default)]
+    pub max_user_entries: Option<usize>,
+}
+
+/// What one maintenance pass removed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CleanupStats {
+    pub stale_sessions: usize,
+    pub stale_users: usize,
+    /// Orphans repaired, by kind.
+    pub orphans: HashMap<IntegrityIssueKind, usize>,
+}
+
+impl CleanupStats {
+    pub fn orphans_total(&self) -> usize {
+        self.orphans.values().sum()
+    }
+}
+
+impl Kernel {
+    pub fn set_maintenance_policy(&mut self, policy: MaintenancePolicy) {
+        self.maintenance = policy;
+    }
+
+    /// Run one maintenance pass under the current policy.
+    pub fn run_maintenance(&mut self) -> CleanupStats {
+        let started = std::time::Instant::now();
+        let stale_sessions = self.maintenance.session_max_age_seconds
+            .map_or(0, |age| self.cleanup_stale_sessions(age));
+        let stale_users = self.maintenance.max_user_entries
+            .map_or(0, |max| self.cleanup_stale_user_usage(max));
+        let mut orphans = HashMap::new();
+        for issue in self.reconcile().issues {
+            *orphans.entry(issue.kind).or_insert(0) += 1;
+        }
+        let stats = CleanupStats { stale_sessions, stale_users, orphans };
+        tracing::info!(
+            stale_sessions,
+            stale_users,
+            orphans = stats.orphans_total(),
+            "maintenance_completed"
+        );
+        self.perf.record("run_maintenance", started.elapsed());
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::{test_helpers, ManualClock};
+    use crate::types::RunId;
+
+    #[test]
+    fn stale_cleanup_leaves_nothing_to_reconcile() {
+        let clock = std::sync::Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        kernel.set_maintenance_policy(MaintenancePolicy { session_max_age_seconds: Some(60), ..Default::default() });
+        let run_id = RunId::must("idle");
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+
+        assert_eq!(kernel.run_maintenance(), CleanupStats::default());
+        clock.advance(chrono::Duration::seconds(120));
+        let stats = kernel.run_maintenance();
+        assert_eq!(stats.stale_sessions, 1);
+        assert_eq!(stats.orphans_total(), 0);
+        assert!(kernel.lifecycle.get(&run_id).is_none());
+        assert!(kernel.check_integrity().is_clean());
+    }
+}
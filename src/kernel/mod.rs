@@ -7,17 +7,36 @@
 use std::collections::HashMap;
 
 pub mod actor;
+pub mod agents;
+pub mod attempts;
+pub mod cancel;
+pub mod clock;
+pub mod cost;
+pub mod diagnostics;
+pub(crate) mod field_mask;
 pub mod handle;
+pub mod info;
+pub mod integrity;
+pub mod input;
 pub mod interrupts;
 pub mod lifecycle;
+pub mod maintenance;
 pub mod orchestrator;
 mod orchestrator_queries;
 mod orchestrator_session;
+pub mod perf;
+pub mod postprocess;
 pub mod protocol;
+pub mod quarantine;
 pub mod resources;
 pub mod routing;
 pub mod runner;
+#[cfg(feature = "screening")]
+pub mod screening;
+pub mod stage_groups;
+pub mod terminal_log;
 pub mod types;
+pub mod validate;
 
 #[cfg(test)]
 pub(crate) mod test_helpers;
@@ -25,16 +44,34 @@ pub(crate) mod test_helpers;
 mod dispatch;
 
 // Re-export key types
-pub use interrupts::{InterruptService, PendingInterrupt};
+pub use agents::{AgentBindings, AgentRollout};
+pub use attempts::{ResolutionFailure, ResolutionGuard, ResolutionLimits, ResolutionStats};
+pub use cancel::{CancelOutcome, PendingCancel};
+pub use clock::{Clock, ManualClock, SharedClock, SystemClock};
+pub use cost::{CostEstimate, CostPolicy, ModelPrice};
+pub use diagnostics::{DiagnosticCheck, DiagnosticsReport};
+pub use interrupts::{InterruptKind, InterruptLimits, InterruptService, InterruptStats, PendingInterrupt};
 pub use lifecycle::RunRegistry;
-pub use resources::ResourceTracker;
+pub use info::{ServerInfo, ServerLimits};
+pub use integrity::{IntegrityIssue, IntegrityIssueKind, IntegrityReport};
+pub use input::InputPolicy;
+pub use maintenance::{CleanupStats, MaintenancePolicy};
+pub use quarantine::{AgentQuarantine, QuarantineList, QuarantinePolicy, AGENT_OUTCOME_WINDOW};
+pub use perf::{CommandPerf, KernelPerf, PerfRecorder, PERF_SAMPLE_WINDOW};
+pub use postprocess::{PostProcessor, PostProcessorRegistry, PostProcessorStats, Transform, TransformFn};
+pub use resources::{ResourceTracker, USAGE_SAMPLE_WINDOW};
+#[cfg(feature = "screening")]
+pub use screening::{Screener, ScreeningAction, ScreeningRule};
+pub use stage_groups::{StageGroupStats, StageGroups};
+pub use terminal_log::{TerminalLog, TerminalLogConfig, TerminalRecord, DEFAULT_TERMINAL_LOG_CAPACITY};
+pub use validate::{DiagnosticSeverity, PipelineDiagnostic, PipelineValidation};
 pub use types::{
-    RunRecord, RunStatus, QuotaViolation, ResourceQuota, ResourceUsage,
+    AgentHeartbeat, DwellState, QuotaMergePolicy, QuotaRecommendation, QuotaTransfer, QuotaViolation, RemainingBudget, ResourceQuota,
+    ResourceUsage, RunExtension, RunQuery, RunRecord, RunSearchPage, RunStatus, RunStore, StuckRun, StuckThresholds, MAX_QUOTA_TRANSFER_FRACTION,
+    ANNOTATION_RESERVED_KEYS, DEFAULT_SEARCH_LIMIT, MAX_HEARTBEAT_PROGRESS_BYTES, MAX_RUN_EXTENSION_BYTES, RUN_STATUS_TRANSITIONS,
 };
 
-use crate::run::Run;
 use crate::workflow::MergeStrategy;
-use crate::types::RunId;
 
 /// Merge a value into run.state according to the configured strategy.
 fn merge_state_field(
@@ -47,7 +84,7 @@ fn merge_state_field(
         MergeStrategy::Replace => {
             state.insert(key.to_string(), val);
         }
-        MergeStrategy::Append => {
+        MergeStrategy::Append | MergeStrategy::AppendDedup => {
             let arr = state.entry(key.to_string()).or_insert_with(|| serde_json::json!([]));
             if let Some(existing) = arr.as_array_mut() {
                 existing.push(val);
@@ -74,6 +111,21 @@ fn merge_state_field(
     }
 }
 
+/// Append a `{"$same_as": i}` marker to the array at `key`, where `i` is
+/// the entry holding the full copy of its last element. Returns `false`
+/// (appending nothing) when there is no non-empty array to refer back to.
+fn append_same_as(state: &mut HashMap<String, serde_json::Value>, key: &str) -> bool {
+    let Some(entries) = state.get_mut(key).and_then(|v| v.as_array_mut()) else {
+        return false;
+    };
+    let Some(last) = entries.len().checked_sub(1) else {
+        return false;
+    };
+    let target = entries[last].get("$same_as").and_then(|i| i.as_u64()).unwrap_or(last as u64);
+    entries.push(serde_json::json!({"$same_as": target}));
+    true
+}
+
 /// Kernel-side tool subsystem. ACL lives on the consumer's `ToolRegistry`
 /// via [`ToolRegistryBuilder::with_access_policy`]; only health tracking
 /// hangs off the kernel.
@@ -101,24 +153,57 @@ pub struct Kernel {
     pub(crate) orchestrator: orchestrator::Orchestrator,
 
     /// Process run storage (run_id -> run).
-    pub(crate) runs: HashMap<RunId, Run>,
+    pub(crate) runs: RunStore,
+
+    /// Preprocessing applied to `raw_input` as runs enter the kernel.
+    pub(crate) input_policy: InputPolicy,
+
+    /// Model prices and the confirmation threshold for cost preflight.
+    pub(crate) cost_policy: CostPolicy,
+
+    /// Regex rules screening input and agent outputs.
+    #[cfg(feature = "screening")]
+    pub(crate) screener: Screener,
+
+    /// Time source shared with the orchestrator and interrupt service.
+    pub(crate) clock: SharedClock,
 
     /// Tool subsystem (catalog, access, health).
     pub(crate) tools: ToolDomain,
+
+    /// Command and maintenance latency counters.
+    pub(crate) perf: PerfRecorder,
+
+    /// Agents barred from dispatch, and the failure-rate policy.
+    pub(crate) quarantine: QuarantineList,
+
+    /// Keys each caller type may send to `update_run`.
+    pub(crate) update_policies: HashMap<String, crate::run::UpdatePolicy>,
+    /// Records of terminated runs, kept after their state is dropped.
+    pub(crate) terminal_log: TerminalLog,
+    /// Rewrites applied to agent outputs before they are merged.
+    pub(crate) post_processors: PostProcessorRegistry,
+    /// Cleanup passes `run_maintenance` applies when the host calls it.
+    pub(crate) maintenance: MaintenancePolicy,
+    /// Failed interrupt-resolution attempts per pending interrupt.
+    pub(crate) resolution_guard: ResolutionGuard,
+    /// Requested cancellations still inside their grace period.
+    pub(crate) cancellations: HashMap<crate::types::RunId, PendingCancel>,
+    /// Stage concurrency groups, and the permits held or awaited per run.
+    pub(crate) stage_groups: StageGroups,
+    /// Declared agents, their versions and rollouts.
+    pub(crate) agents: AgentBindings,
+    /// Format of the IDs this kernel generates (`Config.id_format`).
+    pub(crate) id_format: crate::types::IdFormat,
+
+    /// When the kernel was constructed, for `ServerInfo`.
+    pub(crate) started_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) started: std::time::Instant,
 }
 
 impl Kernel {
     pub fn new() -> Self {
-        Self {
-            lifecycle: RunRegistry::default(),
-            resources: ResourceTracker::default(),
-            interrupts: interrupts::InterruptService::new(),
-            orchestrator: orchestrator::Orchestrator::new(),
-            runs: HashMap::new(),
-            tools: ToolDomain {
-                health: crate::tools::ToolHealthTracker::default(),
-            },
-        }
+        Self::with_quota(None)
     }
 
     /// Register a routing function by name.
@@ -126,8 +211,9 @@ impl Kernel {
         self.orchestrator.register_routing_fn(name, f);
     }
 
-    /// Create a Kernel wired from a Config struct.
-    pub fn from_config(config: &crate::Config) -> Self {
+    /// Create a Kernel wired from a Config struct. Fails on invalid
+    /// `resolution_limits` or `agents` rather than starting without them.
+    pub fn from_config(config: &crate::Config) -> crate::types::Result<Self> {
         let default_quota = ResourceQuota {
             max_llm_calls: config.defaults.max_llm_calls,
             max_tool_calls: config.defaults.max_tool_calls,
@@ -136,7 +222,68 @@ impl Kernel {
             timeout_seconds: config.defaults.process_timeout.as_secs() as i32,
             ..ResourceQuota::default()
         };
-        Self::with_quota(Some(default_quota))
+        let bounds = crate::run::BoundsDefaults::from(&default_quota);
+        let mut kernel = Self::with_quota(Some(default_quota));
+        kernel.set_bounds_defaults(bounds);
+        kernel.input_policy = config.input.clone();
+        kernel.cost_policy = config.cost.clone();
+        kernel.quarantine.policy = config.quarantine.clone();
+        kernel.interrupts.limits = config.interrupts.clone();
+        kernel.update_policies = config.update_policies.clone();
+        kernel.terminal_log = TerminalLog::new(config.terminal_log.clone());
+        kernel.resolution_guard = ResolutionGuard::new(config.resolution_limits.clone())?;
+        kernel.maintenance = config.maintenance.clone();
+        kernel.set_agent_bindings(config.agents.clone())?;
+        kernel.id_format = config.id_format;
+        kernel.lifecycle.max_active = config.defaults.max_active_runs.filter(|&n| n > 0);
+        Ok(kernel)
+    }
+
+    /// Replace the kernel's time source (e.g. with a [`ManualClock`] in
+    /// tests). Interrupt expiry, session staleness and execution windows
+    /// all read from it.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.orchestrator.clock = clock.clone();
+        self.interrupts.set_clock(clock.clone());
+        self.lifecycle.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Install the `raw_input` preprocessing applied as runs are initialized.
+    pub fn set_input_policy(&mut self, policy: InputPolicy) {
+        self.input_policy = policy;
+    }
+
+    /// Install model prices and the cost-confirmation threshold.
+    pub fn set_cost_policy(&mut self, policy: CostPolicy) {
+        self.cost_policy = policy;
+    }
+
+    /// Restrict `update_run` calls from `caller` to `policy`, or lift the
+    /// restriction with `None`.
+    pub fn set_update_policy(&mut self, caller: &str, policy: Option<crate::run::UpdatePolicy>) {
+        match policy {
+            Some(policy) => {
+                self.update_policies.insert(caller.to_string(), policy);
+            }
+            None => {
+                self.update_policies.remove(caller);
+            }
+        }
+    }
+
+    /// Install the bounds given to workflows that leave theirs at 0.
+    pub fn set_bounds_defaults(&mut self, bounds: crate::run::BoundsDefaults) {
+        self.orchestrator.bounds = bounds;
+    }
+
+    pub fn bounds_defaults(&self) -> crate::run::BoundsDefaults {
+        self.orchestrator.bounds
+    }
+
+    /// Install the caps on pending interrupts.
+    pub fn set_interrupt_limits(&mut self, limits: InterruptLimits) {
+        self.interrupts.limits = limits;
     }
 
     /// Construct a Kernel with an optional default quota for new processes.
@@ -146,31 +293,43 @@ impl Kernel {
             resources: ResourceTracker::new(),
             interrupts: interrupts::InterruptService::new(),
             orchestrator: orchestrator::Orchestrator::new(),
-            runs: HashMap::new(),
+            runs: RunStore::default(),
+            input_policy: InputPolicy::default(),
+            cost_policy: CostPolicy::default(),
+            #[cfg(feature = "screening")]
+            screener: Screener::default(),
+            clock: clock::system(),
             tools: ToolDomain {
                 health: crate::tools::ToolHealthTracker::default(),
             },
+            perf: PerfRecorder::default(),
+            quarantine: QuarantineList::default(),
+            update_policies: HashMap::new(),
+            terminal_log: TerminalLog::default(),
+            post_processors: PostProcessorRegistry::default(),
+            maintenance: MaintenancePolicy::default(),
+            resolution_guard: ResolutionGuard::default(),
+            cancellations: HashMap::new(),
+            stage_groups: StageGroups::default(),
+            agents: AgentBindings::default(),
+            id_format: crate::types::IdFormat::default(),
+            started_at: chrono::Utc::now(),
+            started: std::time::Instant::now(),
         }
     }
 }
 
-/// Remaining resource budget for a process.
-#[derive(Debug, Clone)]
-pub struct RemainingBudget {
-    pub llm_calls_remaining: i32,
-    pub iterations_remaining: i32,
-    pub agent_hops_remaining: i32,
-    pub tokens_in_remaining: i64,
-    pub tokens_out_remaining: i64,
-    pub time_remaining_seconds: f64,
-}
-
 /// Full system status snapshot returned by `Kernel::get_system_status()`.
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct SystemStatus {
     pub runs_total: usize,
     pub runs_by_state: HashMap<RunStatus, usize>,
     pub active_orchestration_sessions: usize,
+    /// Cap set by `Kernel::set_max_active_runs`, if any.
+    pub max_active_runs: Option<usize>,
+    /// Live runs, counted as for admission, have reached `max_active_runs`;
+    /// new runs are being shed.
+    pub overloaded: bool,
 }
 
 impl Default for Kernel {
@@ -182,7 +341,7 @@ impl Default for Kernel {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{RequestId, SessionId, UserId};
+    use crate::types::{RequestId, RunId, SessionId, UserId};
 
     #[test]
     fn test_get_system_status_empty_kernel() {
@@ -244,6 +403,1129 @@ mod tests {
         assert_eq!(kernel.lifecycle.count(), 0);
     }
 
+    #[test]
+    fn test_initialize_run_rolls_back_record_on_invalid_workflow() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("bad");
+        let invalid = crate::workflow::Workflow::test_default("empty", vec![]);
+
+        assert!(kernel.initialize_run(run_id.clone(), invalid, test_helpers::create_test_run(), false, None).is_err());
+        assert!(kernel.lifecycle.get(&run_id).is_none());
+        assert!(!kernel.runs.contains_key(&run_id));
+
+        let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+        assert!(kernel.lifecycle.get(&run_id).is_some());
+    }
+
+    #[test]
+    fn test_initialize_run_keeps_existing_record_on_failure() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("pre");
+        kernel.create_run(run_id.clone(), RequestId::must("req1"), UserId::must("user1"), SessionId::must("sess1"), None).unwrap();
+
+        let invalid = crate::workflow::Workflow::test_default("empty", vec![]);
+        assert!(kernel.initialize_run(run_id.clone(), invalid, test_helpers::create_test_run(), false, None).is_err());
+        assert!(kernel.lifecycle.get(&run_id).is_some());
+    }
+
+    #[test]
+    fn test_transfer_quota_between_siblings() {
+        let mut kernel = Kernel::new();
+        let a = RunId::must("a");
+        let b = RunId::must("b");
+        let stranger = RunId::must("c");
+        for (id, user) in [(&a, "u1"), (&b, "u1"), (&stranger, "u2")] {
+            let mut run = test_helpers::create_test_run();
+            run.identity.user_id = UserId::must(user);
+            run.identity.session_id = SessionId::must("s1");
+            let _ = kernel.initialize_run(id.clone(), test_helpers::create_test_workflow(), run, false, None).unwrap();
+        }
+        let before_a = kernel.lifecycle.get(&a).unwrap().quota.max_llm_calls;
+        let before_b = kernel.lifecycle.get(&b).unwrap().quota.max_llm_calls;
+        let run_limit_b = kernel.runs[&b].limits.max_llm_calls;
+
+        let amounts = QuotaTransfer { llm_calls: 5, ..Default::default() };
+        kernel.transfer_quota(&a, &b, amounts).unwrap();
+
+        assert_eq!(kernel.lifecycle.get(&a).unwrap().quota.max_llm_calls, before_a - 5);
+        assert_eq!(kernel.lifecycle.get(&b).unwrap().quota.max_llm_calls, before_b + 5);
+        assert_eq!(kernel.runs[&b].limits.max_llm_calls, run_limit_b + 5);
+        assert_eq!(kernel.runs[&a].audit.metadata["quota_transfers"][0]["to"], "b");
+
+        // Different user → rejected.
+        assert!(matches!(
+            kernel.transfer_quota(&a, &stranger, amounts),
+            Err(crate::types::Error::PolicyViolation(_))
+        ));
+        // More than half of the donor's remaining budget → rejected.
+        let greedy = QuotaTransfer { llm_calls: before_a, ..Default::default() };
+        assert!(matches!(
+            kernel.transfer_quota(&a, &b, greedy),
+            Err(crate::types::Error::QuotaExceeded(_))
+        ));
+
+        // A recipient near the ceiling saturates instead of overflowing.
+        kernel.lifecycle.get_mut(&b).unwrap().quota.max_llm_calls = i32::MAX - 1;
+        kernel.transfer_quota(&a, &b, amounts).unwrap();
+        assert_eq!(kernel.lifecycle.get(&b).unwrap().quota.max_llm_calls, i32::MAX);
+
+        // An unset (unlimited) dimension has nothing to move.
+        kernel.lifecycle.get_mut(&b).unwrap().quota.max_tool_calls = 0;
+        let tools = QuotaTransfer { tool_calls: 1, ..Default::default() };
+        assert!(matches!(kernel.transfer_quota(&a, &b, tools), Err(crate::types::Error::Validation { .. })));
+
+        // A finished run neither gives nor receives.
+        kernel.runs.get_mut(&b).unwrap().complete("done", chrono::Utc::now());
+        let before_a = kernel.lifecycle.get(&a).unwrap().quota.max_llm_calls;
+        assert!(matches!(kernel.transfer_quota(&a, &b, amounts), Err(crate::types::Error::Validation { .. })));
+        assert!(matches!(kernel.transfer_quota(&b, &a, amounts), Err(crate::types::Error::Validation { .. })));
+        assert_eq!(kernel.lifecycle.get(&a).unwrap().quota.max_llm_calls, before_a);
+    }
+
+    #[test]
+    fn test_check_quota_skips_unset_limits_after_max_merge() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("merged");
+        let base = ResourceQuota::default().with_max_llm_calls(10).with_max_tool_calls(0);
+        let other = ResourceQuota::default().with_max_llm_calls(4).with_max_tool_calls(50);
+        let quota = base.merge_with_policy(&other, QuotaMergePolicy::Max);
+        assert_eq!(quota.max_tool_calls, 0);
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, Some(quota),
+        ).unwrap();
+
+        // The unset tool limit is looser, not a zero budget.
+        kernel.runs.get_mut(&run_id).unwrap().metrics.tool_calls = 500;
+        kernel.runs.get_mut(&run_id).unwrap().metrics.llm_calls = 10;
+        kernel.check_quota(&run_id).unwrap();
+
+        kernel.runs.get_mut(&run_id).unwrap().metrics.llm_calls = 11;
+        assert!(matches!(kernel.check_quota(&run_id), Err(crate::types::Error::QuotaExceeded(_))));
+    }
+
+    #[test]
+    fn test_manual_clock_drives_interrupt_expiry_and_staleness() {
+        use crate::kernel::protocol::Instruction;
+        use crate::run::FlowInterrupt;
+        use std::sync::Arc;
+
+        let start = chrono::DateTime::parse_from_rfc3339("2030-01-01T00:00:00Z").unwrap().to_utc();
+        let clock = Arc::new(ManualClock::new(start));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+
+        let run_id = RunId::must("clocked");
+        let _ = kernel.initialize_orchestration(
+            run_id.clone(),
+            test_helpers::create_test_workflow(),
+            test_helpers::create_test_run(),
+            false,
+        ).unwrap();
+
+        let mut interrupt = FlowInterrupt::new();
+        interrupt.expires_at = Some(start + chrono::TimeDelta::seconds(60));
+        kernel.set_run_interrupt(&run_id, interrupt).unwrap();
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::WaitInterrupt { .. }));
+
+        clock.advance(chrono::TimeDelta::seconds(120));
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::RunAgent { .. }));
+
+        assert_eq!(kernel.cleanup_stale_sessions(300), 0);
+        clock.advance(chrono::TimeDelta::seconds(600));
+        assert_eq!(kernel.cleanup_stale_sessions(300), 1);
+    }
+
+    #[test]
+    fn test_annotate_runs_by_query() {
+        use crate::run::Run;
+
+        let mut kernel = Kernel::new();
+        for (id, user) in [("ann-1", "alice"), ("ann-2", "alice"), ("ann-3", "bob")] {
+            let _ = kernel.initialize_run(
+                RunId::must(id), test_helpers::create_test_workflow(), Run::new(user, "s", "hi", None), false, None,
+            ).unwrap();
+        }
+        let query = RunQuery { user_id: Some(UserId::must("alice")), ..Default::default() };
+        let patch = || HashMap::from([("incident".to_string(), serde_json::json!("INC-1234"))]);
+
+        // A record whose run is gone matches but is not counted.
+        kernel.runs.remove(&RunId::must("ann-1"));
+        let _ = kernel.initialize_run(
+            RunId::must("ann-4"), test_helpers::create_test_workflow(), Run::new("alice", "s", "hi", None), false, None,
+        ).unwrap();
+
+        assert_eq!(kernel.annotate_runs(&query, "ops", patch(), true).unwrap(), 2);
+        assert!(!kernel.runs[&RunId::must("ann-2")].audit.metadata.contains_key("incident"));
+        let revision = kernel.runs[&RunId::must("ann-2")].revision;
+        assert_eq!(kernel.annotate_runs(&query, "ops", patch(), false).unwrap(), 2);
+        let run = &kernel.runs[&RunId::must("ann-2")];
+        assert_eq!(run.audit.metadata["incident"], serde_json::json!("INC-1234"));
+        assert_eq!(run.revision, revision + 1);
+        assert_eq!(run.field_revision("metadata.incident"), 1);
+        assert!(!kernel.runs[&RunId::must("ann-3")].audit.metadata.contains_key("incident"));
+        assert!(kernel.annotate_runs(&query, "ops", HashMap::new(), true).is_err());
+
+        let reserved = HashMap::from([("loop_feedback".to_string(), serde_json::json!([]))]);
+        assert!(kernel.annotate_runs(&query, "ops", reserved, false).is_err());
+        // The caller's UpdatePolicy applies, and a rejection writes nothing.
+        kernel.set_update_policy("bot", Some(crate::run::UpdatePolicy { allowed_keys: ["raw_input".to_string()].into() }));
+        assert!(kernel.annotate_runs(&query, "bot", patch(), true).is_err());
+        assert_eq!(kernel.runs[&RunId::must("ann-2")].revision, revision + 1);
+    }
+
+    #[test]
+    fn test_annotate_runs_rejected_by_one_run_writes_none() {
+        use crate::run::Run;
+
+        let mut kernel = Kernel::new();
+        let ids = ["all-1", "all-2", "all-3"].map(RunId::must);
+        for (n, id) in ids.iter().enumerate() {
+            // Records are matched in creation order.
+            let mut run = Run::new("alice", "s", "hi", None);
+            run.received_at += chrono::TimeDelta::seconds(n as i64);
+            let _ = kernel.initialize_run(id.clone(), test_helpers::create_test_workflow(), run, false, None).unwrap();
+        }
+        kernel.runs.get_mut(&ids[1]).unwrap().complete("done", chrono::Utc::now());
+        let revisions: Vec<u64> = ids.iter().map(|id| kernel.runs[id].revision).collect();
+
+        let query = RunQuery { user_id: Some(UserId::must("alice")), ..Default::default() };
+        let patch = HashMap::from([("incident".to_string(), serde_json::json!("INC-7"))]);
+        assert!(matches!(kernel.annotate_runs(&query, "ops", patch, false), Err(crate::types::Error::Validation { .. })));
+        for (id, revision) in ids.iter().zip(revisions) {
+            assert!(!kernel.runs[id].audit.metadata.contains_key("incident"));
+            assert_eq!(kernel.runs[id].revision, revision);
+        }
+    }
+
+    #[test]
+    fn test_update_run_compares_revision() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("cas");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let read = kernel.runs[&run_id].revision;
+        let patch = |v: &str| HashMap::from([("metadata".to_string(), serde_json::json!({"note": v}))]);
+
+        kernel.update_run(&run_id, "ui", patch("a"), &HashMap::new(), Some(read)).unwrap();
+        let current = kernel.runs[&run_id].revision;
+        assert!(current > read);
+        match kernel.update_run(&run_id, "ui", patch("b"), &HashMap::new(), Some(read)) {
+            Err(crate::types::Error::Conflict { current_revision, .. }) => assert_eq!(current_revision, current),
+            other => panic!("expected Conflict, got {:?}", other),
+        }
+        assert_eq!(kernel.runs[&run_id].audit.metadata["note"], serde_json::json!("a"));
+    }
+
+    #[test]
+    fn test_polling_does_not_break_compare_and_swap() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("cas-poll");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let read = kernel.get_orchestration_state(&run_id).unwrap().revision;
+        for _ in 0..3 {
+            let _ = kernel.get_next_instruction(&run_id).unwrap();
+            let _ = kernel.get_orchestration_state(&run_id).unwrap();
+        }
+        let patch = HashMap::from([("metadata".to_string(), serde_json::json!({"note": "a"}))]);
+        assert!(kernel.update_run(&run_id, "ui", patch, &HashMap::new(), Some(read)).is_ok());
+    }
+
+    #[test]
+    fn test_kernel_driven_run_changes_bump_revision() {
+        use crate::kernel::protocol::Instruction;
+        use crate::run::{FlowInterrupt, InterruptResponse};
+
+        let clock = std::sync::Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        let run_id = RunId::must("revisions");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let revision = |kernel: &Kernel| kernel.runs[&run_id].revision;
+
+        // An update that writes nothing leaves the revision alone.
+        let before = revision(&kernel);
+        assert!(kernel.update_run(&run_id, "ui", HashMap::new(), &HashMap::new(), None).unwrap().is_empty());
+        assert_eq!(revision(&kernel), before);
+
+        // Consuming `_interrupt_response` on dispatch is a change.
+        let id = kernel.set_run_interrupt(&run_id, FlowInterrupt::new().with_question("go?".into())).unwrap();
+        let response = InterruptResponse {
+            text: Some("yes".into()),
+            approved: None,
+            decision: None,
+            data: None,
+            responder: None,
+            received_at: clock.now(),
+        };
+        kernel.resolve_run_interrupt(&run_id, id.as_str(), response).unwrap();
+        let before = revision(&kernel);
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        assert!(context.interrupt_response.is_some());
+        assert!(!kernel.runs[&run_id].audit.metadata.contains_key("_interrupt_response"));
+        assert!(revision(&kernel) > before);
+        let before = revision(&kernel);
+        let _ = kernel.get_next_instruction(&run_id).unwrap();
+        assert_eq!(revision(&kernel), before);
+
+        // So is dropping an expired interrupt.
+        let mut expiring = FlowInterrupt::new().with_question("still there?".into());
+        expiring.expires_at = Some(clock.now() + chrono::TimeDelta::seconds(10));
+        kernel.set_run_interrupt(&run_id, expiring).unwrap();
+        clock.advance(chrono::TimeDelta::seconds(11));
+        let before = revision(&kernel);
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::RunAgent { .. }));
+        assert!(kernel.runs[&run_id].interrupts.interrupt.is_none());
+        assert!(revision(&kernel) > before);
+    }
+
+    #[test]
+    fn test_delegated_interrupt_moves_to_new_owner() {
+        use crate::run::{FlowInterrupt, Run};
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("delegate");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), Run::new("alice", "s", "hi", None), false, None,
+        ).unwrap();
+        let id = kernel.set_run_interrupt(&run_id, FlowInterrupt::new().with_question("Refund?".to_string())).unwrap();
+
+        assert!(kernel.delegate_interrupt(&run_id, id.as_str(), "alice", None).is_err());
+        assert!(kernel.delegate_interrupt(&run_id, "int_missing", "bob", None).is_err());
+        kernel.delegate_interrupt(&run_id, id.as_str(), "bob", Some("finance call".to_string())).unwrap();
+        let interrupt = kernel.delegate_interrupt(&run_id, id.as_str(), "carol", None).unwrap();
+
+        let chain: Vec<_> = interrupt.delegations.iter().map(|d| (d.from.as_str(), d.to.as_str())).collect();
+        assert_eq!(chain, [("alice", "bob"), ("bob", "carol")]);
+        assert_eq!(interrupt.delegations[0].note.as_deref(), Some("finance call"));
+        assert_eq!(kernel.runs[&run_id].identity.user_id.as_str(), "alice");
+        assert_eq!(kernel.runs[&run_id].interrupts.history[0].delegations.len(), 2);
+        assert_eq!(kernel.interrupts.get_pending(id.as_str()).unwrap().user_id.as_str(), "carol");
+    }
+
+    #[test]
+    fn test_find_stuck_runs_by_dwell_state() {
+        use crate::run::FlowInterrupt;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+
+        let ready = RunId::must("stuck-ready");
+        kernel.create_run(ready.clone(), "r".into(), "u".into(), "s".into(), None).unwrap();
+        let waiting = RunId::must("stuck-waiting");
+        let _ = kernel.initialize_run(
+            waiting.clone(),
+            test_helpers::create_test_workflow(),
+            test_helpers::create_test_run(),
+            false,
+            None,
+        ).unwrap();
+        kernel.lifecycle.run(&waiting).unwrap();
+        kernel.set_run_interrupt(&waiting, FlowInterrupt::new()).unwrap();
+
+        let thresholds = StuckThresholds {
+            ready: Some(Duration::from_secs(60)),
+            waiting: Some(Duration::from_secs(600)),
+            ..Default::default()
+        };
+        assert!(kernel.find_stuck_runs(&thresholds).is_empty());
+
+        clock.advance(chrono::TimeDelta::seconds(120));
+        let stuck = kernel.find_stuck_runs(&thresholds);
+        assert_eq!(stuck.len(), 1);
+        assert_eq!((stuck[0].run_id.as_str(), stuck[0].state), ("stuck-ready", DwellState::Ready));
+
+        clock.advance(chrono::TimeDelta::seconds(600));
+        let stuck = kernel.find_stuck_runs(&thresholds);
+        assert_eq!(stuck.len(), 2);
+        assert!(stuck.iter().any(|r| r.run_id == waiting && r.state == DwellState::Waiting));
+    }
+
+    #[test]
+    fn test_heartbeat_keeps_run_alive() {
+        use crate::kernel::protocol::Instruction;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        let run_id = RunId::must("heartbeat");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        kernel.lifecycle.run(&run_id).unwrap();
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        let dispatch_id = context.dispatch_id.unwrap();
+
+        clock.advance(chrono::TimeDelta::seconds(50));
+        let beat = kernel.report_heartbeat(&run_id, &dispatch_id, Some(serde_json::json!({"step": 2}))).unwrap();
+        assert_eq!(beat.count, 1);
+        clock.advance(chrono::TimeDelta::seconds(50));
+        let thresholds = StuckThresholds { running: Some(Duration::from_secs(80)), ..Default::default() };
+        assert!(kernel.find_stuck_runs(&thresholds).is_empty());
+        let snapshot = kernel.get_orchestration_state(&run_id).unwrap();
+        assert_eq!(snapshot.heartbeat.unwrap().progress, Some(serde_json::json!({"step": 2})));
+
+        assert!(kernel.report_heartbeat(&run_id, "other", None).is_err());
+        kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({}), None, Default::default(), true, "", false, Some(dispatch_id.as_str()),
+        ).unwrap();
+        assert!(kernel.report_heartbeat(&run_id, &dispatch_id, None).is_err());
+        assert!(kernel.get_orchestration_state(&run_id).unwrap().heartbeat.is_none());
+    }
+
+    #[test]
+    fn test_signal_run_resolves_matching_wait() {
+        use crate::kernel::protocol::Instruction;
+        use crate::run::FlowInterrupt;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("sig");
+        let _ = kernel.initialize_orchestration(
+            run_id.clone(),
+            test_helpers::create_test_workflow(),
+            test_helpers::create_test_run(),
+            false,
+        ).unwrap();
+
+        kernel.set_run_interrupt(&run_id, FlowInterrupt::new().with_await_signal("approved")).unwrap();
+
+        // Unrelated signal is recorded but does not wake the run.
+        assert!(!kernel.signal_run(&run_id, "noise", serde_json::json!(1)).unwrap());
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::WaitInterrupt { .. }));
+
+        assert!(kernel.signal_run(&run_id, "approved", serde_json::json!({"by": "ops"})).unwrap());
+        let signals = &kernel.runs[&run_id].audit.metadata["signals"];
+        assert_eq!(signals["noise"], serde_json::json!(1));
+        assert_eq!(signals["approved"]["by"], "ops");
+
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => {
+                let response = context.interrupt_response.unwrap();
+                assert_eq!(response["decision"], "approved");
+                assert_eq!(response["data"]["payload"]["by"], "ops");
+            }
+            other => panic!("expected RunAgent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stage_field_masks_apply_to_agent_context() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("masked");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].hidden_fields = vec!["metadata.api_key".to_string()];
+        let mut run = test_helpers::create_test_run();
+        run.audit.metadata.insert("api_key".to_string(), serde_json::json!("secret"));
+        run.audit.metadata.insert("locale".to_string(), serde_json::json!("en"));
+        let _ = kernel.initialize_orchestration(run_id.clone(), workflow, run, false).unwrap();
+
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => {
+                let agent_context = context.agent_context.unwrap();
+                assert!(agent_context["metadata"].get("api_key").is_none());
+                assert!(agent_context["template_vars"].get("api_key").is_none());
+                assert_eq!(agent_context["metadata"]["locale"], "en");
+            }
+            other => panic!("expected RunAgent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stage_inputs_bind_prior_outputs() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("inputs");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[1].inputs = HashMap::from([
+            ("query".to_string(), "outputs.agent1.query".to_string()),
+            ("token".to_string(), "metadata.token".to_string()),
+            ("missing".to_string(), "outputs.agent9.x".to_string()),
+        ]);
+        workflow.stages[1].hidden_fields = vec!["metadata.token".to_string()];
+        let mut run = test_helpers::create_test_run();
+        run.audit.metadata.insert("token".to_string(), serde_json::json!("secret"));
+        let _ = kernel.initialize_orchestration(run_id.clone(), workflow, run, false).unwrap();
+
+        kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"query": "rust actors"}), None,
+            Default::default(), true, "", false, None,
+        ).unwrap();
+
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => {
+                let agent_context = context.agent_context.unwrap();
+                assert_eq!(agent_context["inputs"]["query"], "rust actors");
+                assert_eq!(agent_context["template_vars"]["query"], "rust actors");
+                // Hidden fields can't be bound back in.
+                assert!(agent_context["inputs"]["token"].is_null());
+                assert!(agent_context["inputs"]["missing"].is_null());
+            }
+            other => panic!("expected RunAgent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_output_schema_violation_fails_stage() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("contract");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].output_schema = Some(serde_json::json!({
+            "type": "object",
+            "required": ["summary"],
+        }));
+        let _ = kernel.initialize_orchestration(run_id.clone(), workflow, test_helpers::create_test_run(), false).unwrap();
+
+        kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"text": "free-form"}), None,
+            Default::default(), true, "", false, None,
+        ).unwrap();
+
+        let run = &kernel.runs[&run_id];
+        let failure = &run.audit.metadata["last_agent_failure"];
+        assert!(failure["error"].as_str().unwrap().starts_with("output failed schema validation"));
+        assert_eq!(failure["schema_errors"][0], "/: missing required property 'summary'");
+        assert_eq!(run.outputs["agent1"]["success"], false);
+        assert!(!run.outputs["agent1"].contains_key("text"));
+        assert_eq!(
+            run.audit.processing_history[0].status,
+            crate::run::ProcessingStatus::Error
+        );
+    }
+
+    #[test]
+    fn test_output_schema_retry_redispatches_with_feedback() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("retry");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].output_schema = Some(serde_json::json!({"required": ["summary"]}));
+        workflow.stages[0].output_schema_retries = Some(1);
+        let mut run = test_helpers::create_test_run();
+        let review = serde_json::json!({
+            "severity": "info", "code": "review", "message": "tighten tone", "stage": "stage1", "iteration": 0,
+        });
+        run.audit.metadata.insert("loop_feedback".to_string(), serde_json::json!([review]));
+        let _ = kernel.initialize_orchestration(run_id.clone(), workflow, run, false).unwrap();
+
+        let bad = || serde_json::json!({"text": "free-form"});
+        kernel.process_agent_result(&run_id, "agent1", bad(), None, Default::default(), true, "", false, None).unwrap();
+
+        // Same stage again, with the errors in loop_feedback and nothing
+        // of the rejected output merged.
+        let run = &kernel.runs[&run_id];
+        assert_eq!(run.current_stage.as_str(), "stage1");
+        assert!(run.outputs.is_empty());
+        assert_eq!(run.audit.metadata["loop_feedback"][1]["details"][0], "/: missing required property 'summary'");
+        assert_eq!(run.audit.metadata["loop_feedback"][1]["code"], "output_schema");
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { agent, context } => {
+                assert_eq!(agent.as_str(), "agent1");
+                assert!(context.agent_context.unwrap()["metadata"]["loop_feedback"].is_array());
+            }
+            other => panic!("expected RunAgent, got {:?}", other),
+        }
+
+        // Retry budget spent: the violation now fails the stage.
+        kernel.process_agent_result(&run_id, "agent1", bad(), None, Default::default(), true, "", false, None).unwrap();
+        let run = &kernel.runs[&run_id];
+        assert_eq!(run.audit.metadata["loop_feedback"], serde_json::json!([review]));
+        assert!(run.audit.metadata["last_agent_failure"]["schema_errors"].is_array());
+        assert_eq!(run.iteration, 2);
+    }
+
+    #[test]
+    fn test_workflow_concurrency_limit_rejects_excess_runs() {
+        let mut kernel = Kernel::new();
+        kernel.set_workflow_concurrency_limit("test_workflow", Some(1)).unwrap();
+        let init = |kernel: &mut Kernel, id: &str| kernel.initialize_orchestration(
+            RunId::must(id),
+            test_helpers::create_test_workflow(),
+            test_helpers::create_test_run(),
+            false,
+        );
+
+        let _ = init(&mut kernel, "first").unwrap();
+        let err = init(&mut kernel, "second").unwrap_err();
+        assert!(matches!(err, crate::types::Error::QuotaExceeded(_)));
+
+        // A terminated run frees its slot.
+        kernel.runs.get_mut(&RunId::must("first")).unwrap()
+            .terminate_with(crate::run::TerminalReason::Completed, None);
+        let _ = init(&mut kernel, "second").unwrap();
+
+        kernel.set_workflow_concurrency_limit("test_workflow", None).unwrap();
+        let _ = init(&mut kernel, "third").unwrap();
+        assert!(kernel.set_workflow_concurrency_limit("test_workflow", Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_recommend_quota_from_terminated_runs() {
+        let mut kernel = Kernel::new();
+        assert!(kernel.recommend_quota("test_workflow", 0.5).is_err());
+
+        for i in 0..4 {
+            let run_id = RunId::must(format!("rq-{}", i));
+            let _ = kernel.initialize_run(
+                run_id.clone(),
+                test_helpers::create_test_workflow(),
+                test_helpers::create_test_run(),
+                false,
+                None,
+            ).unwrap();
+            kernel.runs.get_mut(&run_id).unwrap().metrics.llm_calls = 2 * (i + 1);
+            kernel.terminate_run(&run_id).unwrap();
+        }
+
+        let rec = kernel.recommend_quota("test_workflow", 0.5).unwrap();
+        assert_eq!(rec.samples, 4);
+        assert_eq!(rec.quota.max_llm_calls, 12);
+    }
+
+    #[test]
+    fn test_max_active_runs_sheds_new_runs() {
+        let mut kernel = Kernel::new();
+        kernel.set_max_active_runs(Some(1)).unwrap();
+        let create = |kernel: &mut Kernel, id: &str| {
+            kernel.create_run(RunId::must(id), "r".into(), "u".into(), "s".into(), None)
+        };
+
+        create(&mut kernel, "a").unwrap();
+        assert!(kernel.get_system_status().overloaded);
+        // Re-creating an admitted run returns it rather than shedding.
+        create(&mut kernel, "a").unwrap();
+        let err = create(&mut kernel, "b").unwrap_err();
+        assert!(matches!(err, crate::types::Error::QuotaExceeded(_)));
+
+        kernel.terminate_run(&RunId::must("a")).unwrap();
+        assert!(!kernel.get_system_status().overloaded);
+        create(&mut kernel, "b").unwrap();
+        assert!(kernel.set_max_active_runs(Some(0)).is_err());
+
+        // Runs initialized without a record count and are shed the same way.
+        kernel.terminate_run(&RunId::must("b")).unwrap();
+        let init = |kernel: &mut Kernel, id: &str| {
+            kernel.initialize_orchestration(
+                RunId::must(id), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false,
+            )
+        };
+        let _ = init(&mut kernel, "c").unwrap();
+        assert!(kernel.get_system_status().overloaded);
+        let err = init(&mut kernel, "d").unwrap_err();
+        assert!(matches!(err, crate::types::Error::QuotaExceeded(_)));
+        assert!(!kernel.runs.contains_key(&RunId::must("d")));
+        assert!(create(&mut kernel, "d").is_err());
+    }
+
+    #[test]
+    fn test_unset_workflow_bounds_take_config_defaults() {
+        let mut config = crate::Config::default();
+        config.defaults.max_iterations = 4;
+        config.defaults.max_llm_calls = 7;
+        let kernel = Kernel::from_config(&config).unwrap();
+        let bounds = kernel.bounds_defaults();
+        assert_eq!((bounds.max_iterations, bounds.max_llm_calls, bounds.max_agent_hops), (4, 7, 10));
+        assert_eq!(kernel.server_info().limits.default_bounds, bounds);
+
+        let mut kernel = kernel;
+        let workflow: crate::workflow::Workflow = serde_json::from_value(serde_json::json!({
+            "name": "unbounded",
+            "stages": [{"name": "only", "agent": "agent"}],
+            "max_llm_calls": 3
+        })).unwrap();
+        let run_id = RunId::must("defaults");
+        let run = crate::run::Run::with_defaults("u", "s", "hi", None, &bounds, crate::types::IdFormat::default());
+        assert_eq!(run.max_iterations, 4);
+        let _ = kernel.initialize_run(run_id.clone(), workflow, run, false, None).unwrap();
+
+        let run = kernel.runs.get(&run_id).unwrap();
+        assert_eq!((run.max_iterations, run.limits.max_llm_calls, run.limits.max_agent_hops), (4, 3, 10));
+        assert_eq!(kernel.orchestrator.sessions[&run_id].workflow.max_iterations, 4);
+    }
+
+    #[test]
+    fn test_run_extensions_in_snapshot() {
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("ext");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let ext = RunExtension { schema: "origin/v1".into(), value: serde_json::json!("slack") };
+        kernel.set_run_extension(&run_id, "origin", Some(ext.clone())).unwrap();
+        assert_eq!(kernel.get_orchestration_state(&run_id).unwrap().extensions["origin"], ext);
+
+        kernel.set_run_extension(&run_id, "origin", None).unwrap();
+        assert!(kernel.get_orchestration_state(&run_id).unwrap().extensions.is_empty());
+        assert!(kernel.set_run_extension(&RunId::must("missing"), "origin", None).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_stage_pauses_after_result() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("checkpoint");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].checkpoint = true;
+        let _ = kernel.initialize_run(
+            run_id.clone(), workflow, test_helpers::create_test_run(), false, None,
+        ).unwrap();
+
+        kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"draft": "v1"}), None,
+            Default::default(), true, "", false, None,
+        ).unwrap();
+
+        let interrupt = match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::WaitInterrupt { interrupt, .. } => interrupt.unwrap(),
+            other => panic!("expected WaitInterrupt, got {:?}", other),
+        };
+        let data = interrupt.data.unwrap();
+        assert_eq!(data["stage"], "stage1");
+        assert_eq!(data["output"]["draft"], "v1");
+        assert_eq!(kernel.runs[&run_id].current_stage.as_str(), "stage2");
+    }
+
+    #[test]
+    fn test_checkpoint_over_interrupt_limit_leaves_run_untouched() {
+        use crate::kernel::protocol::Instruction;
+        use crate::run::FlowInterrupt;
+
+        let mut kernel = Kernel::new();
+        kernel.set_interrupt_limits(InterruptLimits { max_pending: Some(1), ..Default::default() });
+        let waiting = RunId::must("waiting");
+        let _ = kernel.initialize_run(
+            waiting.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        kernel.set_run_interrupt(&waiting, FlowInterrupt::new().with_question("ok?".into())).unwrap();
+
+        let run_id = RunId::must("checkpoint-full");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].checkpoint = true;
+        let _ = kernel.initialize_run(
+            run_id.clone(), workflow, test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        let before = kernel.runs[&run_id].clone();
+        let report = |kernel: &mut Kernel| kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"draft": "v1"}), None,
+            Default::default(), true, "", false, context.dispatch_id.as_deref(),
+        );
+        assert!(matches!(report(&mut kernel).unwrap_err(), crate::types::Error::QuotaExceeded(_)));
+        assert_eq!(kernel.runs[&run_id], before);
+
+        // Once there is room, the same report goes through.
+        kernel.set_interrupt_limits(InterruptLimits::default());
+        report(&mut kernel).unwrap();
+        assert_eq!(kernel.runs[&run_id].current_stage.as_str(), "stage2");
+        assert!(kernel.runs[&run_id].interrupts.interrupt.is_some());
+    }
+
+    #[test]
+    fn test_duplicate_report_is_acknowledged_even_when_checkpoints_are_full() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        kernel.set_interrupt_limits(InterruptLimits { max_pending: Some(1), ..Default::default() });
+        let run_id = RunId::must("checkpoint-dup");
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].checkpoint = true;
+        workflow.stages[1].checkpoint = true;
+        let _ = kernel.initialize_run(
+            run_id.clone(), workflow, test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let Instruction::RunAgent { context, .. } = kernel.get_next_instruction(&run_id).unwrap() else {
+            panic!("expected RunAgent");
+        };
+        let report = |kernel: &mut Kernel| kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"draft": "v1"}), None,
+            Default::default(), true, "", false, context.dispatch_id.as_deref(),
+        );
+        report(&mut kernel).unwrap();
+        let after = kernel.runs[&run_id].clone();
+
+        // The checkpoint now fills the limit, and stage2 is a checkpoint
+        // too; the repeat is still recognised as already applied.
+        report(&mut kernel).unwrap();
+        assert_eq!(kernel.runs[&run_id], after);
+    }
+
+    #[test]
+    fn test_wait_interrupt_carries_poll_hint() {
+        use crate::kernel::protocol::Instruction;
+        use crate::run::FlowInterrupt;
+
+        let mut kernel = Kernel::new();
+        kernel.set_max_active_runs(Some(2)).unwrap();
+        let run_id = RunId::must("poll-hint");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        kernel.set_run_interrupt(&run_id, FlowInterrupt::new()).unwrap();
+
+        // Default 1s, stretched 1.5× with one of two slots taken.
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::WaitInterrupt { poll_after_ms, .. } => assert_eq!(poll_after_ms, Some(1_500)),
+            other => panic!("expected WaitInterrupt, got {:?}", other),
+        }
+
+        let mut expiring = FlowInterrupt::new().with_message("expiring".to_string());
+        expiring.expires_at = Some(chrono::Utc::now() + chrono::TimeDelta::milliseconds(400));
+        kernel.set_run_interrupt(&run_id, expiring).unwrap();
+        match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::WaitInterrupt { poll_after_ms: Some(ms), .. } => assert!((interrupts::MIN_POLL_MS..=400).contains(&ms)),
+            other => panic!("expected WaitInterrupt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_interrupt_flood_is_capped_per_session() {
+        use crate::run::FlowInterrupt;
+
+        let mut kernel = Kernel::new();
+        kernel.set_interrupt_limits(InterruptLimits { max_pending_per_session: Some(2), ..Default::default() });
+        let run_id = RunId::must("flood");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let flood = |n: usize| FlowInterrupt::new().with_message(format!("attempt {}", n));
+        kernel.set_run_interrupt(&run_id, flood(1)).unwrap();
+        kernel.set_run_interrupt(&run_id, flood(2)).unwrap();
+        let err = kernel.set_run_interrupt(&run_id, flood(3)).unwrap_err();
+        assert!(matches!(err, crate::types::Error::QuotaExceeded(_)));
+        assert_eq!(kernel.interrupts.stats().suppressed.get(&InterruptKind::Message), Some(&1));
+    }
+
+    #[test]
+    fn test_terminated_run_interrupts_leave_the_cap() {
+        use crate::run::FlowInterrupt;
+        use std::sync::Arc;
+
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut kernel = Kernel::new();
+        kernel.set_clock(clock.clone());
+        kernel.set_interrupt_limits(InterruptLimits { max_pending: Some(1), ..Default::default() });
+        let ask = || FlowInterrupt::new().with_question("Proceed?".to_string());
+        let mut start = |id: &str| {
+            let run_id = RunId::must(id);
+            let _ = kernel.initialize_run(
+                run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+            ).unwrap();
+            run_id
+        };
+        let (r1, r2) = (start("r1"), start("r2"));
+
+        kernel.set_run_interrupt(&r1, ask()).unwrap();
+        kernel.terminate_run(&r1).unwrap();
+        assert_eq!(kernel.interrupts.pending_count(), 0);
+        kernel.set_run_interrupt(&r2, ask()).unwrap();
+
+        clock.advance(chrono::TimeDelta::seconds(600));
+        assert_eq!(kernel.cleanup_stale_sessions(300), 1);
+        assert_eq!(kernel.interrupts.pending_count(), 0);
+        let r3 = RunId::must("r3");
+        let _ = kernel.initialize_run(
+            r3.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        kernel.set_run_interrupt(&r3, ask()).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_interrupt_is_coalesced() {
+        use crate::run::FlowInterrupt;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("coalesce");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let ask = || FlowInterrupt::new().with_question("Which branch?".to_string());
+        let first = kernel.set_run_interrupt(&run_id, ask()).unwrap();
+        let second = kernel.set_run_interrupt(&run_id, ask()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(kernel.interrupts.pending_count(), 1);
+        assert_eq!(kernel.runs[&run_id].interrupts.history.len(), 1);
+
+        let different = kernel.set_run_interrupt(&run_id, FlowInterrupt::new().with_question("Which tag?".to_string())).unwrap();
+        assert_ne!(first, different);
+    }
+
+    #[test]
+    fn test_resolution_is_checked_against_response_spec() {
+        use crate::run::{FlowInterrupt, InterruptResponse, ResponseSpec, ResponseViolation};
+        use std::error::Error as _;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("response-spec");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let spec = ResponseSpec {
+            allowed_decisions: vec!["merge".to_string(), "close".to_string()],
+            required_data: vec!["reason".to_string()],
+            ..Default::default()
+        };
+        let id = kernel.set_run_interrupt(&run_id, FlowInterrupt::new().with_message("Triage".to_string()).with_response_spec(spec)).unwrap();
+        let response = |decision: &str, data: Option<HashMap<String, serde_json::Value>>| InterruptResponse {
+            text: None,
+            approved: None,
+            decision: Some(decision.to_string()),
+            data,
+            responder: None,
+            received_at: chrono::Utc::now(),
+        };
+
+        let err = kernel.resolve_run_interrupt(&run_id, id.as_str(), response("rebase", None)).unwrap_err();
+        let violation = err.source().and_then(|s| s.downcast_ref::<ResponseViolation>()).unwrap();
+        assert_eq!(violation.missing, vec!["data.reason".to_string()]);
+        assert_eq!(violation.decision.as_deref(), Some("rebase"));
+        assert!(kernel.runs[&run_id].interrupts.interrupt.is_some());
+
+        let data = HashMap::from([("reason".to_string(), serde_json::json!("stale"))]);
+        kernel.resolve_run_interrupt(&run_id, id.as_str(), response("close", Some(data))).unwrap();
+        assert!(kernel.runs[&run_id].interrupts.interrupt.is_none());
+
+        // A question needs some answer even without a spec.
+        let question = kernel.set_run_interrupt(&run_id, FlowInterrupt::new().with_question("Why?".to_string())).unwrap();
+        let mut empty = response("x", None);
+        empty.decision = None;
+        assert!(kernel.resolve_run_interrupt(&run_id, question.as_str(), empty).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_dispatch_report_is_ignored() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("idem");
+        let _ = kernel.initialize_orchestration(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false,
+        ).unwrap();
+
+        let dispatch_id = match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => context.dispatch_id.unwrap(),
+            other => panic!("expected RunAgent, got {:?}", other),
+        };
+        let metrics = crate::agent::metrics::AgentExecutionMetrics { llm_calls: 1, ..Default::default() };
+        for _ in 0..2 {
+            kernel.process_agent_result(
+                &run_id, "agent1", serde_json::json!({}), None,
+                metrics.clone(), true, "", false, Some(&dispatch_id),
+            ).unwrap();
+        }
+
+        let run = &kernel.runs[&run_id];
+        assert_eq!(run.metrics.llm_calls, 1);
+        assert_eq!(run.current_stage.as_str(), "stage2");
+        assert_eq!(run.audit.processing_history.len(), 1);
+
+        // A token this run never issued is refused, not merged.
+        let forged = kernel.process_agent_result(
+            &run_id, "agent2", serde_json::json!({}), None,
+            metrics, true, "", false, Some("forged"),
+        );
+        assert!(matches!(forged, Err(crate::types::Error::Validation { .. })));
+        assert_eq!(kernel.runs[&run_id].audit.processing_history.len(), 1);
+    }
+
+    #[test]
+    fn test_failed_dispatch_report_can_be_retried() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("retry-report");
+        let _ = kernel.initialize_orchestration(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false,
+        ).unwrap();
+        let dispatch_id = match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => context.dispatch_id.unwrap(),
+            other => panic!("expected RunAgent, got {:?}", other),
+        };
+
+        // The report fails partway (the run is briefly missing); the token
+        // stays outstanding.
+        let run = kernel.runs.remove(&run_id).unwrap();
+        let report = |kernel: &mut Kernel| kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({}), None, Default::default(), true, "", false, Some(&dispatch_id),
+        );
+        assert!(report(&mut kernel).is_err());
+        kernel.runs.insert(run_id.clone(), run);
+
+        report(&mut kernel).unwrap();
+        let run = &kernel.runs[&run_id];
+        assert_eq!(run.current_stage.as_str(), "stage2");
+        assert_eq!(run.audit.processing_history.len(), 1);
+
+        // Now it counts as reported.
+        report(&mut kernel).unwrap();
+        assert_eq!(kernel.runs[&run_id].audit.processing_history.len(), 1);
+    }
+
+    #[test]
+    fn test_refused_report_records_nothing_before_retry() {
+        use crate::agent::metrics::{AgentExecutionMetrics, ToolCallResult};
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("refused-report");
+        let _ = kernel.initialize_run(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None,
+        ).unwrap();
+        let dispatch_id = match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => context.dispatch_id.unwrap(),
+            other => panic!("expected RunAgent, got {:?}", other),
+        };
+        let metrics = AgentExecutionMetrics {
+            llm_calls: 1,
+            tool_results: vec![ToolCallResult { name: "search".into(), success: true, latency_ms: 5, error_type: None }],
+            ..Default::default()
+        };
+        let report = |kernel: &mut Kernel| kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"answer": 42}), None,
+            metrics.clone(), true, "", false, Some(&dispatch_id),
+        );
+
+        // A stage the workflow doesn't know fails the report at routing.
+        kernel.runs.get_mut(&run_id).unwrap().current_stage = "ghost".into();
+        let before = kernel.runs[&run_id].clone();
+        assert!(matches!(report(&mut kernel).unwrap_err(), crate::types::Error::StateTransition(_)));
+        assert_eq!(kernel.runs[&run_id], before);
+        assert_eq!(kernel.tools.health.check_tool_health("search").total_calls, 0);
+        assert!(kernel.resources.stage_median("test_workflow", "ghost").is_none());
+        assert_eq!(kernel.resources.total_usage().llm_calls, 0);
+
+        kernel.runs.get_mut(&run_id).unwrap().current_stage = "stage1".into();
+        report(&mut kernel).unwrap();
+        let run = &kernel.runs[&run_id];
+        assert_eq!(run.metrics.llm_calls, 1);
+        assert_eq!(run.current_stage.as_str(), "stage2");
+        assert_eq!(run.audit.processing_history.len(), 1);
+        assert_eq!(kernel.tools.health.check_tool_health("search").total_calls, 1);
+        assert_eq!(kernel.resources.stage_median("test_workflow", "stage1").unwrap().1, 1);
+    }
+
+    #[test]
+    fn test_search_runs_filters_and_pages() {
+        use crate::run::Run;
+
+        let mut kernel = Kernel::new();
+        for (id, user, tier) in [("r1", "alice", "gold"), ("r2", "alice", "free"), ("r3", "bob", "gold"), ("r4", "alice", "gold")] {
+            let run = Run::new(user, "s", "hi", Some(serde_json::json!({"tier": tier})));
+            let _ = kernel.initialize_run(RunId::must(id), test_helpers::create_test_workflow(), run, false, None).unwrap();
+        }
+
+        let query = RunQuery {
+            user_id: Some("alice".into()),
+            metadata: HashMap::from([("tier".to_string(), serde_json::json!("gold"))]),
+            limit: 1,
+            ..RunQuery::default()
+        };
+        let first = kernel.search_runs(&query);
+        assert_eq!((first.total, first.runs.len(), first.next_offset), (2, 1, Some(1)));
+        let second = kernel.search_runs(&RunQuery { offset: 1, ..query.clone() });
+        assert_eq!(second.next_offset, None);
+        let mut ids = vec![first.runs[0].run_id.as_str(), second.runs[0].run_id.as_str()];
+        ids.sort();
+        assert_eq!(ids, ["r1", "r4"]);
+
+        let by_workflow = kernel.search_runs(&RunQuery { workflow: Some("other".into()), ..RunQuery::default() });
+        assert_eq!(by_workflow.total, 0);
+        assert_eq!(kernel.search_runs(&RunQuery::default()).total, 4);
+    }
+
+    #[test]
+    fn test_external_ref_is_searchable() {
+        use crate::run::Run;
+
+        let mut kernel = Kernel::new();
+        let mut run = Run::new("u", "s", "hi", None);
+        run.identity.external_ref = Some("ZD-4411".into());
+        let (record, _) = kernel.initialize_run(RunId::must("ext"), test_helpers::create_test_workflow(), run, false, None).unwrap();
+        assert_eq!(record.external_ref.as_deref(), Some("ZD-4411"));
+        let _ = kernel.initialize_run(RunId::must("plain"), test_helpers::create_test_workflow(), Run::new("u", "s", "hi", None), false, None).unwrap();
+
+        let page = kernel.search_runs(&RunQuery { external_ref: Some("ZD-4411".into()), ..RunQuery::default() });
+        assert_eq!(page.runs.len(), 1);
+        assert_eq!(page.runs[0].run_id.as_str(), "ext");
+        assert_eq!(kernel.runs[&RunId::must("ext")].identity.external_ref.as_deref(), Some("ZD-4411"));
+    }
+
+    #[test]
+    fn test_outputs_carry_provenance() {
+        use crate::kernel::protocol::Instruction;
+
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("provenance");
+        let _ = kernel.initialize_orchestration(
+            run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false,
+        ).unwrap();
+        let dispatch_id = match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::RunAgent { context, .. } => context.dispatch_id.unwrap(),
+            other => panic!("expected RunAgent, got {:?}", other),
+        };
+        let metrics = crate::agent::metrics::AgentExecutionMetrics { model: Some("fast-1".into()), ..Default::default() };
+        kernel.process_agent_result(
+            &run_id, "agent1", serde_json::json!({"answer": 42, "notes": ["a"]}), None,
+            metrics, true, "", false, Some(&dispatch_id),
+        ).unwrap();
+
+        let run = kernel.runs.get_mut(&run_id).unwrap();
+        let stamp = &run.outputs_provenance["agent1"];
+        assert_eq!((stamp.stage.as_str(), stamp.model.as_deref()), ("stage1", Some("fast-1")));
+        assert_eq!(stamp.dispatch_id.as_deref(), Some(dispatch_id.as_str()));
+        assert_eq!(run.output_matches_provenance("agent1"), Some(true));
+
+        run.outputs.get_mut("agent1").unwrap().insert("answer".into(), serde_json::json!(43));
+        assert_eq!(run.output_matches_provenance("agent1"), Some(false));
+        assert_eq!(run.output_matches_provenance("agent2"), None);
+    }
+
+    #[test]
+    fn test_repeated_output_is_stored_as_reference() {
+        let workflow: crate::workflow::Workflow = serde_json::from_value(serde_json::json!({
+            "name": "loop",
+            "stages": [{"name": "draft", "agent": "draft", "default_next": "draft", "max_visits": 10}],
+            "state_schema": [{"key": "draft", "merge": "AppendDedup"}],
+            "max_iterations": 10,
+            "max_llm_calls": 10,
+            "max_agent_hops": 10,
+        })).unwrap();
+        let mut kernel = Kernel::new();
+        let run_id = RunId::must("dedup");
+        let _ = kernel.initialize_orchestration(run_id.clone(), workflow, test_helpers::create_test_run(), false).unwrap();
+        for text in ["a", "a", "b", "b", "b"] {
+            let _ = kernel.get_next_instruction(&run_id).unwrap();
+            kernel.process_agent_result(
+                &run_id, "draft", serde_json::json!({"text": text}), None,
+                Default::default(), true, "", false, None,
+            ).unwrap();
+        }
+
+        let run = &kernel.runs[&run_id];
+        assert_eq!(run.state["draft"], serde_json::json!([
+            {"text": "a"}, {"$same_as": 0}, {"text": "b"}, {"$same_as": 2}, {"$same_as": 2},
+        ]));
+        assert_eq!(run.metrics.outputs_deduplicated, 3);
+        assert_eq!(run.metrics.dedup_bytes_saved, 3 * r#"{"text":"a"}"#.len() as i64);
+    }
 }
 
 #[cfg(test)]
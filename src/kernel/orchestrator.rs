@@ -20,8 +20,8 @@ use tracing::instrument;
 pub use super::protocol::{Instruction, RunSnapshot};
 pub use crate::agent::metrics::AgentExecutionMetrics;
 pub use super::routing::{
-    evaluate_routing_with_reason, RoutingContext, RoutingDecision, RoutingFn, RoutingReason,
-    RoutingRegistry, RoutingResult,
+    evaluate_routing_with_reason, RouteCount, RoutingContext, RoutingDecision, RoutingFn,
+    RoutingReason, RoutingRegistry, RoutingResult,
 };
 pub use crate::workflow::{Workflow, Stage};
 
@@ -41,6 +41,45 @@ pub(crate) fn get_agent_for_stage(
         .ok_or_else(|| Error::not_found(format!("Stage not found in workflow: {}", stage_name)))
 }
 
+/// Changes to the run that come with an instruction from
+/// [`Orchestrator::next_instruction`]. The run's owner applies them, so a
+/// caller-visible change goes through `RunStore::edit`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunChanges {
+    /// The pending interrupt expired and is cleared.
+    pub expire_interrupt: bool,
+    /// The run is past its bounds and terminates.
+    pub terminate: Option<TerminalReason>,
+}
+
+impl RunChanges {
+    pub fn is_empty(&self) -> bool {
+        !self.expire_interrupt && self.terminate.is_none()
+    }
+
+    pub fn apply(self, run: &mut Run) {
+        if self.expire_interrupt {
+            run.clear_interrupt();
+        }
+        if let Some(reason) = self.terminate {
+            run.terminate_with(reason, None);
+        }
+    }
+}
+
+/// Fold one agent execution's metrics into the run and count the iteration.
+fn record_metrics(run: &mut Run, metrics: &AgentExecutionMetrics) {
+    run.metrics.llm_calls += metrics.llm_calls;
+    run.metrics.tool_calls += metrics.tool_calls;
+    if let Some(tokens_in) = metrics.tokens_in {
+        run.metrics.tokens_in += tokens_in;
+    }
+    if let Some(tokens_out) = metrics.tokens_out {
+        run.metrics.tokens_out += tokens_out;
+    }
+    run.iteration += 1;
+}
+
 /// Orchestration represents an active workflow execution session.
 ///
 /// The session tracks workflow execution state only (workflow definition,
@@ -54,15 +93,43 @@ pub struct Orchestration {
     #[allow(dead_code)] // Retained for diagnostics
     pub(crate) created_at: DateTime<Utc>,
     pub(crate) last_activity_at: DateTime<Utc>,
+    /// When the run entered its current stage; feeds `RoutingContext::stage_elapsed`.
+    pub(crate) stage_entered_at: DateTime<Utc>,
     /// Last routing decision made by report_agent_result (consumed by get_next_instruction).
     pub(crate) last_routing_decision: Option<super::routing::RoutingDecision>,
+    /// Per-route match counts, in first-seen order.
+    pub(crate) route_counts: Vec<super::routing::RouteCount>,
+    /// Output-schema retries spent on the current stage visit.
+    pub(crate) output_retries: u32,
+    /// Tokens handed out with `RunAgent` and not yet reported, oldest first.
+    pub(crate) issued_dispatches: std::collections::VecDeque<String>,
+    /// Most recently reported dispatch tokens, oldest first.
+    pub(crate) reported_dispatches: std::collections::VecDeque<String>,
+    /// Token of the `RunAgent` awaiting its result, if any.
+    pub(crate) current_dispatch: Option<String>,
+    /// Agent version resolved for `current_dispatch`.
+    pub(crate) dispatched_version: Option<String>,
+    /// Latest heartbeat for `current_dispatch`.
+    pub(crate) heartbeat: Option<super::AgentHeartbeat>,
+    /// Stage whose cost-confirmation interrupt is awaiting (or has just
+    /// received) its answer.
+    pub(crate) cost_confirmation: Option<crate::types::StageName>,
 }
 
+/// How many issued and reported dispatch tokens a session remembers, each.
+const DISPATCH_DEDUP_WINDOW: usize = 32;
+
 /// Orchestrator manages kernel-side workflow execution.
 #[derive(Debug)]
 pub struct Orchestrator {
     pub(crate) sessions: HashMap<RunId, Orchestration>,
     pub(crate) routing_registry: RoutingRegistry,
+    /// Workflow name → max concurrently active runs.
+    pub(crate) concurrency_limits: HashMap<String, usize>,
+    /// Time source for expiry, staleness and execution windows.
+    pub(crate) clock: super::clock::SharedClock,
+    /// Bounds for workflows that leave them at 0.
+    pub(crate) bounds: crate::run::BoundsDefaults,
 }
 
 impl Orchestrator {
@@ -70,6 +137,9 @@ impl Orchestrator {
         Self {
             sessions: HashMap::new(),
             routing_registry: RoutingRegistry::new(),
+            concurrency_limits: HashMap::new(),
+            clock: super::clock::system(),
+            bounds: crate::run::BoundsDefaults::default(),
         }
     }
 
@@ -80,45 +150,60 @@ impl Orchestrator {
     /// Decide what to run next for `run_id`. Returns one of `RunAgent`,
     /// `Terminate`, or `WaitInterrupt`. The run may be mutated for
     /// bounds-driven termination.
-    #[instrument(skip(self, run), fields(run_id = %run_id))]
     pub fn get_next_instruction(
         &mut self,
         run_id: &RunId,
         run: &mut Run,
     ) -> Result<Instruction> {
+        let (instruction, changes) = self.next_instruction(run_id, run)?;
+        changes.apply(run);
+        Ok(instruction)
+    }
+
+    /// [`Self::get_next_instruction`] without touching the run: the changes
+    /// it calls for (an expired interrupt, bounds-driven termination) are
+    /// returned for the caller to apply.
+    #[instrument(skip(self, run), fields(run_id = %run_id))]
+    pub fn next_instruction(
+        &mut self,
+        run_id: &RunId,
+        run: &Run,
+    ) -> Result<(Instruction, RunChanges)> {
+        let mut changes = RunChanges::default();
         let session = self
             .sessions
             .get_mut(run_id)
             .ok_or_else(|| Error::not_found(format!("Unknown process: {}", run_id)))?;
-        session.last_activity_at = Utc::now();
+        session.last_activity_at = self.clock.now();
 
         if run.is_terminated() {
-            return Ok(Instruction::terminate(
+            return Ok((Instruction::terminate(
                 run.terminal_reason().unwrap_or(TerminalReason::Completed),
                 "Session already terminated",
-            ));
+            ), changes));
         }
 
         // Pending tool-confirmation interrupt suspends the stage.
         if run.interrupts.is_pending() {
             let expired = run.interrupts.interrupt.as_ref()
                 .and_then(|i| i.expires_at)
-                .map(|exp| Utc::now() > exp)
+                .map(|exp| self.clock.now() > exp)
                 .unwrap_or(false);
             if expired {
-                run.clear_interrupt();
+                changes.expire_interrupt = true;
                 // Fall through to dispatch the agent again now that the interrupt is gone.
             } else {
-                return Ok(Instruction::WaitInterrupt {
+                return Ok((Instruction::WaitInterrupt {
                     interrupt: run.interrupts.interrupt.clone(),
-                });
+                    poll_after_ms: None,
+                }, changes));
             }
         }
 
         if let Some(reason) = run.check_bounds() {
             tracing::warn!(reason = ?reason, "bounds_terminated");
-            run.terminate_with(reason, None);
-            return Ok(Instruction::terminate(reason, format!("Bounds exceeded: {:?}", reason)));
+            changes.terminate = Some(reason);
+            return Ok((Instruction::terminate(reason, format!("Bounds exceeded: {:?}", reason)), changes));
         }
 
         let current_stage = &run.current_stage;
@@ -126,8 +211,12 @@ impl Orchestrator {
             return Err(Error::state_transition("No current stage set"));
         }
 
+        if let Some(until) = crate::workflow::window::next_open(&session.workflow.execution_windows, self.clock.now())? {
+            return Ok((Instruction::WaitWindow { until }, changes));
+        }
+
         let agent_name = get_agent_for_stage(&session.workflow, current_stage.as_str())?;
-        Ok(Instruction::run_agent(agent_name.as_str()))
+        Ok((Instruction::run_agent(agent_name.as_str()), changes))
     }
 
     /// Process agent execution result and advance the workflow.
@@ -151,17 +240,18 @@ impl Orchestrator {
             .sessions
             .get_mut(run_id)
             .ok_or_else(|| Error::not_found(format!("Unknown process: {}", run_id)))?;
+        let current_stage = run.current_stage.clone();
+        let pipeline_stage = session.workflow.stages
+            .iter()
+            .find(|s| s.name.as_str() == current_stage.as_str())
+            .ok_or_else(|| Error::state_transition(format!(
+                "Current stage '{}' not found in workflow",
+                current_stage
+            )))?
+            .clone();
 
-        // Bookkeeping
-        run.metrics.llm_calls += metrics.llm_calls;
-        run.metrics.tool_calls += metrics.tool_calls;
-        if let Some(tokens_in) = metrics.tokens_in {
-            run.metrics.tokens_in += tokens_in;
-        }
-        if let Some(tokens_out) = metrics.tokens_out {
-            run.metrics.tokens_out += tokens_out;
-        }
-        run.iteration += 1;
+        record_metrics(run, &metrics);
+        session.output_retries = 0;
 
         if let Some(reason) = run.check_bounds() {
             run.terminate_with(reason, None);
@@ -170,20 +260,10 @@ impl Orchestrator {
 
         if break_loop {
             run.terminate_with(TerminalReason::BreakRequested, None);
-            session.last_activity_at = Utc::now();
+            session.last_activity_at = self.clock.now();
             return Ok(());
         }
 
-        let current_stage = run.current_stage.clone();
-        let pipeline_stage = session.workflow.stages
-            .iter()
-            .find(|s| s.name.as_str() == current_stage.as_str())
-            .ok_or_else(|| Error::state_transition(format!(
-                "Current stage '{}' not found in workflow",
-                current_stage
-            )))?
-            .clone();
-
         *session.stage_visits.entry(current_stage.clone()).or_insert(0) += 1;
 
         let agent_lookup = pipeline_stage.agent.clone();
@@ -191,6 +271,7 @@ impl Orchestrator {
             .and_then(|i| i.response.as_ref())
             .and_then(|r| serde_json::to_value(r).ok());
 
+        let now = self.clock.now();
         let ctx = RoutingContext {
             current_stage: current_stage.as_str(),
             agent_name: agent_lookup.as_str(),
@@ -199,6 +280,9 @@ impl Orchestrator {
             metadata: &run.audit.metadata,
             interrupt_response: interrupt_response.as_ref(),
             state: &run.state,
+            iteration: run.iteration,
+            run_elapsed: (now - run.received_at).to_std().unwrap_or_default(),
+            stage_elapsed: (now - session.stage_entered_at).to_std().unwrap_or_default(),
         };
         let routing_decision = evaluate_routing_with_reason(
             &pipeline_stage,
@@ -209,14 +293,102 @@ impl Orchestrator {
         let next_target = routing_decision.target.clone();
 
         if let Some(session) = self.sessions.get_mut(run_id) {
+            super::routing::record_route(&mut session.route_counts, &routing_decision);
             session.last_routing_decision = Some(routing_decision);
         }
 
         self.apply_routing_result(run_id, current_stage.as_str(), next_target, run)
     }
 
+    /// Whether a report for `dispatch_id` should be applied. Returns
+    /// `false` if it was already reported (within the last
+    /// `DISPATCH_DEDUP_WINDOW` reports), and fails if it is not a token this
+    /// session issued and is still waiting on. Changes nothing.
+    pub fn check_dispatch_report(&self, run_id: &RunId, dispatch_id: &str) -> Result<bool> {
+        let session = self
+            .sessions
+            .get(run_id)
+            .ok_or_else(|| Error::not_found(format!("Unknown process: {}", run_id)))?;
+        if session.reported_dispatches.iter().any(|d| d == dispatch_id) {
+            return Ok(false);
+        }
+        if !session.issued_dispatches.iter().any(|d| d == dispatch_id) {
+            return Err(Error::validation(format!(
+                "Dispatch {} is not outstanding for run {}",
+                dispatch_id, run_id
+            )));
+        }
+        Ok(true)
+    }
+
+    /// Remember `dispatch_id` as reported, once its result has been applied.
+    pub fn record_dispatch_report(&mut self, run_id: &RunId, dispatch_id: &str) {
+        let Some(session) = self.sessions.get_mut(run_id) else { return };
+        let Some(issued) = session.issued_dispatches.iter().position(|d| d == dispatch_id) else {
+            return;
+        };
+        session.issued_dispatches.remove(issued);
+        if session.reported_dispatches.len() == DISPATCH_DEDUP_WINDOW {
+            session.reported_dispatches.pop_front();
+        }
+        session.reported_dispatches.push_back(dispatch_id.to_string());
+        if session.current_dispatch.as_deref() == Some(dispatch_id) {
+            session.current_dispatch = None;
+            session.heartbeat = None;
+        }
+    }
+
+    /// Record that `dispatch_id` was handed out; any earlier heartbeat
+    /// belonged to the previous dispatch.
+    pub(crate) fn record_dispatch(&mut self, run_id: &RunId, dispatch_id: &str) {
+        if let Some(session) = self.sessions.get_mut(run_id) {
+            if session.issued_dispatches.len() == DISPATCH_DEDUP_WINDOW {
+                session.issued_dispatches.pop_front();
+            }
+            session.issued_dispatches.push_back(dispatch_id.to_string());
+            session.current_dispatch = Some(dispatch_id.to_string());
+            session.heartbeat = None;
+        }
+    }
+
+    /// Claim one output-schema retry for the run's current stage. Returns
+    /// `false` once the stage's `output_schema_retries` budget is spent.
+    pub fn claim_output_retry(&mut self, run_id: &RunId, stage_name: &str) -> bool {
+        let Some(session) = self.sessions.get_mut(run_id) else { return false };
+        let limit = session.workflow.stages.iter()
+            .find(|s| s.name.as_str() == stage_name)
+            .and_then(|s| s.output_schema_retries)
+            .unwrap_or(0);
+        if session.output_retries >= limit {
+            return false;
+        }
+        session.output_retries += 1;
+        true
+    }
+
+    /// Record a rejected attempt without routing: metrics and the iteration
+    /// counter advance, bounds are enforced, and the current stage stays put
+    /// so the next instruction re-dispatches the same agent.
+    pub fn retry_current_stage(
+        &mut self,
+        run_id: &RunId,
+        metrics: AgentExecutionMetrics,
+        run: &mut Run,
+    ) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(run_id)
+            .ok_or_else(|| Error::not_found(format!("Unknown process: {}", run_id)))?;
+        record_metrics(run, &metrics);
+        session.last_activity_at = self.clock.now();
+        if let Some(reason) = run.check_bounds() {
+            run.terminate_with(reason, None);
+        }
+        Ok(())
+    }
+
     /// Advance to the next stage or terminate.
-    fn apply_routing_result(
+    pub(crate) fn apply_routing_result(
         &mut self,
         run_id: &RunId,
         from_stage: &str,
@@ -238,7 +410,7 @@ impl Orchestrator {
                                 TerminalReason::MaxStageVisitsExceeded,
                                 Some(format!("Stage '{}' exceeded max_visits limit of {}", target, max_visits)),
                             );
-                            session.last_activity_at = Utc::now();
+                            session.last_activity_at = self.clock.now();
                             return Ok(());
                         }
                     }
@@ -248,12 +420,14 @@ impl Orchestrator {
                 tracing::info!(from = %from_stage, to = %target, "stage_transition");
 
                 run.current_stage = target;
-                session.last_activity_at = Utc::now();
+                let now = self.clock.now();
+                session.last_activity_at = now;
+                session.stage_entered_at = now;
             }
             None => {
                 tracing::info!(reason = ?TerminalReason::Completed, "run_completed");
                 run.terminate_with(TerminalReason::Completed, None);
-                session.last_activity_at = Utc::now();
+                session.last_activity_at = self.clock.now();
             }
         }
 
@@ -293,7 +467,7 @@ mod tests {
         let run_id = RunId::must("p1");
         let mut run = make_run(&config);
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         let instr = orch.get_next_instruction(&run_id, &mut run).unwrap();
         match instr {
@@ -302,6 +476,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn closed_execution_window_returns_wait_window() {
+        let mut config = Workflow::test_default("p", vec![linear_stage("s1", None)]);
+        let opens = Utc::now() + chrono::Duration::hours(2);
+        config.execution_windows = vec![crate::workflow::ExecutionWindow {
+            days: vec![],
+            start: opens.format("%H:%M").to_string(),
+            end: (opens + chrono::Duration::hours(1)).format("%H:%M").to_string(),
+            utc_offset_minutes: 0,
+        }];
+        let run_id = RunId::must("p1");
+        let mut run = make_run(&config);
+        let mut orch = Orchestrator::new();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+
+        match orch.get_next_instruction(&run_id, &mut run).unwrap() {
+            Instruction::WaitWindow { until } => assert!(until > Utc::now()),
+            other => panic!("expected WaitWindow, got {:?}", other),
+        }
+    }
+
     #[test]
     fn already_terminated_returns_terminate() {
         let config = Workflow::test_default("p", vec![linear_stage("s1", None)]);
@@ -310,7 +505,7 @@ mod tests {
         run.terminate_with(TerminalReason::Completed, None);
 
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
         let instr = orch.get_next_instruction(&run_id, &mut run).unwrap();
         assert!(matches!(instr, Instruction::Terminate { .. }));
     }
@@ -324,7 +519,7 @@ mod tests {
         run.set_interrupt(FlowInterrupt::new());
 
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
         let instr = orch.get_next_instruction(&run_id, &mut run).unwrap();
         assert!(matches!(instr, Instruction::WaitInterrupt { .. }));
     }
@@ -338,7 +533,7 @@ mod tests {
         let run_id = RunId::must("p1");
         let mut run = make_run(&config);
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         // first dispatch is s1
         let instr = orch.get_next_instruction(&run_id, &mut run).unwrap();
@@ -371,12 +566,80 @@ mod tests {
         orch.register_routing_fn("decide", Arc::new(|_ctx: &RoutingContext<'_>| {
             RoutingResult::Next("target".into())
         }));
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         orch.report_agent_result(&run_id, "s1", zero_metrics(), &mut run, false, false).unwrap();
         assert_eq!(run.current_stage.as_str(), "target");
     }
 
+    #[test]
+    fn routing_fn_sees_iteration_and_elapsed() {
+        let config = Workflow::test_default("p", vec![
+            Stage {
+                name: "loop".into(),
+                agent: "loop".into(),
+                routing_fn: Some("until_third".into()),
+                ..Stage::default()
+            },
+            linear_stage("summarize", None),
+        ]);
+        let run_id = RunId::must("p1");
+        let mut run = make_run(&config);
+        run.received_at = Utc::now() - chrono::Duration::seconds(90);
+        let mut orch = Orchestrator::new();
+        orch.register_routing_fn("until_third", Arc::new(|ctx: &RoutingContext<'_>| {
+            assert!(ctx.run_elapsed >= std::time::Duration::from_secs(90));
+            assert!(ctx.stage_elapsed < ctx.run_elapsed);
+            if ctx.iteration >= 3 {
+                RoutingResult::Next("summarize".into())
+            } else {
+                RoutingResult::Next("loop".into())
+            }
+        }));
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+
+        for _ in 0..2 {
+            orch.report_agent_result(&run_id, "loop", zero_metrics(), &mut run, false, false).unwrap();
+            assert_eq!(run.current_stage.as_str(), "loop");
+        }
+        orch.report_agent_result(&run_id, "loop", zero_metrics(), &mut run, false, false).unwrap();
+        assert_eq!(run.current_stage.as_str(), "summarize");
+    }
+
+    #[test]
+    fn route_counts_track_each_route_taken() {
+        let config = Workflow::test_default("p", vec![
+            Stage {
+                name: "loop".into(),
+                agent: "loop".into(),
+                routing_fn: Some("twice".into()),
+                ..Stage::default()
+            },
+            linear_stage("done", None),
+        ]);
+        let run_id = RunId::must("p1");
+        let mut run = make_run(&config);
+        let mut orch = Orchestrator::new();
+        orch.register_routing_fn("twice", Arc::new(|ctx: &RoutingContext<'_>| {
+            if ctx.iteration >= 3 { RoutingResult::Next("done".into()) } else { RoutingResult::Next("loop".into()) }
+        }));
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+
+        for _ in 0..3 {
+            orch.report_agent_result(&run_id, "loop", zero_metrics(), &mut run, false, false).unwrap();
+        }
+        orch.report_agent_result(&run_id, "done", zero_metrics(), &mut run, false, false).unwrap();
+
+        let counts = orch.get_session_state(&run_id, &run).unwrap().route_counts;
+        let count_of = |from: &str, to: Option<&str>| counts.iter()
+            .find(|c| c.from_stage.as_str() == from && c.target.as_ref().map(|t| t.as_str()) == to)
+            .map(|c| c.count);
+        assert_eq!(count_of("loop", Some("loop")), Some(2));
+        assert_eq!(count_of("loop", Some("done")), Some(1));
+        assert_eq!(count_of("done", None), Some(1));
+        assert_eq!(counts[2].reason, RoutingReason::NoMatch);
+    }
+
     #[test]
     fn error_next_routes_on_failure() {
         let config = Workflow::test_default("p", vec![
@@ -393,7 +656,7 @@ mod tests {
         let run_id = RunId::must("p1");
         let mut run = make_run(&config);
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         orch.report_agent_result(&run_id, "s1", zero_metrics(), &mut run, true, false).unwrap();
         assert_eq!(run.current_stage.as_str(), "s_err");
@@ -411,7 +674,7 @@ mod tests {
         let run_id = RunId::must("p1");
         let mut run = make_run(&config);
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         // First report: visit count was 0, becomes 1, transitions back to loop (visits=1, allowed since limit=2)
         orch.report_agent_result(&run_id, "loop", zero_metrics(), &mut run, false, false).unwrap();
@@ -427,7 +690,7 @@ mod tests {
         let run_id = RunId::must("p1");
         let mut run = make_run(&config);
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         orch.report_agent_result(&run_id, "s1", zero_metrics(), &mut run, false, true).unwrap();
         assert_eq!(run.terminal_reason(), Some(TerminalReason::BreakRequested));
@@ -446,7 +709,7 @@ mod tests {
         let run_id = RunId::must("p1");
         let mut run = make_run(&config);
         let mut orch = Orchestrator::new();
-        orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
+        let _ = orch.initialize_session(run_id.clone(), config, &mut run, false).unwrap();
 
         orch.report_agent_result(&run_id, "s1", zero_metrics(), &mut run, false, false).unwrap();
         assert!(run.is_terminated());
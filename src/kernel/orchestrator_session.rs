@@ -2,10 +2,10 @@
 
 use crate::run::Run;
 use crate::types::{Error, RunId, Result};
-use chrono::Utc;
 use tracing::instrument;
 
 use super::orchestrator::{Orchestrator, Orchestration};
+use super::types::{QuotaMergePolicy, ResourceQuota};
 use crate::workflow::{Workflow};
 use crate::kernel::protocol::{RunSnapshot};
 
@@ -34,6 +34,17 @@ impl Orchestrator {
         // Validate workflow.
         workflow.validate()?;
 
+        // Bounds left at 0 take the kernel defaults.
+        let mut workflow = workflow;
+        let bounds = ResourceQuota::unset()
+            .with_max_iterations(workflow.max_iterations)
+            .with_max_llm_calls(workflow.max_llm_calls)
+            .with_max_agent_hops(workflow.max_agent_hops)
+            .merge_with_policy(&self.bounds.into(), QuotaMergePolicy::FillUnset);
+        workflow.max_iterations = bounds.max_iterations;
+        workflow.max_llm_calls = bounds.max_llm_calls;
+        workflow.max_agent_hops = bounds.max_agent_hops;
+
         // Initialize run with workflow bounds
         run.max_iterations = workflow.max_iterations;
         run.limits.max_llm_calls = workflow.max_llm_calls;
@@ -45,14 +56,23 @@ impl Orchestrator {
             run.current_stage = run.stage_order[0].clone();
         }
 
-        let now = Utc::now();
+        let now = self.clock.now();
         let session = Orchestration {
             run_id: run_id.clone(),
             workflow,
             stage_visits: std::collections::HashMap::new(),
             created_at: now,
             last_activity_at: now,
+            stage_entered_at: now,
             last_routing_decision: None,
+            route_counts: Vec::new(),
+            output_retries: 0,
+            issued_dispatches: std::collections::VecDeque::new(),
+            reported_dispatches: std::collections::VecDeque::new(),
+            current_dispatch: None,
+            dispatched_version: None,
+            heartbeat: None,
+            cost_confirmation: None,
         };
 
         let state = self.build_session_state(&session, run);
@@ -75,7 +95,7 @@ impl Orchestrator {
     /// Returns the run IDs of removed sessions so the Kernel can also clean
     /// up the corresponding entries from `runs`.
     pub fn cleanup_stale_sessions(&mut self, max_age_seconds: i64) -> Vec<RunId> {
-        let cutoff = Utc::now() - chrono::TimeDelta::seconds(max_age_seconds);
+        let cutoff = self.clock.now() - chrono::TimeDelta::seconds(max_age_seconds);
         let mut to_remove = Vec::new();
 
         for (run_id, session) in &self.sessions {
@@ -97,11 +117,15 @@ impl Orchestrator {
 
         RunSnapshot {
             run_id: session.run_id.clone(),
+            revision: run.revision,
             current_stage: run.current_stage.clone(),
             stage_order: run.stage_order.clone(),
             run: run_value,
             terminated: run.is_terminated(),
             terminal_reason: run.terminal_reason(),
+            route_counts: session.route_counts.clone(),
+            extensions: Default::default(),
+            heartbeat: None,
         }
     }
 }
@@ -0,0 +1,117 @@
+//! Kernel performance counters.
+//!
+//! The actor is the kernel's only lock: a command holds `&mut Kernel` for
+//! exactly as long as its handler runs, and everything behind it waits in
+//! the command queue. `PerfRecorder` keeps the last `PERF_SAMPLE_WINDOW`
+//! handler durations per command name, plus maintenance calls
+//! (`cleanup_stale_*`), so operators can see where the time goes.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use super::resources::percentile;
+
+/// Durations kept per command for percentile calculation.
+pub const PERF_SAMPLE_WINDOW: usize = 256;
+
+/// Latency summary for one command or maintenance call.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandPerf {
+    pub command: String,
+    /// Calls since the kernel started.
+    pub count: u64,
+    /// Percentiles over the last `PERF_SAMPLE_WINDOW` calls.
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+/// Snapshot returned by `KernelHandle::get_kernel_perf`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KernelPerf {
+    /// Commands waiting for the actor when the snapshot was taken.
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    /// Total time commands have held the kernel.
+    pub busy_us: u64,
+    /// Sorted by command name.
+    pub commands: Vec<CommandPerf>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    count: u64,
+    recent: VecDeque<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct PerfRecorder {
+    samples: HashMap<&'static str, Samples>,
+    busy_us: u64,
+}
+
+impl PerfRecorder {
+    pub fn record(&mut self, command: &'static str, elapsed: Duration) {
+        let us = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.busy_us = self.busy_us.saturating_add(us);
+        let entry = self.samples.entry(command).or_default();
+        entry.count += 1;
+        if entry.recent.len() == PERF_SAMPLE_WINDOW {
+            entry.recent.pop_front();
+        }
+        entry.recent.push_back(us);
+    }
+
+    /// Summary without queue figures; the handle fills those in.
+    pub fn report(&self) -> KernelPerf {
+        let mut commands: Vec<CommandPerf> = self
+            .samples
+            .iter()
+            .map(|(name, s)| {
+                let values: Vec<f64> = s.recent.iter().map(|&us| us as f64).collect();
+                CommandPerf {
+                    command: (*name).to_string(),
+                    count: s.count,
+                    p50_us: percentile(values.clone(), 0.50) as u64,
+                    p95_us: percentile(values.clone(), 0.95) as u64,
+                    p99_us: percentile(values, 0.99) as u64,
+                    max_us: s.recent.iter().copied().max().unwrap_or(0),
+                }
+            })
+            .collect();
+        commands.sort_by(|a, b| a.command.cmp(&b.command));
+        KernelPerf {
+            busy_us: self.busy_us,
+            commands,
+            ..KernelPerf::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_over_bounded_window() {
+        let mut perf = PerfRecorder::default();
+        for us in 1..=100 {
+            perf.record("GetNextInstruction", Duration::from_micros(us));
+        }
+        for _ in 0..PERF_SAMPLE_WINDOW {
+            perf.record("CreateRun", Duration::from_micros(7));
+        }
+        perf.record("CreateRun", Duration::from_micros(9));
+
+        let report = perf.report();
+        let names: Vec<_> = report.commands.iter().map(|c| c.command.as_str()).collect();
+        assert_eq!(names, ["CreateRun", "GetNextInstruction"]);
+        let next = &report.commands[1];
+        assert_eq!((next.count, next.p50_us, next.p95_us, next.max_us), (100, 50, 95, 100));
+        let create = &report.commands[0];
+        assert_eq!((create.count, create.p50_us, create.max_us), (PERF_SAMPLE_WINDOW as u64 + 1, 7, 9));
+        assert_eq!(report.busy_us, 5050 + 7 * PERF_SAMPLE_WINDOW as u64 + 9);
+    }
+}
@@ -0,0 +1,351 @@
+//! Kernel-side post-processing of reported agent outputs.
+//!
+//! Each [`PostProcessor`] rewrites an output before it is validated against
+//! the stage's `output_schema` and merged into the run. Processors run in
+//! registration order, scoped to stages and/or to reports that used given
+//! tools. A processor that errors or panics is skipped for that output —
+//! the value it was handed passes on unchanged — and counted as failed.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::Kernel;
+use crate::types::{Error, Result};
+
+/// Rewrites an output in place.
+pub type TransformFn = Arc<dyn Fn(&mut serde_json::Value) -> Result<()> + Send + Sync>;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transform {
+    /// Remove ANSI escape sequences from every string.
+    StripAnsi,
+    /// Clamp every non-integer number into `[min, max]`.
+    ClampFloats { min: f64, max: f64 },
+    /// Rewrite recognised date strings as ISO 8601: dates as `YYYY-MM-DD`,
+    /// timestamps as UTC RFC 3339.
+    NormalizeDates,
+    /// Host-supplied code; registered through the API, not config.
+    #[serde(skip)]
+    Custom(TransformFn),
+}
+
+impl std::fmt::Debug for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StripAnsi => write!(f, "StripAnsi"),
+            Self::ClampFloats { min, max } => write!(f, "ClampFloats {{ min: {}, max: {} }}", min, max),
+            Self::NormalizeDates => write!(f, "NormalizeDates"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostProcessor {
+    pub name: String,
+    pub transform: Transform,
+    /// Stages whose outputs are processed. With `tools`, either match is
+    /// enough; both empty = every output.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<String>,
+    /// Process outputs of reports whose tool results include one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+}
+
+impl PostProcessor {
+    pub fn new(name: impl Into<String>, transform: Transform) -> Self {
+        Self { name: name.into(), transform, stages: Vec::new(), tools: Vec::new() }
+    }
+
+    pub fn custom(name: impl Into<String>, f: impl Fn(&mut serde_json::Value) -> Result<()> + Send + Sync + 'static) -> Self {
+        Self::new(name, Transform::Custom(Arc::new(f)))
+    }
+
+    pub fn for_stages(mut self, stages: &[&str]) -> Self {
+        self.stages = stages.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn for_tools(mut self, tools: &[&str]) -> Self {
+        self.tools = tools.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    fn applies_to(&self, stage: &str, tools: &[String]) -> bool {
+        (self.stages.is_empty() && self.tools.is_empty())
+            || self.stages.iter().any(|s| s == stage)
+            || self.tools.iter().any(|t| tools.contains(t))
+    }
+}
+
+/// Per-processor counters since it was installed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PostProcessorStats {
+    pub name: String,
+    /// Outputs the processor ran on.
+    pub applied: u64,
+    /// Runs that changed the output.
+    pub changed: u64,
+    /// Runs that errored or panicked; the output passed on unchanged.
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct PostProcessorRegistry {
+    processors: Vec<(PostProcessor, PostProcessorStats)>,
+}
+
+impl PostProcessorRegistry {
+    pub fn new(processors: Vec<PostProcessor>) -> Result<Self> {
+        let mut seen = std::collections::HashSet::new();
+        for p in &processors {
+            if p.name.is_empty() {
+                return Err(Error::validation("Post-processor name must not be empty"));
+            }
+            if !seen.insert(p.name.as_str()) {
+                return Err(Error::validation(format!("Duplicate post-processor '{}'", p.name)));
+            }
+            if let Transform::ClampFloats { min, max } = p.transform {
+                if min.is_nan() || max.is_nan() || min > max {
+                    return Err(Error::validation(format!("Post-processor '{}' has min > max", p.name)));
+                }
+            }
+        }
+        Ok(Self {
+            processors: processors.into_iter()
+                .map(|p| {
+                    let stats = PostProcessorStats { name: p.name.clone(), ..Default::default() };
+                    (p, stats)
+                })
+                .collect(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Run every processor scoped to `stage` / `tools` over `output`.
+    pub fn apply(&mut self, stage: &str, tools: &[String], mut output: serde_json::Value) -> serde_json::Value {
+        for (processor, stats) in &mut self.processors {
+            if !processor.applies_to(stage, tools) {
+                continue;
+            }
+            stats.applied += 1;
+            let mut candidate = output.clone();
+            let transform = &processor.transform;
+            let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run_transform(transform, &mut candidate)));
+            match outcome {
+                Ok(Ok(())) => {
+                    if candidate != output {
+                        stats.changed += 1;
+                        output = candidate;
+                    }
+                }
+                Ok(Err(e)) => {
+                    stats.failed += 1;
+                    tracing::warn!(processor = %processor.name, stage, error = %e, "post_processor_failed");
+                }
+                Err(_) => {
+                    stats.failed += 1;
+                    tracing::warn!(processor = %processor.name, stage, "post_processor_panicked");
+                }
+            }
+        }
+        output
+    }
+
+    pub fn stats(&self) -> Vec<PostProcessorStats> {
+        self.processors.iter().map(|(_, s)| s.clone()).collect()
+    }
+}
+
+fn run_transform(transform: &Transform, value: &mut serde_json::Value) -> Result<()> {
+    match transform {
+        Transform::StripAnsi => {
+            for_each_leaf(value, &mut |leaf| {
+                if let serde_json::Value::String(s) = leaf {
+                    if s.contains('\u{1b}') {
+                        *s = strip_ansi(s);
+                    }
+                }
+            });
+        }
+        Transform::ClampFloats { min, max } => {
+            for_each_leaf(value, &mut |leaf| {
+                let Some(f) = leaf.as_f64().filter(|_| leaf.is_f64()) else { return };
+                let clamped = f.clamp(*min, *max);
+                if clamped != f {
+                    if let Some(n) = serde_json::Number::from_f64(clamped) {
+                        *leaf = serde_json::Value::Number(n);
+                    }
+                }
+            });
+        }
+        Transform::NormalizeDates => {
+            for_each_leaf(value, &mut |leaf| {
+                if let serde_json::Value::String(s) = leaf {
+                    if let Some(normalized) = normalize_date(s) {
+                        *s = normalized;
+                    }
+                }
+            });
+        }
+        Transform::Custom(f) => f(value)?,
+    }
+    Ok(())
+}
+
+fn for_each_leaf(value: &mut serde_json::Value, f: &mut impl FnMut(&mut serde_json::Value)) {
+    match value {
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|v| for_each_leaf(v, f)),
+        serde_json::Value::Object(map) => map.values_mut().for_each(|v| for_each_leaf(v, f)),
+        leaf => f(leaf),
+    }
+}
+
+/// Drop CSI (`ESC [ … final`), OSC (`ESC ] … BEL` or `ESC ] … ESC \`) and
+/// two-byte escape sequences.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// ISO 8601 form of `s` if it parses as a date or timestamp in a format
+/// other than the canonical one.
+fn normalize_date(s: &str) -> Option<String> {
+    use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+
+    let s = s.trim();
+    if s.len() > 40 {
+        return None;
+    }
+    let timestamp = DateTime::parse_from_rfc3339(s).or_else(|_| DateTime::parse_from_rfc2822(s)).ok();
+    if let Some(ts) = timestamp {
+        let normalized = ts.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        return (normalized != s).then_some(normalized);
+    }
+    ["%Y/%m/%d", "%d.%m.%Y", "%B %d, %Y", "%b %d, %Y", "%d %B %Y", "%d %b %Y"]
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(s, fmt).ok())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+impl Kernel {
+    /// Replace the output post-processors and reset their stats. Fails
+    /// without changing anything on an empty or duplicate name or an
+    /// inverted clamp range.
+    pub fn set_post_processors(&mut self, processors: Vec<PostProcessor>) -> Result<()> {
+        self.post_processors = PostProcessorRegistry::new(processors)?;
+        Ok(())
+    }
+
+    /// Counters for each installed post-processor, in order.
+    pub fn post_processor_stats(&self) -> Vec<PostProcessorStats> {
+        self.post_processors.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+    use crate::types::RunId;
+    use serde_json::json;
+
+    #[test]
+    fn builtins_rewrite_strings_and_floats() {
+        let mut registry = PostProcessorRegistry::new(vec![
+            PostProcessor::new("ansi", Transform::StripAnsi),
+            PostProcessor::new("clamp", Transform::ClampFloats { min: 0.0, max: 1.0 }),
+            PostProcessor::new("dates", Transform::NormalizeDates),
+        ]).unwrap();
+        let output = registry.apply("any", &[], json!({
+            "log": "\u{1b}[31mred\u{1b}[0m \u{1b}]0;title\u{7}done",
+            "scores": [1.7, -0.2, 0.5, 3],
+            "due": "March 5, 2024",
+            "at": "Tue, 5 Mar 2024 10:00:00 +0100",
+            "iso": "2024-03-05",
+        }));
+        assert_eq!(output, json!({
+            "log": "red done",
+            "scores": [1.0, 0.0, 0.5, 3],
+            "due": "2024-03-05",
+            "at": "2024-03-05T09:00:00Z",
+            "iso": "2024-03-05",
+        }));
+        let changed: Vec<u64> = registry.stats().iter().map(|s| s.changed).collect();
+        assert_eq!(changed, vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn failures_are_isolated_and_scope_is_honoured() {
+        let mut registry = PostProcessorRegistry::new(vec![
+            PostProcessor::custom("broken", |_| Err(Error::internal("boom"))),
+            PostProcessor::custom("panics", |_| panic!("processor bug")),
+            PostProcessor::custom("tag", |v| {
+                v["tagged"] = json!(true);
+                Ok(())
+            }).for_stages(&["review"]).for_tools(&["search"]),
+        ]).unwrap();
+
+        let output = registry.apply("draft", &[], json!({"text": "x"}));
+        assert_eq!(output, json!({"text": "x"}));
+        let output = registry.apply("draft", &["search".to_string()], json!({"text": "x"}));
+        assert_eq!(output, json!({"text": "x", "tagged": true}));
+
+        let stats = registry.stats();
+        assert_eq!((stats[0].applied, stats[0].failed), (2, 2));
+        assert_eq!((stats[1].applied, stats[1].failed), (2, 2));
+        assert_eq!((stats[2].applied, stats[2].changed), (1, 1));
+    }
+
+    #[test]
+    fn invalid_registrations_are_rejected() {
+        assert!(PostProcessorRegistry::new(vec![PostProcessor::new("", Transform::StripAnsi)]).is_err());
+        assert!(PostProcessorRegistry::new(vec![
+            PostProcessor::new("a", Transform::StripAnsi),
+            PostProcessor::new("a", Transform::NormalizeDates),
+        ]).is_err());
+        assert!(PostProcessorRegistry::new(vec![PostProcessor::new("c", Transform::ClampFloats { min: 1.0, max: 0.0 })]).is_err());
+    }
+
+    #[test]
+    fn outputs_are_processed_before_merge() {
+        let mut kernel = Kernel::new();
+        kernel.set_post_processors(vec![PostProcessor::new("ansi", Transform::StripAnsi).for_stages(&["stage1"])]).unwrap();
+        let run_id = RunId::must("processed");
+        let _ = kernel.initialize_orchestration(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false).unwrap();
+        let _ = kernel.get_next_instruction(&run_id).unwrap();
+        kernel.process_agent_result(&run_id, "agent1", json!({"text": "\u{1b}[1mbold\u{1b}[0m"}), None, Default::default(), true, "", false, None).unwrap();
+
+        assert_eq!(kernel.runs[&run_id].outputs["agent1"]["text"], json!("bold"));
+        assert_eq!(kernel.post_processor_stats()[0].changed, 1);
+    }
+}
@@ -1,13 +1,15 @@
 //! Kernel ↔ runner contract types. Not part of the consumer-facing API.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use crate::agent::policy::ContextOverflow;
 use crate::run::{FlowInterrupt, TerminalReason};
 use crate::types::{RunId, StageName};
 use crate::workflow::RetryPolicy;
 
-use super::routing::RoutingDecision;
+use super::routing::{RouteCount, RoutingDecision};
+use super::types::RunExtension;
 
 /// Per-dispatch context layered on after the orchestrator runs. Populated by
 /// `kernel::dispatch::get_next_instruction`.
@@ -33,6 +35,17 @@ pub struct AgentDispatchContext {
     pub timeout_seconds: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub retry_policy: Option<RetryPolicy>,
+    /// Agent version resolved from the declared `AgentBindings` (see
+    /// `kernel::agents`); execute this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
+    /// Idempotency token for this `RunAgent`; echo it back on
+    /// `process_agent_result` so duplicate reports are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispatch_id: Option<String>,
+    /// Expected usage and cost of this stage, from past executions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_estimate: Option<super::CostEstimate>,
     /// Routing decision that selected this stage; emitted as an audit event.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_routing_decision: Option<RoutingDecision>,
@@ -60,6 +73,29 @@ pub enum Instruction {
     WaitInterrupt {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         interrupt: Option<FlowInterrupt>,
+        /// Suggested delay before asking again, from kernel load and how
+        /// long interrupts have recently taken to resolve.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        poll_after_ms: Option<u64>,
+    },
+    /// Outside every `Workflow.execution_windows` entry; ask again at `until`.
+    WaitWindow {
+        until: chrono::DateTime<chrono::Utc>,
+    },
+    /// The stage's concurrency group is full; the run is queued for a permit.
+    WaitConcurrency {
+        group: String,
+        /// 1-based place in the group's FIFO queue.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<usize>,
+        poll_after_ms: u64,
+    },
+    /// No declared version of the stage's agent matches its pinned
+    /// `agent_version`; ask again once the declarations change.
+    WaitAgent {
+        agent: String,
+        requirement: String,
+        poll_after_ms: u64,
     },
 }
 
@@ -90,6 +126,7 @@ impl Instruction {
     pub fn wait_interrupt(interrupt: FlowInterrupt) -> Self {
         Self::WaitInterrupt {
             interrupt: Some(interrupt),
+            poll_after_ms: None,
         }
     }
 }
@@ -100,6 +137,9 @@ impl Instruction {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunSnapshot {
     pub run_id: RunId,
+    /// `Run.revision` when the snapshot was taken.
+    #[serde(default)]
+    pub revision: u64,
     /// Name of the stage currently executing or about to execute.
     pub current_stage: StageName,
     /// Ordered list of all stage names in the workflow.
@@ -109,4 +149,13 @@ pub struct RunSnapshot {
     pub terminated: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub terminal_reason: Option<TerminalReason>,
+    /// Routes taken so far with match counts (see [`RouteCount`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route_counts: Vec<RouteCount>,
+    /// Embedder extensions from the run's `RunRecord`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extensions: HashMap<String, RunExtension>,
+    /// Latest heartbeat for the outstanding `RunAgent`, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<super::AgentHeartbeat>,
 }
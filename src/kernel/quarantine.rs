@@ -0,0 +1,233 @@
+//! Agent quarantine.
+//!
+//! A quarantined agent is never dispatched. A stage bound to one is routed
+//! to its `error_next`; without one, the run pauses on an agent-review
+//! interrupt that is raised again on every resume until the agent is
+//! released. Agents are quarantined by hand or, with a
+//! `QuarantinePolicy.max_failure_rate`, automatically once their recent
+//! failure rate crosses it.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use super::protocol::Instruction;
+use super::Kernel;
+use crate::run::FlowInterrupt;
+use crate::types::{Error, Result, RunId};
+
+/// Reported outcomes kept per agent for the failure rate.
+pub const AGENT_OUTCOME_WINDOW: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinePolicy {
+    /// Quarantine an agent whose failure rate over its last
+    /// `AGENT_OUTCOME_WINDOW` reports exceeds this (0.0–1.0).
+    #[serde(default)]
+    pub max_failure_rate: Option<f64>,
+    /// Reports needed before the rate is judged.
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+}
+
+fn default_min_samples() -> usize {
+    10
+}
+
+impl Default for QuarantinePolicy {
+    fn default() -> Self {
+        Self { max_failure_rate: None, min_samples: default_min_samples() }
+    }
+}
+
+/// One quarantined agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentQuarantine {
+    pub agent: String,
+    pub reason: String,
+    pub since: DateTime<Utc>,
+    /// Set by the failure-rate policy rather than an operator.
+    pub automatic: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct QuarantineList {
+    pub(crate) policy: QuarantinePolicy,
+    entries: HashMap<String, AgentQuarantine>,
+    outcomes: HashMap<String, VecDeque<bool>>,
+}
+
+impl QuarantineList {
+    pub fn get(&self, agent: &str) -> Option<&AgentQuarantine> {
+        self.entries.get(agent)
+    }
+
+    /// Quarantined agents, by name.
+    pub fn list(&self) -> Vec<AgentQuarantine> {
+        let mut entries: Vec<_> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| a.agent.cmp(&b.agent));
+        entries
+    }
+
+    pub fn insert(&mut self, entry: AgentQuarantine) {
+        self.entries.insert(entry.agent.clone(), entry);
+    }
+
+    /// Release `agent` and forget its outcomes. Returns whether it was
+    /// quarantined.
+    pub fn release(&mut self, agent: &str) -> bool {
+        self.outcomes.remove(agent);
+        self.entries.remove(agent).is_some()
+    }
+
+    /// Record one reported outcome. Returns the failure rate when it has
+    /// just crossed the policy threshold.
+    pub fn record(&mut self, agent: &str, success: bool) -> Option<f64> {
+        let limit = self.policy.max_failure_rate?;
+        if self.entries.contains_key(agent) {
+            return None;
+        }
+        let outcomes = self.outcomes.entry(agent.to_string()).or_default();
+        if outcomes.len() == AGENT_OUTCOME_WINDOW {
+            outcomes.pop_front();
+        }
+        outcomes.push_back(success);
+        if outcomes.len() < self.policy.min_samples.max(1) {
+            return None;
+        }
+        let rate = outcomes.iter().filter(|ok| !**ok).count() as f64 / outcomes.len() as f64;
+        (rate > limit).then_some(rate)
+    }
+}
+
+impl Kernel {
+    /// Quarantine `agent` with `reason`, or release it with `None`.
+    pub fn set_agent_quarantine(&mut self, agent: &str, reason: Option<String>) -> Result<()> {
+        if agent.is_empty() {
+            return Err(Error::validation("Agent name must not be empty"));
+        }
+        match reason {
+            Some(reason) => {
+                tracing::warn!(agent, reason = %reason, "agent_quarantined");
+                self.quarantine.insert(AgentQuarantine {
+                    agent: agent.to_string(),
+                    reason,
+                    since: self.clock.now(),
+                    automatic: false,
+                });
+            }
+            None => {
+                if self.quarantine.release(agent) {
+                    tracing::info!(agent, "agent_released");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Install the automatic quarantine policy.
+    pub fn set_quarantine_policy(&mut self, policy: QuarantinePolicy) {
+        self.quarantine.policy = policy;
+    }
+
+    /// Feed one reported outcome into the failure-rate policy.
+    pub(crate) fn record_agent_outcome(&mut self, agent: &str, success: bool) {
+        if let Some(rate) = self.quarantine.record(agent, success) {
+            tracing::warn!(agent, failure_rate = rate, "agent_quarantined");
+            self.quarantine.insert(AgentQuarantine {
+                agent: agent.to_string(),
+                reason: format!("failure rate {:.0}% over recent executions", rate * 100.0),
+                since: self.clock.now(),
+                automatic: true,
+            });
+        }
+    }
+
+    /// Instruction to send instead of dispatching quarantined `agent`, or
+    /// `None` if it isn't quarantined.
+    pub(crate) fn quarantine_gate(&mut self, run_id: &RunId, agent: &str) -> Result<Option<Instruction>> {
+        let Some(entry) = self.quarantine.get(agent).cloned() else {
+            return Ok(None);
+        };
+        let run = self.runs.edit(run_id)
+            .ok_or_else(|| Error::not_found(format!("Run not found: {}", run_id)))?;
+        let stage = run.current_stage.clone();
+        let error_next = self.orchestrator.get_stage_config(run_id, stage.as_str())
+            .and_then(|sc| sc.error_next.clone());
+        if let Some(target) = error_next {
+            tracing::info!(run_id = %run_id, agent, stage = %stage, "quarantined_agent_skipped");
+            self.orchestrator.apply_routing_result(run_id, stage.as_str(), Some(target), run)?;
+            return self.get_next_instruction(run_id).map(Some);
+        }
+
+        let data = HashMap::from([
+            ("agent_review".to_string(), serde_json::json!(true)),
+            ("agent".to_string(), serde_json::json!(agent)),
+            ("reason".to_string(), serde_json::json!(entry.reason)),
+        ]);
+        let interrupt = FlowInterrupt::new_in(self.id_format)
+            .with_message(format!("Agent {} is quarantined: {}", agent, entry.reason))
+            .with_data(data);
+        self.set_run_interrupt(run_id, interrupt.clone())?;
+        Ok(Some(Instruction::wait_interrupt(interrupt)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+
+    #[test]
+    fn failure_rate_quarantines_after_min_samples() {
+        let mut list = QuarantineList {
+            policy: QuarantinePolicy { max_failure_rate: Some(0.5), min_samples: 4 },
+            ..Default::default()
+        };
+        assert_eq!(list.record("flaky", false), None);
+        assert_eq!(list.record("flaky", false), None);
+        assert_eq!(list.record("flaky", true), None);
+        assert_eq!(list.record("flaky", false), Some(0.75));
+        assert_eq!(list.record("steady", true), None);
+    }
+
+    #[test]
+    fn quarantined_agent_pauses_run_until_released() {
+        let mut kernel = Kernel::new();
+        kernel.set_agent_quarantine("agent1", Some("bad outputs".into())).unwrap();
+        let run_id = RunId::must("quarantine");
+        let _ = kernel.initialize_orchestration(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false).unwrap();
+
+        let interrupt = match kernel.get_next_instruction(&run_id).unwrap() {
+            Instruction::WaitInterrupt { interrupt: Some(i), .. } => i,
+            other => panic!("expected WaitInterrupt, got {:?}", other),
+        };
+        assert_eq!(interrupt.data.as_ref().unwrap()["agent"], "agent1");
+        assert_eq!(kernel.quarantine.list().len(), 1);
+
+        kernel.set_agent_quarantine("agent1", None).unwrap();
+        let response = crate::run::InterruptResponse {
+            text: None,
+            approved: Some(true),
+            decision: None,
+            data: None,
+            responder: None,
+            received_at: Utc::now(),
+        };
+        kernel.resolve_run_interrupt(&run_id, interrupt.id.as_str(), response).unwrap();
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::RunAgent { agent, .. } if agent == "agent1"));
+    }
+
+    #[test]
+    fn quarantined_agent_routes_to_error_next() {
+        let mut kernel = Kernel::new();
+        kernel.set_agent_quarantine("agent1", Some("manual".into())).unwrap();
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].error_next = Some("stage2".into());
+        let run_id = RunId::must("quarantine-error-next");
+        let _ = kernel.initialize_orchestration(run_id.clone(), workflow, test_helpers::create_test_run(), false).unwrap();
+
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::RunAgent { agent, .. } if agent == "agent2"));
+        assert_eq!(kernel.runs[&run_id].current_stage.as_str(), "stage2");
+    }
+}
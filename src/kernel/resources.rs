@@ -3,9 +3,12 @@
 //! Tracks resource usage across processes and enforces quotas.
 
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use super::types::ResourceUsage;
+use super::types::{QuotaRecommendation, ResourceQuota, ResourceUsage};
+
+/// Terminated-run usage samples kept per workflow for quota recommendations.
+pub const USAGE_SAMPLE_WINDOW: usize = 200;
 
 /// Per-user resource tracker. Owned by Kernel; mutated via `&mut self` in the
 /// single-actor loop. Per-run quota lives on `RunRecord.quota` and is checked
@@ -14,12 +17,20 @@ use super::types::ResourceUsage;
 pub struct ResourceTracker {
     /// Per-user usage aggregation (optional, for multi-tenant quotas)
     user_usage: HashMap<String, ResourceUsage>,
+    /// Usage of the most recent terminated runs per workflow name.
+    #[serde(default)]
+    workflow_samples: HashMap<String, VecDeque<ResourceUsage>>,
+    /// Usage of the most recent agent executions per `workflow/stage`.
+    #[serde(default)]
+    stage_samples: HashMap<String, VecDeque<ResourceUsage>>,
 }
 
 impl ResourceTracker {
     pub fn new() -> Self {
         Self {
             user_usage: HashMap::new(),
+            workflow_samples: HashMap::new(),
+            stage_samples: HashMap::new(),
         }
     }
 
@@ -73,6 +84,72 @@ impl ResourceTracker {
         before - self.user_usage.len()
     }
 
+    /// Record a terminated run's final usage against its workflow, keeping
+    /// the last `USAGE_SAMPLE_WINDOW` samples.
+    pub fn record_workflow_usage(&mut self, workflow_name: &str, usage: ResourceUsage) {
+        let samples = self.workflow_samples.entry(workflow_name.to_string()).or_default();
+        if samples.len() == USAGE_SAMPLE_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(usage);
+    }
+
+    /// Record one agent execution of `stage`, keeping the last
+    /// `USAGE_SAMPLE_WINDOW` samples.
+    pub fn record_stage_usage(&mut self, workflow_name: &str, stage: &str, usage: ResourceUsage) {
+        let samples = self.stage_samples.entry(format!("{}/{}", workflow_name, stage)).or_default();
+        if samples.len() == USAGE_SAMPLE_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(usage);
+    }
+
+    /// Median tokens and LLM calls of `stage`'s sampled executions, with the
+    /// sample count. `None` when the stage has no samples.
+    pub fn stage_median(&self, workflow_name: &str, stage: &str) -> Option<(ResourceUsage, usize)> {
+        let samples = self.stage_samples
+            .get(&format!("{}/{}", workflow_name, stage))
+            .filter(|s| !s.is_empty())?;
+        let median = |f: fn(&ResourceUsage) -> f64| percentile(samples.iter().map(f).collect(), 0.5);
+        let usage = ResourceUsage {
+            tokens_in: median(|u| u.tokens_in as f64).round() as i64,
+            tokens_out: median(|u| u.tokens_out as f64).round() as i64,
+            llm_calls: median(|u| u.llm_calls as f64).round() as i32,
+            ..ResourceUsage::default()
+        };
+        Some((usage, samples.len()))
+    }
+
+    /// Recommend a quota for `workflow_name`: the p95 of each sampled
+    /// dimension scaled by `1 + headroom`, on top of `base` for dimensions
+    /// that aren't sampled. `None` when the workflow has no samples.
+    pub fn recommend_quota(
+        &self,
+        workflow_name: &str,
+        headroom: f64,
+        base: &ResourceQuota,
+    ) -> Option<QuotaRecommendation> {
+        let samples = self.workflow_samples.get(workflow_name).filter(|s| !s.is_empty())?;
+        let limit = |values: Vec<f64>| -> i32 {
+            let p95 = percentile(values, 0.95);
+            (p95 * (1.0 + headroom.max(0.0))).ceil().min(i32::MAX as f64).max(1.0) as i32
+        };
+        let column = |f: fn(&ResourceUsage) -> f64| samples.iter().map(f).collect::<Vec<_>>();
+
+        let max_input_tokens = limit(column(|u| u.tokens_in as f64));
+        let quota = ResourceQuota {
+            max_input_tokens,
+            max_output_tokens: limit(column(|u| u.tokens_out as f64)),
+            max_context_tokens: base.max_context_tokens.max(max_input_tokens),
+            max_llm_calls: limit(column(|u| u.llm_calls as f64)),
+            max_tool_calls: limit(column(|u| u.tool_calls as f64)),
+            max_agent_hops: limit(column(|u| u.agent_hops as f64)),
+            max_iterations: limit(column(|u| u.iterations as f64)),
+            timeout_seconds: limit(column(|u| u.elapsed_seconds)),
+        };
+        Some(QuotaRecommendation { quota, samples: samples.len() })
+    }
+
     /// Get total usage across all users.
     pub fn total_usage(&self) -> ResourceUsage {
         let mut total = ResourceUsage::default();
@@ -88,6 +165,16 @@ impl ResourceTracker {
     }
 }
 
+/// Nearest-rank percentile of `values` (`q` in `0.0..=1.0`).
+pub(crate) fn percentile(mut values: Vec<f64>, q: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let rank = (q * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,5 +244,38 @@ mod tests {
         let total = tracker.total_usage();
         assert_eq!(total.llm_calls, 0);
     }
-}
 
+    #[test]
+    fn test_recommend_quota_uses_p95_with_headroom() {
+        let mut tracker = ResourceTracker::new();
+        assert!(tracker.recommend_quota("wf", 0.2, &ResourceQuota::default()).is_none());
+
+        for calls in 1..=20 {
+            tracker.record_workflow_usage("wf", ResourceUsage {
+                llm_calls: calls,
+                tokens_in: 1000,
+                elapsed_seconds: 9.5,
+                ..Default::default()
+            });
+        }
+        let rec = tracker.recommend_quota("wf", 0.2, &ResourceQuota::default()).unwrap();
+        assert_eq!(rec.samples, 20);
+        // p95 of 1..=20 is 19; 19 * 1.2 = 22.8 -> 23.
+        assert_eq!(rec.quota.max_llm_calls, 23);
+        assert_eq!(rec.quota.max_input_tokens, 1200);
+        assert_eq!(rec.quota.timeout_seconds, 12);
+        // Unused dimensions floor at 1 rather than 0.
+        assert_eq!(rec.quota.max_tool_calls, 1);
+        assert!(rec.quota.validate().is_ok());
+    }
+
+    #[test]
+    fn test_workflow_samples_are_bounded() {
+        let mut tracker = ResourceTracker::new();
+        for _ in 0..USAGE_SAMPLE_WINDOW + 5 {
+            tracker.record_workflow_usage("wf", ResourceUsage::default());
+        }
+        let rec = tracker.recommend_quota("wf", 0.0, &ResourceQuota::default()).unwrap();
+        assert_eq!(rec.samples, USAGE_SAMPLE_WINDOW);
+    }
+}
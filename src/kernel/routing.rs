@@ -18,6 +18,19 @@ pub struct RoutingContext<'a> {
     pub metadata: &'a HashMap<String, serde_json::Value>,
     pub interrupt_response: Option<&'a serde_json::Value>,
     pub state: &'a HashMap<String, serde_json::Value>,
+    /// Completed agent executions, including the one being routed.
+    pub iteration: i32,
+    /// Wall-clock time since the run was received.
+    pub run_elapsed: std::time::Duration,
+    /// Wall-clock time since the workflow entered `current_stage`.
+    pub stage_elapsed: std::time::Duration,
+}
+
+impl RoutingContext<'_> {
+    /// Highest severity in `metadata["loop_feedback"]`, if any.
+    pub fn max_feedback_severity(&self) -> Option<crate::run::FeedbackSeverity> {
+        crate::run::loop_feedback_from(self.metadata).iter().map(|f| f.severity).max()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -84,7 +97,7 @@ pub struct RoutingDecision {
     pub reason: RoutingReason,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[non_exhaustive]
 pub enum RoutingReason {
@@ -95,6 +108,36 @@ pub enum RoutingReason {
     NoMatch,
 }
 
+/// How many times a given `(from_stage, target, reason)` route was taken in
+/// a session. Routes that never appear are dead wiring; a route with a high
+/// count on a loop-back is a hot loop.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteCount {
+    pub from_stage: StageName,
+    /// `None` means the route terminated the workflow.
+    pub target: Option<StageName>,
+    pub reason: RoutingReason,
+    pub count: u32,
+}
+
+/// Bump the counter matching `decision`, appending a new entry on first use.
+pub(crate) fn record_route(counts: &mut Vec<RouteCount>, decision: &RoutingDecision) {
+    if let Some(entry) = counts.iter_mut().find(|c| {
+        c.from_stage == decision.from_stage
+            && c.target == decision.target
+            && c.reason == decision.reason
+    }) {
+        entry.count += 1;
+        return;
+    }
+    counts.push(RouteCount {
+        from_stage: decision.from_stage.clone(),
+        target: decision.target.clone(),
+        reason: decision.reason.clone(),
+        count: 1,
+    });
+}
+
 pub fn evaluate_routing_with_reason(
     stage: &crate::workflow::Stage,
     registry: &RoutingRegistry,
@@ -189,9 +232,28 @@ mod tests {
             metadata,
             interrupt_response: None,
             state,
+            iteration: 1,
+            run_elapsed: std::time::Duration::ZERO,
+            stage_elapsed: std::time::Duration::ZERO,
         }
     }
 
+    #[test]
+    fn test_max_feedback_severity() {
+        let (outputs, mut metadata) = empty_ctx();
+        let state = HashMap::new();
+        assert_eq!(make_ctx(&outputs, &metadata, &state).max_feedback_severity(), None);
+
+        metadata.insert("loop_feedback".to_string(), serde_json::json!([
+            {"severity": "info", "code": "a", "message": "", "stage": "s1", "iteration": 1},
+            {"severity": "warning", "code": "b", "message": "", "stage": "s1", "iteration": 2},
+        ]));
+        assert_eq!(
+            make_ctx(&outputs, &metadata, &state).max_feedback_severity(),
+            Some(crate::run::FeedbackSeverity::Warning)
+        );
+    }
+
     #[test]
     fn test_routing_fn_called() {
         let reg = test_registry();
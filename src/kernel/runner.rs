@@ -10,6 +10,7 @@ use crate::agent::metrics::AgentExecutionMetrics;
 use crate::agent::{Agent, AgentContext, AgentOutput, AgentRegistry, DeterministicAgent};
 use crate::run::Run;
 use crate::kernel::handle::KernelHandle;
+use crate::kernel::interrupts::DEFAULT_POLL_MS;
 use crate::kernel::protocol::{AgentDispatchContext, Instruction};
 use crate::types::{RunId, Result};
 use crate::workflow::Workflow;
@@ -204,11 +205,28 @@ pub async fn run_loop(
                         output.success,
                         &output.error_message,
                         false,
+                        context.dispatch_id.as_deref(),
                     )
                     .await?;
             }
 
-            Instruction::WaitInterrupt { ref interrupt } => {
+            Instruction::WaitWindow { until } => {
+                let wait = (until - handle.now()).to_std().unwrap_or_default();
+                tracing::info!(until = %until, "waiting_for_execution_window");
+                tokio::time::sleep(wait).await;
+            }
+
+            Instruction::WaitConcurrency { ref group, position, poll_after_ms } => {
+                tracing::info!(group = %group, position = ?position, "waiting_for_concurrency_group");
+                tokio::time::sleep(tokio::time::Duration::from_millis(poll_after_ms)).await;
+            }
+
+            Instruction::WaitAgent { ref agent, ref requirement, poll_after_ms } => {
+                tracing::info!(agent = %agent, requirement = %requirement, "waiting_for_agent_version");
+                tokio::time::sleep(tokio::time::Duration::from_millis(poll_after_ms)).await;
+            }
+
+            Instruction::WaitInterrupt { ref interrupt, poll_after_ms } => {
                 let interrupt_id = interrupt.as_ref().map(|i| i.id.as_str().to_string()).unwrap_or_default();
 
                 if let Some(ref tx) = event_tx {
@@ -222,10 +240,14 @@ pub async fn run_loop(
                         pipeline: workflow_name.clone(),
                     }).await;
                     // Poll: kernel returns WaitInterrupt until resolved, then RunAgent/Terminate
+                    let mut delay = poll_after_ms.unwrap_or(DEFAULT_POLL_MS);
                     loop {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                        if !matches!(handle.get_next_instruction(run_id).await?, Instruction::WaitInterrupt { .. }) {
-                            break; // resolved — outer loop re-fetches
+                        tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
+                        match handle.get_next_instruction(run_id).await? {
+                            Instruction::WaitInterrupt { poll_after_ms, .. } => {
+                                delay = poll_after_ms.unwrap_or(delay);
+                            }
+                            _ => break, // resolved — outer loop re-fetches
                         }
                     }
                 } else {
@@ -269,6 +291,10 @@ fn build_agent_context(
             .and_then(|c| c.get("metadata"))
             .and_then(|v| serde_json::from_value(v.clone()).ok())
             .unwrap_or_default(),
+        inputs: dispatch_payload
+            .and_then(|c| c.get("inputs"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default(),
         event_tx,
         stage_name,
         workflow_name,
@@ -0,0 +1,288 @@
+//! Cross-session stage concurrency groups.
+//!
+//! A stage naming a `concurrency_group` may only be dispatched while its run
+//! holds a permit on that group, so at most `capacity` such stages run at
+//! once across every session (e.g. three `deploy` stages kernel-wide).
+//! Groups are defined with `define_stage_group`.
+//!
+//! `get_next_instruction` takes the permit just before dispatch. When the
+//! group is full the run is queued FIFO and gets `WaitConcurrency` until a
+//! permit is handed to it. The permit is returned when the stage's result
+//! is processed, or when the run terminates.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::interrupts::DEFAULT_POLL_MS;
+use super::protocol::Instruction;
+use super::Kernel;
+use crate::types::{Error, Result, RunId};
+
+/// Capacity and contention metrics for one concurrency group.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StageGroupStats {
+    pub capacity: usize,
+    pub in_use: usize,
+    pub waiting: usize,
+    /// Permits granted, immediately or after waiting.
+    pub acquired_total: u64,
+    /// Acquires that had to queue.
+    pub contended_total: u64,
+}
+
+#[derive(Debug)]
+struct Group {
+    capacity: usize,
+    holders: HashSet<RunId>,
+    queue: VecDeque<RunId>,
+    acquired_total: u64,
+    contended_total: u64,
+}
+
+/// Defined groups, and which runs hold or await a permit for their
+/// current stage.
+#[derive(Debug, Default)]
+pub struct StageGroups {
+    groups: HashMap<String, Group>,
+    held: HashMap<RunId, String>,
+    waiting: HashMap<RunId, String>,
+}
+
+impl StageGroups {
+    /// Define a group, or change its capacity. Raising the capacity
+    /// immediately serves queued runs; lowering it never revokes permits
+    /// already held.
+    pub fn define(&mut self, name: &str, capacity: usize) -> Result<()> {
+        if capacity == 0 {
+            return Err(Error::validation(format!(
+                "concurrency group '{}' capacity must be positive",
+                name
+            )));
+        }
+        let group = self.groups.entry(name.to_string()).or_insert_with(|| Group {
+            capacity,
+            holders: HashSet::new(),
+            queue: VecDeque::new(),
+            acquired_total: 0,
+            contended_total: 0,
+        });
+        group.capacity = capacity;
+        self.pump(name);
+        Ok(())
+    }
+
+    pub fn stats(&self, name: &str) -> Option<StageGroupStats> {
+        self.groups.get(name).map(|group| StageGroupStats {
+            capacity: group.capacity,
+            in_use: group.holders.len(),
+            waiting: group.queue.len(),
+            acquired_total: group.acquired_total,
+            contended_total: group.contended_total,
+        })
+    }
+
+    /// The group `run_id` holds a permit on, if any.
+    pub fn held(&self, run_id: &RunId) -> Option<&str> {
+        self.held.get(run_id).map(String::as_str)
+    }
+
+    /// 1-based place of `run_id` in `name`'s queue, or `None` if it isn't
+    /// queued there.
+    pub fn position(&self, name: &str, run_id: &RunId) -> Option<usize> {
+        self.groups.get(name)?.queue.iter().position(|id| id == run_id).map(|p| p + 1)
+    }
+
+    /// Take a permit on `name` for `run_id`, or queue it. Returns whether
+    /// the permit was granted.
+    pub(crate) fn acquire(&mut self, name: &str, run_id: &RunId) -> Result<bool> {
+        let group = self.groups.get_mut(name)
+            .ok_or_else(|| Error::not_found(format!("Concurrency group '{}' not defined", name)))?;
+        if group.holders.len() < group.capacity {
+            group.holders.insert(run_id.clone());
+            group.acquired_total += 1;
+            self.held.insert(run_id.clone(), name.to_string());
+            return Ok(true);
+        }
+        group.contended_total += 1;
+        group.queue.push_back(run_id.clone());
+        self.waiting.insert(run_id.clone(), name.to_string());
+        Ok(false)
+    }
+
+    /// Return `run_id`'s permit, if it holds one, and hand it on.
+    fn release(&mut self, run_id: &RunId) -> Option<String> {
+        let name = self.held.remove(run_id)?;
+        if let Some(group) = self.groups.get_mut(&name) {
+            group.holders.remove(run_id);
+        }
+        self.pump(&name);
+        Some(name)
+    }
+
+    /// Hand free permits on `name` to queued runs in FIFO order.
+    fn pump(&mut self, name: &str) {
+        let Some(group) = self.groups.get_mut(name) else { return };
+        while group.holders.len() < group.capacity {
+            let Some(run_id) = group.queue.pop_front() else { break };
+            group.holders.insert(run_id.clone());
+            group.acquired_total += 1;
+            self.waiting.remove(&run_id);
+            self.held.insert(run_id, name.to_string());
+        }
+    }
+
+    /// Drop `run_id`'s permit and queued wait. Returns whether it held a
+    /// permit.
+    pub(crate) fn forget(&mut self, run_id: &RunId) -> bool {
+        if let Some(name) = self.waiting.remove(run_id) {
+            if let Some(group) = self.groups.get_mut(&name) {
+                group.queue.retain(|id| id != run_id);
+            }
+        }
+        self.release(run_id).is_some()
+    }
+}
+
+impl Kernel {
+    /// Define a stage concurrency group, or resize an existing one.
+    pub fn define_stage_group(&mut self, name: &str, capacity: usize) -> Result<()> {
+        self.stage_groups.define(name, capacity)
+    }
+
+    /// Capacity and contention metrics for a concurrency group.
+    pub fn get_stage_group_stats(&self, name: &str) -> Result<StageGroupStats> {
+        self.stage_groups
+            .stats(name)
+            .ok_or_else(|| Error::not_found(format!("Concurrency group '{}' not defined", name)))
+    }
+
+    /// Hold `run_id`'s dispatch until it has a permit on its current
+    /// stage's concurrency group. `None` means it may dispatch.
+    pub(crate) fn stage_group_gate(&mut self, run_id: &RunId) -> Result<Option<Instruction>> {
+        let Some(stage) = self.runs.get(run_id).map(|r| r.current_stage.clone()) else {
+            return Ok(None);
+        };
+        let Some(group) = self.orchestrator.get_stage_config(run_id, stage.as_str())
+            .and_then(|sc| sc.concurrency_group.clone()) else {
+            return Ok(None);
+        };
+        if self.stage_groups.held(run_id) == Some(group.as_str()) {
+            return Ok(None);
+        }
+        if self.stage_groups.waiting.get(run_id) == Some(&group) {
+            return Ok(Some(self.wait_for_group(run_id, group)));
+        }
+        // A permit or wait left from an earlier stage in another group goes first.
+        self.stage_groups.forget(run_id);
+
+        if self.stage_groups.acquire(&group, run_id)? {
+            return Ok(None);
+        }
+        tracing::info!(run_id = %run_id, group = %group, stage = %stage, "stage_group_queued");
+        Ok(Some(self.wait_for_group(run_id, group)))
+    }
+
+    fn wait_for_group(&self, run_id: &RunId, group: String) -> Instruction {
+        let position = self.stage_groups.position(&group, run_id);
+        Instruction::WaitConcurrency { group, position, poll_after_ms: DEFAULT_POLL_MS }
+    }
+
+    /// Return the group permit `run_id` holds for its dispatched stage.
+    pub(crate) fn release_stage_group(&mut self, run_id: &RunId) {
+        self.stage_groups.release(run_id);
+    }
+
+    /// Reject workflows naming a concurrency group that is not defined.
+    pub(crate) fn check_stage_groups(&self, workflow: &crate::workflow::Workflow) -> Result<()> {
+        for stage in &workflow.stages {
+            if let Some(group) = &stage.concurrency_group {
+                if self.stage_groups.stats(group).is_none() {
+                    return Err(Error::validation(format!(
+                        "Stage '{}' names concurrency group '{}', which is not defined",
+                        stage.name, group
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+
+    fn start(kernel: &mut Kernel, name: &str) -> RunId {
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[0].concurrency_group = Some("deploy".into());
+        let run_id = RunId::must(name);
+        let _ = kernel.initialize_run(run_id.clone(), workflow, test_helpers::create_test_run(), false, None).unwrap();
+        run_id
+    }
+
+    fn report(kernel: &mut Kernel, run_id: &RunId, agent: &str) {
+        kernel.process_agent_result(run_id, agent, serde_json::json!({}), None, Default::default(), true, "", false, None).unwrap();
+    }
+
+    #[test]
+    fn full_group_queues_runs_in_order() {
+        let mut kernel = Kernel::new();
+        kernel.define_stage_group("deploy", 1).unwrap();
+        let first = start(&mut kernel, "deploy-1");
+        let second = start(&mut kernel, "deploy-2");
+        let third = start(&mut kernel, "deploy-3");
+
+        assert!(matches!(kernel.get_next_instruction(&first).unwrap(), Instruction::RunAgent { .. }));
+        // Re-polling while holding the permit doesn't take another.
+        assert!(matches!(kernel.get_next_instruction(&first).unwrap(), Instruction::RunAgent { .. }));
+        match kernel.get_next_instruction(&second).unwrap() {
+            Instruction::WaitConcurrency { group, position, .. } => {
+                assert_eq!(group, "deploy");
+                assert_eq!(position, Some(1));
+            }
+            other => panic!("expected WaitConcurrency, got {:?}", other),
+        }
+        assert!(matches!(
+            kernel.get_next_instruction(&third).unwrap(),
+            Instruction::WaitConcurrency { position: Some(2), .. }
+        ));
+
+        report(&mut kernel, &first, "agent1");
+        assert!(matches!(kernel.get_next_instruction(&first).unwrap(), Instruction::RunAgent { .. }));
+        assert!(matches!(kernel.get_next_instruction(&third).unwrap(), Instruction::WaitConcurrency { position: Some(1), .. }));
+        assert!(matches!(kernel.get_next_instruction(&second).unwrap(), Instruction::RunAgent { .. }));
+
+        let stats = kernel.get_stage_group_stats("deploy").unwrap();
+        assert_eq!((stats.in_use, stats.waiting, stats.contended_total), (1, 1, 2));
+
+        kernel.terminate_run(&second).unwrap();
+        assert!(matches!(kernel.get_next_instruction(&third).unwrap(), Instruction::RunAgent { .. }));
+    }
+
+    #[test]
+    fn raising_capacity_serves_the_queue() {
+        let mut groups = StageGroups::default();
+        assert!(groups.define("deploy", 0).is_err());
+        groups.define("deploy", 1).unwrap();
+        let (a, b) = (RunId::must("a"), RunId::must("b"));
+        assert!(groups.acquire("deploy", &a).unwrap());
+        assert!(!groups.acquire("deploy", &b).unwrap());
+        assert_eq!(groups.position("deploy", &b), Some(1));
+
+        groups.define("deploy", 2).unwrap();
+        assert_eq!(groups.held(&b), Some("deploy"));
+        assert!(groups.forget(&a));
+        let stats = groups.stats("deploy").unwrap();
+        assert_eq!((stats.in_use, stats.waiting, stats.acquired_total), (1, 0, 2));
+    }
+
+    #[test]
+    fn undefined_group_is_rejected_at_start() {
+        let mut kernel = Kernel::new();
+        let mut workflow = test_helpers::create_test_workflow();
+        workflow.stages[1].concurrency_group = Some("nowhere".into());
+        let err = kernel.initialize_run(RunId::must("ungrouped"), workflow, test_helpers::create_test_run(), false, None).unwrap_err();
+        assert!(err.to_string().contains("nowhere"));
+    }
+}
@@ -0,0 +1,200 @@
+//! Compact records of terminated runs.
+//!
+//! Termination and stale-session cleanup drop a run's state. Before they do,
+//! a [`TerminalRecord`] (identity, workflow, outcome, usage, timestamps) is
+//! pushed onto a bounded ring, oldest evicted first. The kernel does no I/O
+//! here: each record carries a sequence number, and consumers that want
+//! records to outlive the ring page through `terminal_records_since` and
+//! persist them themselves.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use super::{Kernel, ResourceUsage};
+use crate::run::TerminalReason;
+use crate::types::{RequestId, RunId, SessionId, UserId};
+
+/// Records kept in memory when the config does not say otherwise.
+pub const DEFAULT_TERMINAL_LOG_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerminalLogConfig {
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+}
+
+fn default_capacity() -> usize {
+    DEFAULT_TERMINAL_LOG_CAPACITY
+}
+
+impl Default for TerminalLogConfig {
+    fn default() -> Self {
+        Self { capacity: DEFAULT_TERMINAL_LOG_CAPACITY }
+    }
+}
+
+/// What is left of a run after it is gone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerminalRecord {
+    /// Assigned on push, from 1, increasing; resume point for
+    /// `terminal_records_since`.
+    #[serde(default)]
+    pub seq: u64,
+    pub run_id: RunId,
+    pub request_id: RequestId,
+    pub user_id: UserId,
+    pub session_id: SessionId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<String>,
+    /// `None` for runs removed by stale-session cleanup before finishing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_reason: Option<TerminalReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_message: Option<String>,
+    pub usage: ResourceUsage,
+    pub created_at: DateTime<Utc>,
+    pub terminated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct TerminalLog {
+    config: TerminalLogConfig,
+    records: VecDeque<TerminalRecord>,
+    last_seq: u64,
+}
+
+impl TerminalLog {
+    pub fn new(config: TerminalLogConfig) -> Self {
+        Self { config, records: VecDeque::new(), last_seq: 0 }
+    }
+
+    /// Stamp `record` with the next sequence number and retain it.
+    pub fn push(&mut self, mut record: TerminalRecord) {
+        self.last_seq += 1;
+        record.seq = self.last_seq;
+        if self.config.capacity == 0 {
+            return;
+        }
+        while self.records.len() >= self.config.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Newest first, optionally only `user_id`'s, at most `limit`.
+    pub fn recent(&self, user_id: Option<&str>, limit: usize) -> Vec<TerminalRecord> {
+        self.records.iter().rev()
+            .filter(|r| user_id.map_or(true, |u| r.user_id.as_str() == u))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Retained records with `seq` above `after`, oldest first, at most
+    /// `limit`. A first `seq` above `after + 1` means records were evicted
+    /// before the caller read them.
+    pub fn since(&self, after: u64, limit: usize) -> Vec<TerminalRecord> {
+        self.records.iter()
+            .filter(|r| r.seq > after)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Newest record of `run_id`, if still retained.
+    pub fn get(&self, run_id: &RunId) -> Option<&TerminalRecord> {
+        self.records.iter().rev().find(|r| &r.run_id == run_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl Kernel {
+    /// Record `run_id` in the terminal log. Call before its run, record and
+    /// session are dropped; `reason` overrides the run's own termination.
+    pub(crate) fn record_terminal(&mut self, run_id: &RunId, reason: Option<TerminalReason>, message: Option<String>) {
+        if let Some(record) = self.terminal_record(run_id, reason, message) {
+            self.terminal_log.push(record);
+        }
+    }
+
+    /// Build `run_id`'s terminal record without logging it.
+    pub(crate) fn terminal_record(&self, run_id: &RunId, reason: Option<TerminalReason>, message: Option<String>) -> Option<TerminalRecord> {
+        let run = self.runs.get(run_id)?;
+        let usage = self.lifecycle.get(run_id)
+            .map(|record| self.usage_from_run(run_id, record))
+            .unwrap_or_default();
+        Some(TerminalRecord {
+            seq: 0,
+            run_id: run_id.clone(),
+            request_id: run.identity.request_id.clone(),
+            user_id: run.identity.user_id.clone(),
+            session_id: run.identity.session_id.clone(),
+            workflow: self.orchestrator.sessions.get(run_id).map(|s| s.workflow.name.clone()),
+            terminal_reason: reason.or_else(|| run.termination.as_ref().map(|t| t.reason)),
+            terminal_message: message.or_else(|| run.termination.as_ref().and_then(|t| t.message.clone())),
+            usage,
+            created_at: run.audit.created_at,
+            terminated_at: self.clock.now(),
+        })
+    }
+
+    /// Terminated runs, newest first, optionally only `user_id`'s.
+    pub fn terminal_records(&self, user_id: Option<&str>, limit: usize) -> Vec<TerminalRecord> {
+        self.terminal_log.recent(user_id, limit)
+    }
+
+    /// Terminated runs recorded after `after_seq`, oldest first, for
+    /// consumers that persist them.
+    pub fn terminal_records_since(&self, after_seq: u64, limit: usize) -> Vec<TerminalRecord> {
+        self.terminal_log.since(after_seq, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers;
+
+    #[test]
+    fn records_survive_termination_and_ring_evicts_oldest() {
+        let mut kernel = Kernel::new();
+        kernel.terminal_log = TerminalLog::new(TerminalLogConfig { capacity: 2 });
+        for n in 0..3 {
+            let run_id = RunId::must(format!("gone-{}", n));
+            let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+            kernel.terminate_run(&run_id).unwrap();
+        }
+
+        let records = kernel.terminal_records(None, 10);
+        assert_eq!(records.iter().map(|r| r.run_id.as_str()).collect::<Vec<_>>(), vec!["gone-2", "gone-1"]);
+        assert_eq!(records[0].workflow.as_deref(), Some(test_helpers::create_test_workflow().name.as_str()));
+        assert_eq!(records[0].terminal_reason, Some(TerminalReason::Completed));
+        assert!(kernel.terminal_records(Some("nobody"), 10).is_empty());
+    }
+
+    #[test]
+    fn consumers_page_by_sequence() {
+        let mut kernel = Kernel::new();
+        kernel.terminal_log = TerminalLog::new(TerminalLogConfig { capacity: 2 });
+        for n in 0..3 {
+            let run_id = RunId::must(format!("paged-{}", n));
+            let _ = kernel.initialize_run(run_id.clone(), test_helpers::create_test_workflow(), test_helpers::create_test_run(), false, None).unwrap();
+            kernel.terminate_run(&run_id).unwrap();
+        }
+
+        // Record 1 was evicted; the gap tells the consumer.
+        let page = kernel.terminal_records_since(0, 1);
+        assert_eq!(page.iter().map(|r| (r.seq, r.run_id.as_str())).collect::<Vec<_>>(), vec![(2, "paged-1")]);
+        let page = kernel.terminal_records_since(page[0].seq, 10);
+        assert_eq!(page.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![3]);
+        assert!(kernel.terminal_records_since(3, 10).is_empty());
+    }
+}
@@ -1,8 +1,10 @@
 //! Kernel types: RunStatus, RunRecord, Resource tracking.
 
 use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use crate::types::{InterruptId, RunId, RequestId, SessionId, UserId};
+use std::collections::HashMap;
+use crate::types::{Error, InterruptId, Result, RunId, RequestId, SessionId, UserId};
 
 /// Run lifecycle state.
 ///
@@ -22,11 +24,32 @@ pub enum RunStatus {
     Terminated,
 }
 
+/// Allowed `RunStatus` edges, `(from, to)`. Every transition the kernel
+/// makes is checked against this table.
+pub const RUN_STATUS_TRANSITIONS: &[(RunStatus, RunStatus)] = &[
+    (RunStatus::Ready, RunStatus::Running),
+    (RunStatus::Ready, RunStatus::Terminated),
+    (RunStatus::Running, RunStatus::Terminated),
+];
+
 impl RunStatus {
     /// Check if this is a terminal state.
     pub fn is_terminal(self) -> bool {
         self == RunStatus::Terminated
     }
+
+    /// States reachable from this one in a single transition.
+    pub fn allowed_transitions(self) -> Vec<RunStatus> {
+        RUN_STATUS_TRANSITIONS
+            .iter()
+            .filter(|(from, _)| *from == self)
+            .map(|(_, to)| *to)
+            .collect()
+    }
+
+    pub fn can_transition_to(self, next: RunStatus) -> bool {
+        RUN_STATUS_TRANSITIONS.contains(&(self, next))
+    }
 }
 
 /// Resource quota — bounds enforced per run.
@@ -42,6 +65,28 @@ pub struct ResourceQuota {
     pub timeout_seconds: i32,
 }
 
+/// A quota profile's pipeline bounds, as defaults for workflows that leave
+/// theirs at 0.
+impl From<&ResourceQuota> for crate::run::BoundsDefaults {
+    fn from(quota: &ResourceQuota) -> Self {
+        Self {
+            max_iterations: quota.max_iterations,
+            max_llm_calls: quota.max_llm_calls,
+            max_agent_hops: quota.max_agent_hops,
+        }
+    }
+}
+
+/// Pipeline bounds as a quota with every other field unset.
+impl From<crate::run::BoundsDefaults> for ResourceQuota {
+    fn from(bounds: crate::run::BoundsDefaults) -> Self {
+        ResourceQuota::unset()
+            .with_max_iterations(bounds.max_iterations)
+            .with_max_llm_calls(bounds.max_llm_calls)
+            .with_max_agent_hops(bounds.max_agent_hops)
+    }
+}
+
 impl ResourceQuota {
     pub fn default_quota() -> Self {
         Self {
@@ -55,6 +100,135 @@ impl ResourceQuota {
             timeout_seconds: 300,
         }
     }
+
+    /// Every field `0`, i.e. unset: no limit is enforced and
+    /// [`merge_with_policy`](Self::merge_with_policy) treats it as missing.
+    pub fn unset() -> Self {
+        Self {
+            max_input_tokens: 0,
+            max_output_tokens: 0,
+            max_context_tokens: 0,
+            max_llm_calls: 0,
+            max_tool_calls: 0,
+            max_agent_hops: 0,
+            max_iterations: 0,
+            timeout_seconds: 0,
+        }
+    }
+
+    pub fn with_max_input_tokens(mut self, n: i32) -> Self {
+        self.max_input_tokens = n;
+        self
+    }
+
+    pub fn with_max_output_tokens(mut self, n: i32) -> Self {
+        self.max_output_tokens = n;
+        self
+    }
+
+    pub fn with_max_context_tokens(mut self, n: i32) -> Self {
+        self.max_context_tokens = n;
+        self
+    }
+
+    pub fn with_max_llm_calls(mut self, n: i32) -> Self {
+        self.max_llm_calls = n;
+        self
+    }
+
+    pub fn with_max_tool_calls(mut self, n: i32) -> Self {
+        self.max_tool_calls = n;
+        self
+    }
+
+    pub fn with_max_agent_hops(mut self, n: i32) -> Self {
+        self.max_agent_hops = n;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, n: i32) -> Self {
+        self.max_iterations = n;
+        self
+    }
+
+    /// `0` disables the wall-clock timeout.
+    pub fn with_timeout_seconds(mut self, n: i32) -> Self {
+        self.timeout_seconds = n;
+        self
+    }
+
+    fn fields(&self) -> [(&'static str, i32); 8] {
+        [
+            ("max_input_tokens", self.max_input_tokens),
+            ("max_output_tokens", self.max_output_tokens),
+            ("max_context_tokens", self.max_context_tokens),
+            ("max_llm_calls", self.max_llm_calls),
+            ("max_tool_calls", self.max_tool_calls),
+            ("max_agent_hops", self.max_agent_hops),
+            ("max_iterations", self.max_iterations),
+            ("timeout_seconds", self.timeout_seconds),
+        ]
+    }
+
+    /// Reject negative limits and a context window smaller than the
+    /// per-call input budget.
+    pub fn validate(&self) -> Result<()> {
+        if let Some((name, value)) = self.fields().into_iter().find(|(_, v)| *v < 0) {
+            return Err(Error::validation(format!("quota {} must be non-negative, got {}", name, value)));
+        }
+        if self.max_context_tokens > 0 && self.max_context_tokens < self.max_input_tokens {
+            return Err(Error::validation(format!(
+                "quota max_context_tokens ({}) must be >= max_input_tokens ({})",
+                self.max_context_tokens, self.max_input_tokens
+            )));
+        }
+        Ok(())
+    }
+
+    /// Combine with `other` field by field. See [`QuotaMergePolicy`]. In
+    /// every field `0` is unset (no limit): it never wins a `Min`, always
+    /// wins a `Max`, and is what `FillUnset` fills.
+    pub fn merge_with_policy(&self, other: &ResourceQuota, policy: QuotaMergePolicy) -> ResourceQuota {
+        let pick = |a: i32, b: i32| match (policy, a, b) {
+            (QuotaMergePolicy::Overwrite, _, b) => b,
+            (QuotaMergePolicy::FillUnset, 0, b) => b,
+            (QuotaMergePolicy::FillUnset, a, _) => a,
+            (QuotaMergePolicy::Min, 0, t) | (QuotaMergePolicy::Min, t, 0) => t,
+            (QuotaMergePolicy::Min, a, b) => a.min(b),
+            (QuotaMergePolicy::Max, 0, _) | (QuotaMergePolicy::Max, _, 0) => 0,
+            (QuotaMergePolicy::Max, a, b) => a.max(b),
+        };
+        ResourceQuota {
+            max_input_tokens: pick(self.max_input_tokens, other.max_input_tokens),
+            max_output_tokens: pick(self.max_output_tokens, other.max_output_tokens),
+            max_context_tokens: pick(self.max_context_tokens, other.max_context_tokens),
+            max_llm_calls: pick(self.max_llm_calls, other.max_llm_calls),
+            max_tool_calls: pick(self.max_tool_calls, other.max_tool_calls),
+            max_agent_hops: pick(self.max_agent_hops, other.max_agent_hops),
+            max_iterations: pick(self.max_iterations, other.max_iterations),
+            timeout_seconds: pick(self.timeout_seconds, other.timeout_seconds),
+        }
+    }
+
+    /// Budget left after `usage`, floored at zero per dimension. An unset
+    /// (`0`) limit leaves the maximum.
+    pub fn remaining(&self, usage: &ResourceUsage) -> RemainingBudget {
+        let left = |limit: i32, used: i32| if limit == 0 { i32::MAX } else { limit.saturating_sub(used).max(0) };
+        let left_tokens = |limit: i32, used: i64| if limit == 0 { i64::MAX } else { (limit as i64).saturating_sub(used).max(0) };
+        RemainingBudget {
+            llm_calls_remaining: left(self.max_llm_calls, usage.llm_calls),
+            tool_calls_remaining: left(self.max_tool_calls, usage.tool_calls),
+            iterations_remaining: left(self.max_iterations, usage.iterations),
+            agent_hops_remaining: left(self.max_agent_hops, usage.agent_hops),
+            tokens_in_remaining: left_tokens(self.max_input_tokens, usage.tokens_in),
+            tokens_out_remaining: left_tokens(self.max_output_tokens, usage.tokens_out),
+            time_remaining_seconds: if self.timeout_seconds > 0 {
+                (self.timeout_seconds as f64 - usage.elapsed_seconds).max(0.0)
+            } else {
+                f64::MAX
+            },
+        }
+    }
 }
 
 impl Default for ResourceQuota {
@@ -63,6 +237,151 @@ impl Default for ResourceQuota {
     }
 }
 
+/// How [`ResourceQuota::merge_with_policy`] combines two quotas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaMergePolicy {
+    /// The other quota replaces ours in every field, unset ones included.
+    Overwrite,
+    /// Ours is kept; only fields we leave unset take the other's value.
+    FillUnset,
+    /// Tighter limit wins.
+    Min,
+    /// Looser limit wins.
+    Max,
+}
+
+/// Quota suggested by `Kernel::recommend_quota` from recent usage.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaRecommendation {
+    pub quota: ResourceQuota,
+    /// Number of terminated runs the recommendation is based on.
+    pub samples: usize,
+}
+
+/// Remaining resource budget for a run.
+#[derive(Debug, Clone)]
+pub struct RemainingBudget {
+    pub llm_calls_remaining: i32,
+    pub tool_calls_remaining: i32,
+    pub iterations_remaining: i32,
+    pub agent_hops_remaining: i32,
+    pub tokens_in_remaining: i64,
+    pub tokens_out_remaining: i64,
+    pub time_remaining_seconds: f64,
+}
+
+/// State a run is dwelling in, for stuck-run detection. `Waiting` is
+/// `Running` with a pending interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DwellState {
+    Ready,
+    Running,
+    Waiting,
+}
+
+/// Per-state dwell limits for `Kernel::find_stuck_runs`. `None` disables
+/// the check for that state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StuckThresholds {
+    pub ready: Option<std::time::Duration>,
+    pub running: Option<std::time::Duration>,
+    pub waiting: Option<std::time::Duration>,
+}
+
+/// A run that has stayed in one state longer than its threshold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StuckRun {
+    pub run_id: RunId,
+    pub state: DwellState,
+    /// When the run entered `state`.
+    pub since: DateTime<Utc>,
+    pub dwell_seconds: f64,
+}
+
+/// Metadata keys the kernel writes itself; `annotate_runs` refuses them.
+pub const ANNOTATION_RESERVED_KEYS: &[&str] = &["loop_feedback", "_interrupt_response"];
+
+/// Upper bound on the serialized size of `AgentHeartbeat.progress`.
+pub const MAX_HEARTBEAT_PROGRESS_BYTES: usize = 1024;
+
+/// Latest liveness report from the worker executing a `RunAgent`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentHeartbeat {
+    pub dispatch_id: String,
+    /// Worker-defined hint, e.g. `{"step": 3, "of": 10}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub progress: Option<serde_json::Value>,
+    pub at: DateTime<Utc>,
+    /// Heartbeats received for this dispatch.
+    pub count: u32,
+}
+
+/// Filters for `Kernel::search_runs`. Every set field must match; an
+/// empty query matches all live runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<UserId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<SessionId>,
+    /// Exact `external_ref` the run was created with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<RunStatus>,
+    /// `Workflow.name` the run was initialized with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<String>,
+    /// `metadata[key] == value` for each entry.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+    /// Agent that has written to `outputs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_output_from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_reason: Option<crate::run::TerminalReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    /// Matches to skip, from a previous page's `next_offset`.
+    #[serde(default)]
+    pub offset: usize,
+    /// Page size; `0` means [`DEFAULT_SEARCH_LIMIT`].
+    #[serde(default)]
+    pub limit: usize,
+}
+
+/// Page size used when `RunQuery.limit` is `0`.
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// One page of `Kernel::search_runs` results, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSearchPage {
+    pub runs: Vec<RunRecord>,
+    /// Matches across all pages.
+    pub total: usize,
+    /// `offset` for the next page; `None` on the last one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+/// Budget moved from one run to a sibling by `Kernel::transfer_quota`.
+/// Zero fields are left untouched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaTransfer {
+    pub llm_calls: i32,
+    pub tool_calls: i32,
+    pub agent_hops: i32,
+}
+
+/// Largest share of a donor run's *unused* budget that a single transfer
+/// may take, per dimension.
+pub const MAX_QUOTA_TRANSFER_FRACTION: f64 = 0.5;
+
 /// Resource usage tracking.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct ResourceUsage {
@@ -102,24 +421,24 @@ impl std::fmt::Display for QuotaViolation {
 }
 
 impl ResourceUsage {
-    /// Check if any quota is exceeded.
+    /// Check if any quota is exceeded. Unset (`0`) limits are skipped.
     pub fn exceeds_quota(&self, quota: &ResourceQuota) -> Option<QuotaViolation> {
-        if self.llm_calls > quota.max_llm_calls {
+        if quota.max_llm_calls != 0 && self.llm_calls > quota.max_llm_calls {
             return Some(QuotaViolation::LlmCalls { used: self.llm_calls, limit: quota.max_llm_calls });
         }
-        if self.tool_calls > quota.max_tool_calls {
+        if quota.max_tool_calls != 0 && self.tool_calls > quota.max_tool_calls {
             return Some(QuotaViolation::ToolCalls { used: self.tool_calls, limit: quota.max_tool_calls });
         }
-        if self.agent_hops > quota.max_agent_hops {
+        if quota.max_agent_hops != 0 && self.agent_hops > quota.max_agent_hops {
             return Some(QuotaViolation::AgentHops { used: self.agent_hops, limit: quota.max_agent_hops });
         }
-        if self.iterations > quota.max_iterations {
+        if quota.max_iterations != 0 && self.iterations > quota.max_iterations {
             return Some(QuotaViolation::Iterations { used: self.iterations, limit: quota.max_iterations });
         }
-        if self.tokens_in > quota.max_input_tokens as i64 {
+        if quota.max_input_tokens != 0 && self.tokens_in > quota.max_input_tokens as i64 {
             return Some(QuotaViolation::TokensIn { used: self.tokens_in, limit: quota.max_input_tokens as i64 });
         }
-        if self.tokens_out > quota.max_output_tokens as i64 {
+        if quota.max_output_tokens != 0 && self.tokens_out > quota.max_output_tokens as i64 {
             return Some(QuotaViolation::TokensOut { used: self.tokens_out, limit: quota.max_output_tokens as i64 });
         }
         if quota.timeout_seconds > 0 && self.elapsed_seconds > quota.timeout_seconds as f64 {
@@ -160,6 +479,26 @@ pub struct RunRecord {
     /// on. None when actively running.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub pending_interrupt: Option<InterruptId>,
+
+    /// `Identity.external_ref` of the run this record was created for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_ref: Option<String>,
+
+    /// Embedder data (billing account, origin channel, ...). Opaque to the
+    /// kernel; bounded by [`MAX_RUN_EXTENSION_BYTES`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extensions: HashMap<String, RunExtension>,
+}
+
+/// Upper bound on the serialized size of `RunRecord.extensions`.
+pub const MAX_RUN_EXTENSION_BYTES: usize = 4096;
+
+/// One entry in `RunRecord.extensions`. `schema` names the value's shape
+/// (e.g. `"billing/v1"`) so readers can reject data they don't understand.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunExtension {
+    pub schema: String,
+    pub value: serde_json::Value,
 }
 
 impl RunRecord {
@@ -175,28 +514,76 @@ impl RunRecord {
             started_at: None,
             completed_at: None,
             pending_interrupt: None,
+            external_ref: None,
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Insert or replace an extension entry. Rejects an empty key or schema,
+    /// and any write that would push the map past [`MAX_RUN_EXTENSION_BYTES`].
+    pub fn insert_extension(&mut self, key: impl Into<String>, extension: RunExtension) -> Result<()> {
+        let key = key.into();
+        if key.is_empty() || extension.schema.is_empty() {
+            return Err(Error::validation("Extension key and schema must be non-empty"));
+        }
+        let previous = self.extensions.insert(key.clone(), extension);
+        let size = serde_json::to_vec(&self.extensions).map(|v| v.len()).unwrap_or(usize::MAX);
+        if size > MAX_RUN_EXTENSION_BYTES {
+            match previous {
+                Some(prev) => self.extensions.insert(key.clone(), prev),
+                None => self.extensions.remove(&key),
+            };
+            return Err(Error::validation(format!(
+                "Extension '{}' would grow run extensions to {} bytes (max {})",
+                key, size, MAX_RUN_EXTENSION_BYTES
+            )));
         }
+        Ok(())
     }
 
-    /// Transition to RUNNING state.
-    pub(crate) fn start(&mut self) {
+    /// Serialize `value` and store it under `key`, tagged with `schema`.
+    pub fn set_extension<T: Serialize>(&mut self, key: &str, schema: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| Error::validation(format!("Extension '{}': {}", key, e)))?;
+        self.insert_extension(key, RunExtension { schema: schema.to_string(), value })
+    }
+
+    /// Read the extension under `key` as `T`. `Ok(None)` when absent; an
+    /// error when it carries a different schema tag or does not decode.
+    pub fn extension<T: DeserializeOwned>(&self, key: &str, schema: &str) -> Result<Option<T>> {
+        let Some(ext) = self.extensions.get(key) else {
+            return Ok(None);
+        };
+        if ext.schema != schema {
+            return Err(Error::validation(format!(
+                "Extension '{}' has schema '{}', expected '{}'",
+                key, ext.schema, schema
+            )));
+        }
+        serde_json::from_value(ext.value.clone())
+            .map(Some)
+            .map_err(|e| Error::validation(format!("Extension '{}': {}", key, e)))
+    }
+
+    /// Transition to RUNNING state at `now`.
+    pub(crate) fn start(&mut self, now: DateTime<Utc>) {
         self.state = RunStatus::Running;
-        self.started_at = Some(Utc::now());
+        self.started_at = Some(now);
     }
 
-    /// Transition to TERMINATED state.
-    pub(crate) fn complete(&mut self) {
+    /// Transition to TERMINATED state at `now`.
+    pub(crate) fn complete(&mut self, now: DateTime<Utc>) {
         self.state = RunStatus::Terminated;
-        self.completed_at = Some(Utc::now());
+        self.completed_at = Some(now);
     }
 
-    /// Elapsed wall-clock seconds since `start()`. `0.0` before start. Frozen
-    /// after `complete()`.
-    pub fn elapsed_seconds(&self) -> f64 {
+    /// Seconds from `start()` to `now`. `0.0` before start. Frozen after
+    /// `complete()`.
+    pub fn elapsed_seconds(&self, now: DateTime<Utc>) -> f64 {
         let Some(started) = self.started_at else {
             return 0.0;
         };
-        let end = self.completed_at.unwrap_or_else(Utc::now);
+        let end = self.completed_at.unwrap_or(now);
         (end - started).num_milliseconds() as f64 / 1000.0
     }
 
@@ -205,3 +592,115 @@ impl RunRecord {
         self.state.is_terminal()
     }
 }
+
+/// The kernel's live runs. Reads go straight to the map. Paths that change
+/// what callers see of a run (outputs, state, metadata, interrupts,
+/// termination) take it through `edit`, which bumps `Run.revision`;
+/// `get_mut` leaves the revision alone, so polling never causes a conflict.
+#[derive(Debug, Default)]
+pub struct RunStore(HashMap<RunId, crate::run::Run>);
+
+impl RunStore {
+    pub fn get_mut(&mut self, run_id: &RunId) -> Option<&mut crate::run::Run> {
+        self.0.get_mut(run_id)
+    }
+
+    /// `get_mut` for a change callers can see; bumps `Run.revision`.
+    pub fn edit(&mut self, run_id: &RunId) -> Option<&mut crate::run::Run> {
+        let run = self.0.get_mut(run_id)?;
+        run.revision += 1;
+        Some(run)
+    }
+
+    pub fn insert(&mut self, run_id: RunId, run: crate::run::Run) -> Option<crate::run::Run> {
+        self.0.insert(run_id, run)
+    }
+
+    pub fn remove(&mut self, run_id: &RunId) -> Option<crate::run::Run> {
+        self.0.remove(run_id)
+    }
+}
+
+impl std::ops::Deref for RunStore {
+    type Target = HashMap<RunId, crate::run::Run>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a RunStore {
+    type Item = (&'a RunId, &'a crate::run::Run);
+    type IntoIter = std::collections::hash_map::Iter<'a, RunId, crate::run::Run>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_extensions_typed_and_bounded() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Billing {
+            account: String,
+        }
+
+        let mut record = RunRecord::new(
+            RunId::must("r1"), RequestId::must("q"), UserId::must("u"), SessionId::must("s"),
+        );
+        let billing = Billing { account: "acct-7".into() };
+        record.set_extension("billing", "billing/v1", &billing).unwrap();
+        assert_eq!(record.extension::<Billing>("billing", "billing/v1").unwrap(), Some(billing));
+        assert!(record.extension::<Billing>("billing", "billing/v2").is_err());
+        assert_eq!(record.extension::<Billing>("origin", "origin/v1").unwrap(), None);
+
+        let huge = "x".repeat(MAX_RUN_EXTENSION_BYTES);
+        assert!(record.set_extension("billing", "billing/v1", &huge).is_err());
+        assert_eq!(record.extensions["billing"].value, serde_json::json!({"account": "acct-7"}));
+        assert!(record.set_extension("", "billing/v1", &1).is_err());
+    }
+
+    #[test]
+    fn test_quota_validate() {
+        assert!(ResourceQuota::default().validate().is_ok());
+        assert!(ResourceQuota::default().with_max_llm_calls(-1).validate().is_err());
+        assert!(ResourceQuota::default().with_max_context_tokens(10).validate().is_err());
+        assert!(ResourceQuota::unset().validate().is_ok());
+    }
+
+    #[test]
+    fn test_quota_merge_policies() {
+        let base = ResourceQuota::default().with_max_llm_calls(10).with_timeout_seconds(0);
+        let other = ResourceQuota::default().with_max_llm_calls(4).with_max_tool_calls(0).with_timeout_seconds(60);
+
+        let over = base.merge_with_policy(&other, QuotaMergePolicy::Overwrite);
+        assert_eq!(over, other);
+
+        let fill = base.merge_with_policy(&other, QuotaMergePolicy::FillUnset);
+        assert_eq!((fill.max_llm_calls, fill.max_tool_calls, fill.timeout_seconds), (10, base.max_tool_calls, 60));
+
+        // Unset never wins a Min and always wins a Max, in every field.
+        let min = base.merge_with_policy(&other, QuotaMergePolicy::Min);
+        assert_eq!((min.max_llm_calls, min.max_tool_calls, min.timeout_seconds), (4, base.max_tool_calls, 60));
+
+        let max = base.merge_with_policy(&other, QuotaMergePolicy::Max);
+        assert_eq!((max.max_llm_calls, max.max_tool_calls, max.timeout_seconds), (10, 0, 0));
+    }
+
+    #[test]
+    fn test_quota_remaining_saturates() {
+        let quota = ResourceQuota::default().with_max_llm_calls(i32::MIN + 1);
+        let usage = ResourceUsage { llm_calls: i32::MAX, tool_calls: 100, ..Default::default() };
+        let left = quota.remaining(&usage);
+        assert_eq!(left.llm_calls_remaining, 0);
+        assert_eq!(left.tool_calls_remaining, 0);
+        assert_eq!(left.agent_hops_remaining, quota.max_agent_hops);
+
+        let unset = ResourceQuota::unset().remaining(&usage);
+        assert_eq!((unset.llm_calls_remaining, unset.tokens_in_remaining), (i32::MAX, i64::MAX));
+    }
+}
@@ -0,0 +1,219 @@
+//! Dry-run workflow validation.
+//!
+//! `Kernel::validate_pipeline` runs every static check a workflow would face
+//! at `initialize_session`, plus checks that need the live kernel or span
+//! stages, and returns all findings at once instead of the first error. No
+//! run or session is created, so CI can gate workflow changes against a
+//! running kernel.
+//!
+//! Errors would make `initialize_session` reject the workflow or name a
+//! routing function this kernel cannot call. Warnings are legal configs that
+//! are probably mistakes: unreachable stages, inputs bound to outputs no
+//! stage produces, and `state_schema` keys no stage merges into.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use super::Kernel;
+use crate::workflow::{Stage, Workflow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PipelineDiagnostic {
+    pub severity: DiagnosticSeverity,
+    /// Stable machine-readable identifier, e.g. `unknown_routing_fn`.
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    pub message: String,
+}
+
+/// Result of [`Kernel::validate_pipeline`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PipelineValidation {
+    /// True when there are no `Error` diagnostics.
+    pub valid: bool,
+    pub diagnostics: Vec<PipelineDiagnostic>,
+}
+
+impl PipelineValidation {
+    pub fn errors(&self) -> impl Iterator<Item = &PipelineDiagnostic> {
+        self.diagnostics.iter().filter(|d| d.severity == DiagnosticSeverity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &PipelineDiagnostic> {
+        self.diagnostics.iter().filter(|d| d.severity == DiagnosticSeverity::Warning)
+    }
+}
+
+fn diagnostic(severity: DiagnosticSeverity, code: &'static str, stage: Option<&Stage>, message: String) -> PipelineDiagnostic {
+    PipelineDiagnostic { severity, code, stage: stage.map(|s| s.name.as_str().to_string()), message }
+}
+
+impl Kernel {
+    /// Check `workflow` without initializing anything.
+    pub fn validate_pipeline(&self, workflow: &Workflow) -> PipelineValidation {
+        use DiagnosticSeverity::{Error, Warning};
+
+        let mut diagnostics: Vec<PipelineDiagnostic> = workflow.validation_errors().into_iter()
+            .map(|e| {
+                let message = match e {
+                    crate::types::Error::Validation { message, .. } => message,
+                    other => other.to_string(),
+                };
+                diagnostic(Error, "invalid_config", None, message)
+            })
+            .collect();
+
+        for stage in &workflow.stages {
+            if let Some(routing_fn) = &stage.routing_fn {
+                if !self.orchestrator.routing_registry.contains(routing_fn.as_str()) {
+                    diagnostics.push(diagnostic(Error, "unknown_routing_fn", Some(stage), format!(
+                        "routing_fn '{}' is not registered with this kernel", routing_fn
+                    )));
+                }
+            }
+        }
+
+        for stage in &workflow.stages {
+            if let Some(group) = &stage.concurrency_group {
+                if self.stage_groups.stats(group).is_none() {
+                    diagnostics.push(diagnostic(Error, "unknown_concurrency_group", Some(stage), format!(
+                        "concurrency_group '{}' is not a defined group", group
+                    )));
+                }
+            }
+        }
+
+        for stage in self.undeclared_stages(workflow) {
+            diagnostics.push(diagnostic(Error, "undeclared_agent", Some(stage), format!(
+                "agent '{}' is not declared in Config.agents at a matching version", stage.agent
+            )));
+        }
+
+        // A routing function may pick any stage, so reachability is only
+        // known when every edge is static.
+        if workflow.stages.iter().all(|s| s.routing_fn.is_none()) {
+            let reachable = reachable_stages(workflow);
+            for stage in workflow.stages.iter().filter(|s| !reachable.contains(s.name.as_str())) {
+                diagnostics.push(diagnostic(Warning, "unreachable_stage", Some(stage), format!(
+                    "Stage '{}' is not reachable from '{}'", stage.name, workflow.stages[0].name
+                )));
+            }
+        }
+
+        let producers: HashMap<&str, &Stage> = workflow.stages.iter().map(|s| (s.agent.as_str(), s)).collect();
+        for stage in &workflow.stages {
+            let mut inputs: Vec<(&String, &String)> = stage.inputs.iter().collect();
+            inputs.sort();
+            for (name, path) in inputs {
+                let mut segments = path.split('.');
+                if segments.next() != Some("outputs") {
+                    continue;
+                }
+                let Some(agent) = segments.next() else {
+                    continue;
+                };
+                let Some(producer) = producers.get(agent) else {
+                    diagnostics.push(diagnostic(Warning, "unbound_input", Some(stage), format!(
+                        "Input '{}' reads '{}' but no stage runs agent '{}'", name, path, agent
+                    )));
+                    continue;
+                };
+                let properties = producer.output_schema.as_ref()
+                    .and_then(|s| s.get("properties"))
+                    .and_then(|p| p.as_object());
+                if let (Some(field), Some(properties)) = (segments.next(), properties) {
+                    if !properties.contains_key(field) {
+                        diagnostics.push(diagnostic(Warning, "input_not_in_schema", Some(stage), format!(
+                            "Input '{}' reads '{}' but stage '{}' output_schema has no property '{}'",
+                            name, path, producer.name, field
+                        )));
+                    }
+                }
+            }
+        }
+
+        let merged: HashSet<&str> = workflow.stages.iter()
+            .map(|s| s.output_key.as_ref().map_or(s.agent.as_str(), |k| k.as_str()))
+            .collect();
+        for field in workflow.state_schema.iter().filter(|f| !merged.contains(f.key.as_str())) {
+            diagnostics.push(diagnostic(Warning, "unused_state_field", None, format!(
+                "state_schema key '{}' matches no stage output_key or agent", field.key
+            )));
+        }
+
+        PipelineValidation {
+            valid: !diagnostics.iter().any(|d| d.severity == Error),
+            diagnostics,
+        }
+    }
+}
+
+/// Stages reachable from the first one over `default_next` and `error_next`.
+fn reachable_stages(workflow: &Workflow) -> HashSet<&str> {
+    let by_name: HashMap<&str, &Stage> = workflow.stages.iter().map(|s| (s.name.as_str(), s)).collect();
+    let mut seen = HashSet::new();
+    let mut frontier: Vec<&str> = workflow.stages.first().map(|s| s.name.as_str()).into_iter().collect();
+    while let Some(name) = frontier.pop() {
+        if !seen.insert(name) {
+            continue;
+        }
+        if let Some(stage) = by_name.get(name) {
+            frontier.extend(stage.default_next.as_ref().map(|n| n.as_str()));
+            frontier.extend(stage.error_next.as_ref().map(|n| n.as_str()));
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::test_helpers::stage;
+    use crate::workflow::{MergeStrategy, StateField};
+
+    fn codes(validation: &PipelineValidation) -> Vec<&'static str> {
+        validation.diagnostics.iter().map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn reports_every_problem_without_creating_a_session() {
+        let kernel = Kernel::new();
+        let mut workflow = Workflow::test_default("dry-run", vec![
+            stage("first", "first_agent", None, Some("second")),
+            stage("second", "second_agent", None, None),
+            stage("orphan", "orphan_agent", None, None),
+        ]);
+        workflow.max_iterations = -1;
+        workflow.stages[0].output_schema = Some(serde_json::json!({"type": "object", "properties": {"query": {}}}));
+        workflow.stages[1].error_next = Some("missing".into());
+        workflow.stages[1].inputs.insert("q".into(), "outputs.first_agent.qeury".into());
+        workflow.stages[1].inputs.insert("r".into(), "outputs.ghost.field".into());
+        workflow.state_schema.push(StateField { key: "notes".into(), merge: MergeStrategy::Replace });
+
+        let validation = kernel.validate_pipeline(&workflow);
+        assert!(!validation.valid);
+        assert_eq!(codes(&validation), vec![
+            "invalid_config", "invalid_config", "unreachable_stage", "input_not_in_schema", "unbound_input", "unused_state_field",
+        ]);
+        assert_eq!(validation.diagnostics[2].stage.as_deref(), Some("orphan"));
+        assert!(kernel.orchestrator.sessions.is_empty());
+        assert!(kernel.runs.is_empty());
+    }
+
+    #[test]
+    fn unregistered_routing_fn_is_an_error() {
+        let workflow = Workflow::test_default("routed", vec![stage("only", "agent", Some("pick_next"), None)]);
+
+        let validation = Kernel::new().validate_pipeline(&workflow);
+        assert!(!validation.valid);
+        assert_eq!(codes(&validation), vec!["unknown_routing_fn"]);
+    }
+}
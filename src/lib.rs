@@ -51,8 +51,8 @@ pub mod prelude {
     pub use crate::agent::AgentRegistry;
     pub use crate::run::Run;
     pub use crate::kernel::actor::spawn;
-    pub use crate::kernel::handle::KernelHandle;
-    pub use crate::workflow::Workflow;
+    pub use crate::kernel::handle::{KernelHandle, KernelObserver};
+    pub use crate::workflow::{PipelineBuilder, Workflow};
     pub use crate::kernel::routing::{RoutingContext, RoutingFn, RoutingResult};
     pub use crate::kernel::runner::{run, run_streaming, WorkerResult};
     pub use crate::kernel::Kernel;
@@ -6,15 +6,17 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::types::{AgentName, EnvelopeId, OutputKey, RequestId, SessionId, StageName, UserId};
+use crate::types::{AgentName, EnvelopeId, IdFormat, OutputKey, RequestId, SessionId, StageName, UserId};
 
 pub mod enums;
 pub mod events;
 pub mod types;
+pub mod updates;
 
 pub use enums::*;
 pub use events::{AggregateMetrics, RunEvent, StageMetrics};
 pub use types::*;
+pub use updates::{FieldConflict, UpdatePolicy, UpdateRejection};
 
 #[must_use]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -26,6 +28,10 @@ pub struct Run {
     /// `agent_name → output_key → value`. Any agent can write here.
     pub outputs: HashMap<AgentName, HashMap<OutputKey, serde_json::Value>>,
 
+    /// Provenance of each `outputs` entry, keyed the same way.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub outputs_provenance: HashMap<AgentName, OutputProvenance>,
+
     /// Accumulator merged across loop-backs per `state_schema`.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub state: HashMap<String, serde_json::Value>,
@@ -41,31 +47,48 @@ pub struct Run {
     pub termination: Option<Termination>,
     pub interrupts: InterruptState,
     pub audit: Audit,
+
+    /// Bumped by the kernel on every change callers can see; compare-and-swap
+    /// callers pass the revision they read (see `KernelHandle::update_run`).
+    #[serde(default)]
+    pub revision: u64,
 }
 
 impl Run {
     /// Anonymous Run with a generated identity. Test convenience.
     pub fn anonymous() -> Self {
-        let uuid_short = || uuid::Uuid::new_v4().simple().to_string()[..16].to_string();
         Self::new(
             "anonymous",
-            &format!("sess_{}", uuid_short()),
+            &format!("sess_{}", IdFormat::default().generate_short()),
             "",
             None,
         )
     }
 
-    /// Run instance with the given identity and inputs. Pipeline bounds are
-    /// safe placeholders that `initialize_orchestration` overwrites from the
-    /// `Workflow`.
+    /// Run instance with the given identity and inputs, bounded by
+    /// [`BoundsDefaults::default`] and with UUID v4 IDs.
+    /// `initialize_orchestration` overwrites the bounds from the `Workflow`.
     pub fn new(
         user_id: &str,
         session_id: &str,
         raw_input: &str,
         metadata: Option<serde_json::Value>,
+    ) -> Self {
+        Self::with_defaults(user_id, session_id, raw_input, metadata, &BoundsDefaults::default(), IdFormat::default())
+    }
+
+    /// As [`new`](Self::new), starting from `bounds` and generating IDs in
+    /// `ids` (e.g. `ServerInfo.limits.default_bounds` and
+    /// `ServerInfo.id_format`).
+    pub fn with_defaults(
+        user_id: &str,
+        session_id: &str,
+        raw_input: &str,
+        metadata: Option<serde_json::Value>,
+        bounds: &BoundsDefaults,
+        ids: IdFormat,
     ) -> Self {
         let now = Utc::now();
-        let uuid_short = || uuid::Uuid::new_v4().simple().to_string()[..16].to_string();
 
         let mut audit_metadata = HashMap::new();
         if let Some(serde_json::Value::Object(map)) = metadata {
@@ -76,34 +99,39 @@ impl Run {
 
         Self {
             identity: Identity {
-                envelope_id: EnvelopeId::must(format!("env_{}", uuid_short())),
-                request_id: RequestId::must(format!("req_{}", uuid_short())),
+                envelope_id: EnvelopeId::must(format!("env_{}", ids.generate_short())),
+                request_id: RequestId::must(format!("req_{}", ids.generate_short())),
                 user_id: UserId::must(user_id),
                 session_id: SessionId::must(session_id),
+                external_ref: None,
             },
             raw_input: raw_input.to_string(),
             received_at: now,
             outputs: HashMap::new(),
+            outputs_provenance: HashMap::new(),
             state: HashMap::new(),
             current_stage: StageName::default(),
             stage_order: Vec::new(),
             iteration: 0,
-            max_iterations: 100,
+            max_iterations: bounds.max_iterations,
             limits: Limits {
-                max_llm_calls: 100,
-                max_agent_hops: 100,
+                max_llm_calls: bounds.max_llm_calls,
+                max_agent_hops: bounds.max_agent_hops,
             },
             metrics: Metrics::default(),
             termination: None,
             interrupts: InterruptState {
                 interrupt: None,
+                history: Vec::new(),
             },
             audit: Audit {
                 processing_history: Vec::new(),
                 created_at: now,
                 completed_at: None,
                 metadata: audit_metadata,
+                field_revisions: HashMap::new(),
             },
+            revision: 0,
         }
     }
 
@@ -127,6 +155,14 @@ impl Run {
         self.check_bounds().is_some()
     }
 
+    /// Whether `agent`'s output still matches its provenance hash. `None`
+    /// when either is missing.
+    pub fn output_matches_provenance(&self, agent: &str) -> Option<bool> {
+        let output = self.outputs.get(agent)?;
+        let stamp = self.outputs_provenance.get(agent)?;
+        Some(OutputProvenance::fingerprint(output) == stamp.content_hash)
+    }
+
     pub fn is_terminated(&self) -> bool {
         self.termination.is_some()
     }
@@ -143,22 +179,136 @@ impl Run {
         self.audit.processing_history.push(record);
     }
 
-    /// Terminate this Run as completed, recording `reason` as the message.
-    pub fn complete(&mut self, reason: impl Into<String>) {
+    /// Terminate this Run as completed at `now`, recording `reason` as the
+    /// message.
+    pub fn complete(&mut self, reason: impl Into<String>, now: DateTime<Utc>) {
         self.terminate_with(TerminalReason::Completed, Some(reason.into()));
-        self.audit.completed_at = Some(Utc::now());
+        self.audit.completed_at = Some(now);
     }
 
     /// Set interrupt pending.
     pub fn set_interrupt(&mut self, interrupt: FlowInterrupt) {
+        self.interrupts.history.push(InterruptRecord {
+            id: interrupt.id.clone(),
+            text: interrupt.question.clone().or_else(|| interrupt.message.clone()),
+            raised_at: interrupt.created_at,
+            resolved_at: None,
+            responder: None,
+            delegations: interrupt.delegations.clone(),
+        });
         self.interrupts.interrupt = Some(interrupt);
     }
 
+    /// Record a hand-off of the pending interrupt on it and in its history
+    /// entry. Returns false when `interrupt_id` isn't pending.
+    pub fn delegate_interrupt(&mut self, interrupt_id: &str, delegation: InterruptDelegation) -> bool {
+        let Some(pending) = self.interrupts.interrupt.as_mut().filter(|i| i.id.as_str() == interrupt_id) else {
+            return false;
+        };
+        pending.delegations.push(delegation.clone());
+        if let Some(record) = self.interrupts.history.iter_mut().rev().find(|r| r.id.as_str() == interrupt_id) {
+            record.delegations.push(delegation);
+        }
+        true
+    }
+
+    /// Clear the pending interrupt, noting `response` in its history entry.
+    pub fn resolve_interrupt(&mut self, response: &InterruptResponse) {
+        if let Some(pending) = self.interrupts.interrupt.take() {
+            if let Some(record) = self.interrupts.history.iter_mut().rev().find(|r| r.id == pending.id) {
+                record.resolved_at = Some(response.received_at);
+                record.responder = response.responder.clone();
+            }
+        }
+    }
+
     /// Clear interrupt.
     pub fn clear_interrupt(&mut self) {
         self.interrupts.interrupt = None;
     }
 
+    /// Append to `metadata["loop_feedback"]`. An entry identical to the
+    /// last one (same severity, code, message and stage) bumps its
+    /// `repeats` instead of growing the list; past `MAX_LOOP_FEEDBACK`
+    /// typed entries the oldest are dropped and counted. Entries that
+    /// aren't `LoopFeedback`, written by other code, are left as they are.
+    pub fn push_loop_feedback(&mut self, entry: LoopFeedback) {
+        let slot = self.audit.metadata
+            .entry("loop_feedback".to_string())
+            .or_insert_with(|| serde_json::json!([]));
+        if !slot.is_array() {
+            *slot = serde_json::json!([slot.take()]);
+        }
+        let Some(entries) = slot.as_array_mut() else { return };
+
+        let last = entries.last_mut()
+            .and_then(|v| serde_json::from_value::<LoopFeedback>(v.clone()).ok().map(|typed| (v, typed)));
+        match last {
+            Some((raw, mut last))
+                if last.severity == entry.severity
+                    && last.code == entry.code
+                    && last.message == entry.message
+                    && last.stage == entry.stage =>
+            {
+                last.repeats += 1;
+                last.iteration = entry.iteration;
+                last.details = entry.details;
+                *raw = serde_json::to_value(last).unwrap_or_default();
+            }
+            _ => entries.push(serde_json::to_value(entry).unwrap_or_default()),
+        }
+
+        let typed: Vec<usize> = entries.iter().enumerate()
+            .filter(|(_, v)| is_loop_feedback(v))
+            .map(|(i, _)| i)
+            .collect();
+        if typed.len() > MAX_LOOP_FEEDBACK {
+            let excess = typed.len() - MAX_LOOP_FEEDBACK;
+            for &i in typed[..excess].iter().rev() {
+                entries.remove(i);
+            }
+            let dropped = self.audit.metadata
+                .get("loop_feedback_dropped")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0);
+            self.audit.metadata.insert(
+                "loop_feedback_dropped".to_string(),
+                serde_json::json!(dropped + excess as u64),
+            );
+        }
+    }
+
+    /// Typed view of `metadata["loop_feedback"]`. Entries that don't parse
+    /// are skipped.
+    pub fn loop_feedback(&self) -> Vec<LoopFeedback> {
+        loop_feedback_from(&self.audit.metadata)
+    }
+
+    /// Drop the typed loop feedback and its dropped count. Entries written
+    /// by other code stay.
+    pub fn clear_loop_feedback(&mut self) {
+        self.audit.metadata.remove("loop_feedback_dropped");
+        let Some(entries) = self.audit.metadata.get_mut("loop_feedback").and_then(|v| v.as_array_mut()) else {
+            return;
+        };
+        entries.retain(|v| !is_loop_feedback(v));
+        if entries.is_empty() {
+            self.audit.metadata.remove("loop_feedback");
+        }
+    }
+
+    /// Remove the entries with `code` recorded for `stage`, leaving any
+    /// other feedback in place.
+    pub fn remove_loop_feedback(&mut self, code: &str, stage: &str) {
+        let Some(entries) = self.audit.metadata.get_mut("loop_feedback").and_then(|v| v.as_array_mut()) else {
+            return;
+        };
+        entries.retain(|e| !(e["code"] == code && e["stage"] == stage));
+        if entries.is_empty() {
+            self.audit.metadata.remove("loop_feedback");
+        }
+    }
+
     /// Validate run invariants.
     ///
     /// Called after deserialization from external input to catch malformed
@@ -220,18 +370,22 @@ impl Run {
     ///
     /// Supports updating well-known fields: `raw_input`, `metadata` (merged into
     /// `audit.metadata`), and `outputs` (merged into `outputs`). Unknown keys
-    /// are stored in `audit.metadata` as a catch-all.
+    /// are stored in `audit.metadata` as a catch-all. Malformed values are
+    /// skipped; `apply_updates` is the strict variant. Each field written
+    /// has its `audit.field_revisions` entry bumped.
     pub fn merge_updates(&mut self, updates: HashMap<String, serde_json::Value>) {
         for (key, value) in updates {
             match key.as_str() {
                 "raw_input" => {
                     if let Some(s) = value.as_str() {
                         self.raw_input = s.to_string();
+                        self.bump_revision("raw_input".to_string());
                     }
                 }
                 "metadata" => {
                     if let serde_json::Value::Object(map) = value {
                         for (k, v) in map {
+                            self.bump_revision(format!("metadata.{}", k));
                             self.audit.metadata.insert(k, v);
                         }
                     }
@@ -239,17 +393,36 @@ impl Run {
                 "outputs" => {
                     if let Ok(output_map) = serde_json::from_value::<HashMap<AgentName, HashMap<OutputKey, serde_json::Value>>>(value) {
                         for (agent, output) in output_map {
+                            self.bump_revision(format!("outputs.{}", agent));
                             self.outputs.entry(agent).or_default().extend(output);
                         }
                     }
                 }
                 _ => {
                     // Store unknown keys in audit metadata
+                    self.bump_revision(format!("metadata.{}", key));
                     self.audit.metadata.insert(key, value);
                 }
             }
         }
     }
+
+    fn bump_revision(&mut self, field: String) {
+        *self.audit.field_revisions.entry(field).or_default() += 1;
+    }
+}
+
+fn is_loop_feedback(value: &serde_json::Value) -> bool {
+    serde_json::from_value::<LoopFeedback>(value.clone()).is_ok()
+}
+
+/// Parse `metadata["loop_feedback"]`; shared by `Run` and `RoutingContext`.
+pub(crate) fn loop_feedback_from(metadata: &HashMap<String, serde_json::Value>) -> Vec<LoopFeedback> {
+    metadata
+        .get("loop_feedback")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|v| serde_json::from_value(v.clone()).ok()).collect())
+        .unwrap_or_default()
 }
 
 impl Default for Run {
@@ -357,14 +530,15 @@ mod tests {
         assert!(!env.is_terminated());
         assert!(env.audit.completed_at.is_none());
 
-        env.complete("user cancelled the request");
+        let at = DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        env.complete("user cancelled the request", at);
 
         assert!(env.is_terminated());
         assert_eq!(
             env.termination.as_ref().unwrap().message.as_deref(),
             Some("user cancelled the request")
         );
-        assert!(env.audit.completed_at.is_some());
+        assert_eq!(env.audit.completed_at, Some(at));
     }
 
     // ── 8. interrupt flow ───────────────────────────────────────────────
@@ -401,6 +575,7 @@ mod tests {
 
         let record = ProcessingRecord {
             agent: "test-agent".to_string(),
+            agent_version: None,
             stage_order: 1,
             started_at: Utc::now(),
             completed_at: Some(Utc::now()),
@@ -411,6 +586,8 @@ mod tests {
             tool_calls: 0,
             tokens_in: 0,
             tokens_out: 0,
+            model: None,
+            tools: vec![],
         };
 
         env.add_processing_record(record.clone());
@@ -605,4 +782,37 @@ mod tests {
         env.current_stage = "understand".into();
         assert!(env.validate().is_ok());
     }
+
+    #[test]
+    fn test_loop_feedback_dedups_and_caps() {
+        let mut run = Run::anonymous();
+        let foreign = serde_json::json!({"note": "kept as written"});
+        run.audit.metadata.insert("loop_feedback".to_string(), serde_json::json!([foreign.clone()]));
+        let entry = |code: &str, iteration: i32| LoopFeedback {
+            severity: FeedbackSeverity::Warning,
+            code: code.to_string(),
+            message: "retry".to_string(),
+            stage: "s1".to_string(),
+            iteration,
+            details: Vec::new(),
+            repeats: 1,
+        };
+
+        run.push_loop_feedback(entry("a", 1));
+        run.push_loop_feedback(entry("a", 2));
+        let feedback = run.loop_feedback();
+        assert_eq!(feedback.len(), 1);
+        assert_eq!((feedback[0].repeats, feedback[0].iteration), (2, 2));
+
+        for i in 0..MAX_LOOP_FEEDBACK as i32 + 3 {
+            run.push_loop_feedback(entry(&format!("c{}", i), i));
+        }
+        assert_eq!(run.loop_feedback().len(), MAX_LOOP_FEEDBACK);
+        assert_eq!(run.audit.metadata["loop_feedback_dropped"], 4);
+        assert_eq!(run.audit.metadata["loop_feedback"][0], foreign);
+
+        run.clear_loop_feedback();
+        assert_eq!(run.audit.metadata["loop_feedback"], serde_json::json!([foreign]));
+        assert!(!run.audit.metadata.contains_key("loop_feedback_dropped"));
+    }
 }
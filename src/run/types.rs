@@ -4,7 +4,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::types::{EnvelopeId, InterruptId, RequestId, SessionId, UserId};
+use crate::types::{EnvelopeId, IdFormat, InterruptId, RequestId, SessionId, UserId};
 
 
 /// Response to a flow interrupt.
@@ -22,9 +22,110 @@ pub struct InterruptResponse {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub data: Option<HashMap<String, serde_json::Value>>,
 
+    /// Who answered (user id, operator name), as reported by the caller.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responder: Option<String>,
+
     pub received_at: DateTime<Utc>,
 }
 
+/// What a response to an interrupt must contain. Checked as the interrupt
+/// is resolved; an unset spec accepts anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResponseSpec {
+    /// At least one of `text`, `approved`, `decision` or `data`.
+    #[serde(default)]
+    pub require_response: bool,
+    #[serde(default)]
+    pub require_approved: bool,
+    #[serde(default)]
+    pub require_text: bool,
+    /// When non-empty, `decision` is required and must be one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_decisions: Vec<String>,
+    /// Keys `data` must carry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_data: Vec<String>,
+}
+
+/// Why a response failed its [`ResponseSpec`]. Carried as the source of
+/// the `Error::Validation` returned by `resolve_run_interrupt`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResponseViolation {
+    /// Absent fields: `response`, `approved`, `text`, `decision`, or `data.<key>`.
+    pub missing: Vec<String>,
+    /// A decision outside `allowed_decisions`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decision: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowed_decisions: Vec<String>,
+}
+
+impl std::fmt::Display for ResponseViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.missing.is_empty() {
+            write!(f, "missing {}", self.missing.join(", "))?;
+        }
+        if let Some(decision) = &self.decision {
+            if !self.missing.is_empty() {
+                write!(f, "; ")?;
+            }
+            write!(f, "decision '{}' not in [{}]", decision, self.allowed_decisions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResponseViolation {}
+
+impl ResponseSpec {
+    /// `None` when `response` satisfies the spec.
+    pub fn check(&self, response: &InterruptResponse) -> Option<ResponseViolation> {
+        let mut missing = Vec::new();
+        let empty = response.text.is_none()
+            && response.approved.is_none()
+            && response.decision.is_none()
+            && response.data.as_ref().map_or(true, |d| d.is_empty());
+        if self.require_response && empty {
+            missing.push("response".to_string());
+        }
+        if self.require_approved && response.approved.is_none() {
+            missing.push("approved".to_string());
+        }
+        if self.require_text && response.text.as_deref().map_or(true, |t| t.trim().is_empty()) {
+            missing.push("text".to_string());
+        }
+        let mut decision = None;
+        if !self.allowed_decisions.is_empty() {
+            match response.decision.as_ref() {
+                None => missing.push("decision".to_string()),
+                Some(d) if !self.allowed_decisions.contains(d) => decision = Some(d.clone()),
+                Some(_) => {}
+            }
+        }
+        for key in &self.required_data {
+            if !response.data.as_ref().is_some_and(|d| d.contains_key(key)) {
+                missing.push(format!("data.{}", key));
+            }
+        }
+        (!missing.is_empty() || decision.is_some()).then(|| ResponseViolation {
+            missing,
+            decision,
+            allowed_decisions: self.allowed_decisions.clone(),
+        })
+    }
+}
+
+/// One hand-off of an interrupt from one user to another.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InterruptDelegation {
+    pub from: UserId,
+    pub to: UserId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
 /// Flow interrupt — pipeline pause awaiting consumer response.
 ///
 /// Self-describes via `message`, `question`, and `data`. There is no
@@ -40,12 +141,35 @@ pub struct FlowInterrupt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 
+    /// Stable message code (e.g. `interrupt.cost_confirmation`) with its
+    /// params, for consumers that render `message` in the user's language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub message_params: HashMap<String, serde_json::Value>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub data: Option<HashMap<String, serde_json::Value>>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub response: Option<InterruptResponse>,
 
+    /// What the response must contain. Unset, a question requires some
+    /// response and other interrupts accept anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_spec: Option<ResponseSpec>,
+
+    /// Hand-offs, oldest first. The last `to` owns the interrupt; without
+    /// any, the run's user does.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delegations: Vec<InterruptDelegation>,
+
+    /// Signal name that resolves this interrupt when delivered via
+    /// `KernelHandle::signal_run`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub await_signal: Option<String>,
+
     pub created_at: DateTime<Utc>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -53,13 +177,24 @@ pub struct FlowInterrupt {
 }
 
 impl FlowInterrupt {
+    /// With a UUID v4 id.
     pub fn new() -> Self {
+        Self::new_in(IdFormat::default())
+    }
+
+    /// With an id in `ids` (the kernel passes its configured format).
+    pub fn new_in(ids: IdFormat) -> Self {
         Self {
-            id: InterruptId::must(format!("int_{}", &uuid::Uuid::new_v4().simple().to_string()[..16])),
+            id: InterruptId::must(format!("int_{}", ids.generate_short())),
             question: None,
             message: None,
+            message_key: None,
+            message_params: HashMap::new(),
             data: None,
             response: None,
+            response_spec: None,
+            delegations: Vec::new(),
+            await_signal: None,
             created_at: Utc::now(),
             expires_at: None,
         }
@@ -75,11 +210,34 @@ impl FlowInterrupt {
         self
     }
 
+    pub fn with_message_key(mut self, key: impl Into<String>, params: HashMap<String, serde_json::Value>) -> Self {
+        self.message_key = Some(key.into());
+        self.message_params = params;
+        self
+    }
+
     pub fn with_data(mut self, d: HashMap<String, serde_json::Value>) -> Self {
         self.data = Some(d);
         self
     }
 
+    pub fn with_response_spec(mut self, spec: ResponseSpec) -> Self {
+        self.response_spec = Some(spec);
+        self
+    }
+
+    /// The user the interrupt was last delegated to, if any.
+    pub fn delegated_owner(&self) -> Option<&UserId> {
+        self.delegations.last().map(|d| &d.to)
+    }
+
+    pub fn with_await_signal(mut self, signal_name: impl Into<String>) -> Self {
+        self.await_signal = Some(signal_name.into());
+        self
+    }
+
+    /// Expire `duration` from now on the wall clock. Under another kernel
+    /// clock, set `expires_at` from `KernelHandle::now` instead.
     pub fn with_expiry(mut self, duration: std::time::Duration) -> Self {
         self.expires_at = Some(Utc::now() + chrono::Duration::from_std(duration).unwrap_or(chrono::TimeDelta::MAX));
         self
@@ -102,10 +260,81 @@ pub enum ProcessingStatus {
     Skipped,
 }
 
+/// Severity of a [`LoopFeedback`] entry. Ordered, so routing can compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// One entry in `metadata["loop_feedback"]`: why a stage is being run
+/// again, for the agent to act on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoopFeedback {
+    pub severity: FeedbackSeverity,
+    /// Machine-readable reason, e.g. `"output_schema"`.
+    pub code: String,
+    pub message: String,
+    pub stage: String,
+    /// `Run.iteration` when the entry was last recorded.
+    pub iteration: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+    /// How many identical consecutive entries this one stands for.
+    #[serde(default = "one")]
+    pub repeats: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// Cap on `metadata["loop_feedback"]`. Older entries are dropped and
+/// counted in `metadata["loop_feedback_dropped"]`.
+pub const MAX_LOOP_FEEDBACK: usize = 16;
+
+/// Where an entry in `Run.outputs` came from. Stamped by the kernel each
+/// time an agent's output is merged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputProvenance {
+    pub agent: String,
+    pub stage: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// `dispatch_id` the result was reported with, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispatch_id: Option<String>,
+    pub iteration: i32,
+    pub recorded_at: DateTime<Utc>,
+    /// [`OutputProvenance::fingerprint`] of the merged output.
+    pub content_hash: String,
+}
+
+impl OutputProvenance {
+    /// FNV-1a 64 over the output's JSON, as 16 hex digits. Detects later
+    /// edits; it is not a signature and proves nothing against a party
+    /// that can rewrite both the output and its stamp.
+    pub fn fingerprint(output: &HashMap<crate::types::OutputKey, serde_json::Value>) -> String {
+        // Key order must not affect the hash.
+        let sorted: std::collections::BTreeMap<&str, &serde_json::Value> =
+            output.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        let bytes = serde_json::to_vec(&sorted).unwrap_or_default();
+        let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+            (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+}
+
 /// Processing record for audit trail.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProcessingRecord {
     pub agent: String,
+    /// Agent version the registry resolved for this dispatch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
     pub stage_order: i32,
     pub started_at: DateTime<Utc>,
 
@@ -128,6 +357,14 @@ pub struct ProcessingRecord {
 
     #[serde(default)]
     pub tokens_out: i64,
+
+    /// Model the agent reported using (see `AgentExecutionMetrics.model`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Distinct tools the agent called, in first-use order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
 }
 
 /// Run identity fields.
@@ -138,6 +375,11 @@ pub struct Identity {
     pub request_id: RequestId,
     pub user_id: UserId,
     pub session_id: SessionId,
+    /// Caller's correlation ID from an upstream system (ticket, trace,
+    /// order number). Copied to `RunRecord.external_ref` and searchable
+    /// through `RunQuery.external_ref`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_ref: Option<String>,
 }
 
 
@@ -158,6 +400,21 @@ pub struct Limits {
     pub max_agent_hops: i32,
 }
 
+/// Pipeline bounds a run starts with when its `Workflow` leaves them at 0.
+/// `Kernel::from_config` takes them from `Config.defaults`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoundsDefaults {
+    pub max_iterations: i32,
+    pub max_llm_calls: i32,
+    pub max_agent_hops: i32,
+}
+
+impl Default for BoundsDefaults {
+    fn default() -> Self {
+        Self { max_iterations: 100, max_llm_calls: 100, max_agent_hops: 100 }
+    }
+}
+
 /// Live execution counters, incremented as the run progresses. Bounds checking
 /// compares these against `Limits`.
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
@@ -167,6 +424,12 @@ pub struct Metrics {
     pub agent_hops: i32,
     pub tokens_in: i64,
     pub tokens_out: i64,
+    /// `AppendDedup` entries stored as `$same_as` markers.
+    #[serde(default)]
+    pub outputs_deduplicated: i32,
+    /// Serialized bytes those markers stand in for.
+    #[serde(default)]
+    pub dedup_bytes_saved: i64,
 }
 
 /// Human-in-the-loop interrupt state.
@@ -174,6 +437,26 @@ pub struct Metrics {
 pub struct InterruptState {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub interrupt: Option<FlowInterrupt>,
+
+    /// Every interrupt raised on the run, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<InterruptRecord>,
+}
+
+/// An interrupt as it appears in `InterruptState.history`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InterruptRecord {
+    pub id: InterruptId,
+    /// `question`, else `message`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub raised_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responder: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delegations: Vec<InterruptDelegation>,
 }
 
 impl InterruptState {
@@ -192,4 +475,9 @@ pub struct Audit {
     pub completed_at: Option<DateTime<Utc>>,
 
     pub metadata: HashMap<String, serde_json::Value>,
+
+    /// Write count per field touched by `merge_updates` / `apply_updates`:
+    /// `raw_input`, `metadata.<key>`, `outputs.<agent>`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub field_revisions: HashMap<String, u64>,
 }
@@ -0,0 +1,186 @@
+//! Strict partial updates.
+//!
+//! `Run::merge_updates` accepts any key and skips malformed values.
+//! `Run::apply_updates` checks an update against the caller's
+//! [`UpdatePolicy`] (if any) and the revisions the caller last read, and
+//! applies it only if every key is allowed, every value well-formed and no
+//! field has been written since.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use super::Run;
+use crate::types::{AgentName, Error, OutputKey, Result};
+
+/// Top-level keys one caller type may send.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdatePolicy {
+    /// `raw_input`, `metadata`, `outputs`, or a catch-all metadata key.
+    pub allowed_keys: BTreeSet<String>,
+}
+
+/// A field written since the caller read it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldConflict {
+    pub field: String,
+    pub base_revision: u64,
+    pub current_revision: u64,
+}
+
+/// Why `apply_updates` refused an update. Carried as the source of the
+/// `Error::Validation` it returns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UpdateRejection {
+    /// Keys outside the caller's `allowed_keys`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disallowed_keys: Vec<String>,
+    /// Keys whose value has the wrong shape (e.g. a non-string `raw_input`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub malformed_keys: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<FieldConflict>,
+}
+
+impl UpdateRejection {
+    fn is_empty(&self) -> bool {
+        self.disallowed_keys.is_empty() && self.malformed_keys.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+impl std::fmt::Display for UpdateRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if !self.disallowed_keys.is_empty() {
+            parts.push(format!("disallowed keys {}", self.disallowed_keys.join(", ")));
+        }
+        if !self.malformed_keys.is_empty() {
+            parts.push(format!("malformed keys {}", self.malformed_keys.join(", ")));
+        }
+        for c in &self.conflicts {
+            parts.push(format!("{} changed (revision {} > {})", c.field, c.current_revision, c.base_revision));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+impl std::error::Error for UpdateRejection {}
+
+/// Fields `key: value` would write, or `None` if the value is malformed.
+fn touched_fields(key: &str, value: &serde_json::Value) -> Option<Vec<String>> {
+    match key {
+        "raw_input" => value.is_string().then(|| vec!["raw_input".to_string()]),
+        "metadata" => value.as_object().map(|map| map.keys().map(|k| format!("metadata.{}", k)).collect()),
+        "outputs" => serde_json::from_value::<HashMap<AgentName, HashMap<OutputKey, serde_json::Value>>>(value.clone())
+            .ok()
+            .map(|map| map.keys().map(|agent| format!("outputs.{}", agent)).collect()),
+        _ => Some(vec![format!("metadata.{}", key)]),
+    }
+}
+
+impl Run {
+    /// Current write count of `field` (see `Audit.field_revisions`).
+    pub fn field_revision(&self, field: &str) -> u64 {
+        self.audit.field_revisions.get(field).copied().unwrap_or(0)
+    }
+
+    /// Apply `updates` all-or-nothing. Without a `policy` every key is
+    /// allowed, but values are still checked. Fields listed in
+    /// `base_revisions` must still be at that revision; unlisted fields are
+    /// written unconditionally. Returns the new revision of each field
+    /// written.
+    pub fn apply_updates(
+        &mut self,
+        updates: HashMap<String, serde_json::Value>,
+        policy: Option<&UpdatePolicy>,
+        base_revisions: &HashMap<String, u64>,
+    ) -> Result<HashMap<String, u64>> {
+        let fields = self.check_updates(&updates, policy, base_revisions)?;
+        self.merge_updates(updates);
+        Ok(fields.into_iter().map(|f| {
+            let revision = self.field_revision(&f);
+            (f, revision)
+        }).collect())
+    }
+
+    /// The checks of [`Run::apply_updates`], writing nothing. Returns the
+    /// fields the update would write; empty when it writes nothing.
+    pub fn check_updates(
+        &self,
+        updates: &HashMap<String, serde_json::Value>,
+        policy: Option<&UpdatePolicy>,
+        base_revisions: &HashMap<String, u64>,
+    ) -> Result<Vec<String>> {
+        let mut rejection = UpdateRejection::default();
+        let mut fields = Vec::new();
+        for (key, value) in updates {
+            if policy.is_some_and(|p| !p.allowed_keys.contains(key)) {
+                rejection.disallowed_keys.push(key.clone());
+                continue;
+            }
+            match touched_fields(key, value) {
+                Some(touched) => fields.extend(touched),
+                None => rejection.malformed_keys.push(key.clone()),
+            }
+        }
+        for field in &fields {
+            let current = self.field_revision(field);
+            if let Some(&base) = base_revisions.get(field) {
+                if current > base {
+                    rejection.conflicts.push(FieldConflict { field: field.clone(), base_revision: base, current_revision: current });
+                }
+            }
+        }
+        if !rejection.is_empty() {
+            rejection.disallowed_keys.sort();
+            rejection.malformed_keys.sort();
+            rejection.conflicts.sort_by(|a, b| a.field.cmp(&b.field));
+            return Err(Error::validation_with_source(format!("Update rejected: {}", rejection), rejection));
+        }
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::error::Error as _;
+
+    fn policy(keys: &[&str]) -> UpdatePolicy {
+        UpdatePolicy { allowed_keys: keys.iter().map(|k| k.to_string()).collect() }
+    }
+
+    fn rejection(err: &Error) -> &UpdateRejection {
+        err.source().and_then(|s| s.downcast_ref::<UpdateRejection>()).unwrap()
+    }
+
+    #[test]
+    fn disallowed_and_malformed_keys_are_rejected_whole() {
+        let mut run = Run::anonymous();
+        let updates = HashMap::from([
+            ("raw_input".to_string(), json!(42)),
+            ("stage".to_string(), json!("x")),
+            ("metadata".to_string(), json!({"ticket": "T-1"})),
+        ]);
+        let err = run.apply_updates(updates, Some(&policy(&["raw_input", "metadata"])), &HashMap::new()).unwrap_err();
+        let rejection = rejection(&err);
+        assert_eq!(rejection.disallowed_keys, vec!["stage".to_string()]);
+        assert_eq!(rejection.malformed_keys, vec!["raw_input".to_string()]);
+        assert!(!run.audit.metadata.contains_key("ticket"));
+    }
+
+    #[test]
+    fn stale_base_revision_conflicts() {
+        let mut run = Run::anonymous();
+        let allowed = policy(&["metadata"]);
+        let patch = || HashMap::from([("metadata".to_string(), json!({"owner": "a"}))]);
+        let revisions = run.apply_updates(patch(), Some(&allowed), &HashMap::new()).unwrap();
+        assert_eq!(revisions.get("metadata.owner"), Some(&1));
+
+        // Someone else writes the field through the lenient path.
+        run.merge_updates(HashMap::from([("owner".to_string(), json!("b"))]));
+        let err = run.apply_updates(patch(), Some(&allowed), &revisions).unwrap_err();
+        assert_eq!(rejection(&err).conflicts, vec![FieldConflict { field: "metadata.owner".into(), base_revision: 1, current_revision: 2 }]);
+        assert_eq!(run.audit.metadata["owner"], json!("b"));
+    }
+}
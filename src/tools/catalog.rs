@@ -136,6 +136,19 @@ impl ParamType {
         }
     }
 
+    /// JSON Schema fragment for function-calling tool definitions.
+    pub fn to_json_schema(&self) -> Value {
+        match self {
+            ParamType::String => serde_json::json!({"type": "string"}),
+            ParamType::Int => serde_json::json!({"type": "integer"}),
+            ParamType::Float => serde_json::json!({"type": "number"}),
+            ParamType::Bool => serde_json::json!({"type": "boolean"}),
+            ParamType::StringList => serde_json::json!({"type": "array", "items": {"type": "string"}}),
+            ParamType::Enum(variants) => serde_json::json!({"type": "string", "enum": variants}),
+            ParamType::Optional(inner) => inner.to_json_schema(),
+        }
+    }
+
     /// Human-readable type name for prompt generation.
     pub fn display_name(&self) -> String {
         match self {
@@ -204,6 +217,38 @@ impl ToolEntry {
 
         format!("- {}({}): {}", self.id, params.join(", "), self.description)
     }
+
+    /// OpenAI-compatible tool definition:
+    /// `{"type": "function", "function": {name, description, parameters}}`.
+    pub fn to_openai_function(&self) -> Value {
+        let mut properties = serde_json::Map::new();
+        let mut required = Vec::new();
+        for param in &self.parameters {
+            let mut schema = param.param_type.to_json_schema();
+            if let Some(obj) = schema.as_object_mut() {
+                obj.insert("description".to_string(), Value::String(param.description.clone()));
+                if let Some(default) = &param.default {
+                    obj.insert("default".to_string(), default.clone());
+                }
+            }
+            properties.insert(param.name.clone(), schema);
+            if param.is_required() {
+                required.push(Value::String(param.name.clone()));
+            }
+        }
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.id,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                },
+            },
+        })
+    }
 }
 
 /// In-memory tool catalog. Owns metadata, not implementations.
@@ -344,6 +389,20 @@ impl ToolCatalog {
         lines.join("\n")
     }
 
+    /// OpenAI-compatible tool definitions for `agent_name`, sorted by id.
+    /// With a `policy`, only tools the agent is granted are included.
+    pub fn to_openai_functions(
+        &self,
+        agent_name: &str,
+        policy: Option<&super::access::ToolAccessPolicy>,
+    ) -> Vec<Value> {
+        self.list_entries()
+            .into_iter()
+            .filter(|e| policy.map_or(true, |p| p.check_access(agent_name, &e.id)))
+            .map(ToolEntry::to_openai_function)
+            .collect()
+    }
+
     /// Number of registered tools.
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -395,6 +454,30 @@ mod tests {
         assert_eq!(entry.description, "Search the web for information");
     }
 
+    #[test]
+    fn test_to_openai_functions_filtered_by_policy() {
+        let mut catalog = ToolCatalog::new();
+        catalog.register(sample_entry()).unwrap();
+        let mut other = sample_entry();
+        other.id = "delete_file".to_string();
+        catalog.register(other).unwrap();
+
+        let mut policy = crate::tools::ToolAccessPolicy::new();
+        policy.grant("researcher", "search_web");
+
+        let all = catalog.to_openai_functions("researcher", None);
+        assert_eq!(all.len(), 2);
+
+        let granted = catalog.to_openai_functions("researcher", Some(&policy));
+        assert_eq!(granted.len(), 1);
+        let function = &granted[0]["function"];
+        assert_eq!(granted[0]["type"], "function");
+        assert_eq!(function["name"], "search_web");
+        assert_eq!(function["parameters"]["required"], serde_json::json!(["query"]));
+        assert_eq!(function["parameters"]["properties"]["max_results"]["type"], "integer");
+        assert_eq!(function["parameters"]["properties"]["max_results"]["default"], 10);
+    }
+
     #[test]
     fn test_register_empty_id_fails() {
         let mut catalog = ToolCatalog::new();
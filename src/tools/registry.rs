@@ -122,6 +122,15 @@ impl ToolRegistry {
         self.executors.get(name)?.requires_confirmation(name, params)
     }
 
+    /// OpenAI-compatible definitions of the catalog tools `agent_name` may
+    /// call under this registry's access policy. Empty without a catalog.
+    pub fn openai_functions_for(&self, agent_name: &str) -> Vec<serde_json::Value> {
+        self.catalog
+            .as_ref()
+            .map(|c| c.to_openai_functions(agent_name, self.access_policy.as_deref()))
+            .unwrap_or_default()
+    }
+
     pub fn access_policy(&self) -> Option<&Arc<ToolAccessPolicy>> {
         self.access_policy.as_ref()
     }
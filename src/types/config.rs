@@ -19,6 +19,46 @@ pub struct Config {
     /// Default resource limits.
     #[serde(default)]
     pub defaults: DefaultLimits,
+
+    /// `raw_input` preprocessing applied to every run.
+    #[serde(default)]
+    pub input: crate::kernel::InputPolicy,
+
+    /// Model prices and confirmation threshold for dispatch cost estimates.
+    #[serde(default)]
+    pub cost: crate::kernel::CostPolicy,
+
+    /// Automatic agent quarantine by failure rate.
+    #[serde(default)]
+    pub quarantine: crate::kernel::QuarantinePolicy,
+
+    /// Caps on pending interrupts, per kernel and per session.
+    #[serde(default)]
+    pub interrupts: crate::kernel::InterruptLimits,
+
+    /// Keys each caller type may send to `update_run`, by caller type.
+    #[serde(default)]
+    pub update_policies: std::collections::HashMap<String, crate::run::UpdatePolicy>,
+
+    /// Retention of terminated-run records.
+    #[serde(default)]
+    pub terminal_log: crate::kernel::TerminalLogConfig,
+
+    /// Cap on failed interrupt-resolution attempts.
+    #[serde(default)]
+    pub resolution_limits: crate::kernel::ResolutionLimits,
+
+    /// Cleanup passes `run_maintenance` applies when the host calls it.
+    #[serde(default)]
+    pub maintenance: crate::kernel::MaintenancePolicy,
+
+    /// Agents the deployment runs, checked against workflow stages.
+    #[serde(default)]
+    pub agents: crate::kernel::AgentBindings,
+
+    /// Format of generated run, request and interrupt IDs.
+    #[serde(default)]
+    pub id_format: super::IdFormat,
 }
 
 /// Server configuration.
@@ -81,6 +121,10 @@ pub struct DefaultLimits {
     /// Default process timeout.
     #[serde(with = "humantime_serde")]
     pub process_timeout: Duration,
+
+    /// Cap on live runs; new runs beyond it are shed. `None` = no cap.
+    #[serde(default)]
+    pub max_active_runs: Option<usize>,
 }
 
 impl Default for DefaultLimits {
@@ -91,6 +135,7 @@ impl Default for DefaultLimits {
             max_agent_hops: 10,
             max_iterations: 20,
             process_timeout: Duration::from_secs(300),
+            max_active_runs: None,
         }
     }
 }
@@ -154,6 +199,15 @@ impl Config {
         if let Ok(v) = std::env::var("CORE_MAX_AGENT_HOPS") {
             if let Ok(n) = v.parse() { config.defaults.max_agent_hops = n; }
         }
+        if let Ok(v) = std::env::var("CORE_ID_FORMAT") {
+            match serde_json::from_value(serde_json::Value::String(v.clone())) {
+                Ok(f) => config.id_format = f,
+                Err(e) => tracing::warn!(value = %v, error = %e, fallback = ?config.id_format, "invalid CORE_ID_FORMAT, using fallback"),
+            }
+        }
+        if let Ok(v) = std::env::var("CORE_MAX_ACTIVE_RUNS") {
+            if let Ok(n) = v.parse() { config.defaults.max_active_runs = Some(n); }
+        }
         config
     }
 }
@@ -48,6 +48,10 @@ pub enum Error {
     #[error("timeout: {0}")]
     Timeout(String),
 
+    /// Compare-and-swap failure: the target changed since the caller read it.
+    #[error("conflict: {message}")]
+    Conflict { message: String, current_revision: u64 },
+
     /// Policy violation (e.g., ACL rejection).
     #[error("policy violation: {0}")]
     PolicyViolation(String),
@@ -71,6 +75,7 @@ impl Error {
             Error::StateTransition(_) => "FAILED_PRECONDITION",
             Error::Cancelled(_) => "CANCELLED",
             Error::Timeout(_) => "TIMEOUT",
+            Error::Conflict { .. } => "ABORTED",
             Error::PolicyViolation(_) => "PERMISSION_DENIED",
             Error::Internal { .. } | Error::Serialization(_) | Error::Io(_) => "INTERNAL",
         }
@@ -113,6 +118,10 @@ impl Error {
         Self::Internal { message: msg.into(), source: Some(Box::new(source)) }
     }
 
+    pub fn conflict(msg: impl Into<String>, current_revision: u64) -> Self {
+        Self::Conflict { message: msg.into(), current_revision }
+    }
+
     pub fn cancelled(msg: impl Into<String>) -> Self {
         Self::Cancelled(msg.into())
     }
@@ -1,7 +1,8 @@
 //! Strongly-typed identifiers. All IDs validate at construction.
 //!
 //! Two macro forms:
-//! - `define_id!(Name, uuid)` — also provides `new()` (UUID v4) and `Default`.
+//! - `define_id!(Name, uuid)` — also provides `new()` (UUID v4),
+//!   `generate(format)` (in a given [`IdFormat`]) and `Default`.
 //! - `define_id!(Name)` — no auto-generated form; must always be constructed
 //!   from a non-empty string via `must()` or `from_string()`.
 //!
@@ -12,6 +13,71 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Shape of generated identifiers. Time-ordered formats (UUID v7, ULID)
+/// sort by creation time, which some stores and log pipelines rely on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IdFormat {
+    #[default]
+    UuidV4,
+    UuidV7,
+    Ulid,
+}
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+impl IdFormat {
+    /// A fresh identifier: hyphenated UUID or 26-character ULID.
+    pub fn generate(self) -> String {
+        match self {
+            Self::UuidV4 => uuid::Uuid::new_v4().to_string(),
+            Self::UuidV7 => uuid_v7().to_string(),
+            Self::Ulid => ulid(),
+        }
+    }
+
+    /// Compact form for prefixed IDs (`env_…`, `req_…`, `int_…`). UUID v4
+    /// keeps its historical 16 hex digits; the time-ordered formats stay
+    /// whole, since their leading digits are the timestamp.
+    pub fn generate_short(self) -> String {
+        match self {
+            Self::UuidV4 => uuid::Uuid::new_v4().simple().to_string()[..16].to_string(),
+            Self::UuidV7 => uuid_v7().simple().to_string(),
+            Self::Ulid => ulid(),
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// 48-bit millisecond timestamp, then random bits (RFC 9562 §5.7). The
+/// random bits come from a v4 UUID, so no extra crate features are needed.
+fn uuid_v7() -> uuid::Uuid {
+    let mut bytes = uuid::Uuid::new_v4().into_bytes();
+    bytes[..6].copy_from_slice(&unix_millis().to_be_bytes()[2..]);
+    bytes[6] = 0x70 | (bytes[6] & 0x0F);
+    bytes[8] = 0x80 | (bytes[8] & 0x3F);
+    uuid::Uuid::from_bytes(bytes)
+}
+
+/// 48-bit millisecond timestamp plus 80 random bits, Crockford base32.
+fn ulid() -> String {
+    let random = uuid::Uuid::new_v4().into_bytes();
+    // Bytes 6 and 8 of a v4 UUID carry version/variant bits; skip them.
+    let mut value = u128::from(unix_millis() & 0xFFFF_FFFF_FFFF);
+    for byte in random[..4].iter().chain(&random[10..]) {
+        value = (value << 8) | u128::from(*byte);
+    }
+    (0..26)
+        .map(|i| char::from(CROCKFORD[((value >> (5 * (25 - i))) & 31) as usize]))
+        .collect()
+}
 
 macro_rules! define_id {
     ($name:ident, uuid) => {
@@ -21,7 +87,11 @@ macro_rules! define_id {
 
         impl $name {
             pub fn new() -> Self {
-                Self(uuid::Uuid::new_v4().to_string())
+                Self::generate(IdFormat::UuidV4)
+            }
+
+            pub fn generate(format: IdFormat) -> Self {
+                Self(format.generate())
             }
 
             pub fn from_string(s: String) -> Result<Self, &'static str> {
@@ -214,6 +284,23 @@ mod tests {
         assert_ne!(a, b);
     }
 
+    #[test]
+    fn time_ordered_formats() {
+        let v7 = IdFormat::UuidV7.generate();
+        let parsed = uuid::Uuid::parse_str(&v7).unwrap();
+        assert_eq!(parsed.get_version_num(), 7);
+
+        let ulid = IdFormat::Ulid.generate();
+        assert_eq!(ulid.len(), 26);
+        assert!(ulid.bytes().all(|b| CROCKFORD.contains(&b)));
+        // Leading ten characters are the millisecond timestamp.
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(IdFormat::Ulid.generate()[..10] > ulid[..10]);
+
+        assert_eq!(IdFormat::UuidV4.generate_short().len(), 16);
+        assert_eq!(IdFormat::UuidV7.generate_short().len(), 32);
+    }
+
     #[test]
     fn as_ref_str_works() {
         let n = RoutingFnName::must("router");
@@ -12,6 +12,6 @@ mod ids;
 pub use config::{AgentDefinition, Config, ObservabilityConfig};
 pub use errors::{Error, Result};
 pub use ids::{
-    AgentName, EnvelopeId, InterruptId, OutputKey, PromptKey, RequestId, RoutingFnName, RunId,
-    SessionId, StageName, ToolName, UserId,
+    AgentName, EnvelopeId, IdFormat, InterruptId, OutputKey, PromptKey, RequestId, RoutingFnName,
+    RunId, SessionId, StageName, ToolName, UserId,
 };
@@ -0,0 +1,356 @@
+//! Fluent construction of a `Workflow` from Rust.
+//!
+//! ```text
+//! let built = PipelineBuilder::new("review")
+//!     .stage("verify").agent("verifier")
+//!         .route_if(eq("outputs.verifier.verdict", "fail"), "fix")
+//!         .default_next("report")
+//!     .stage("fix").agent("fixer").default_next("verify").max_visits(3)
+//!     .stage("report").agent("reporter")
+//!     .build()?;
+//! built.register(&handle).await?;
+//! ```
+//!
+//! Each stage method applies to the stage most recently opened with
+//! `stage()`. `route_if` conditions are compiled into a routing function,
+//! so routing stays code rather than data: the rules are tried in order,
+//! the first match wins, and otherwise the stage goes to `default_next` (or
+//! terminates). `build()` returns the workflow together with those
+//! functions, which must be registered on the kernel before runs start.
+
+use std::sync::Arc;
+
+use super::{MergeStrategy, Stage, StateField, Workflow};
+use crate::kernel::field_mask::lookup;
+use crate::kernel::handle::KernelHandle;
+use crate::kernel::routing::{RoutingContext, RoutingFn, RoutingResult};
+use crate::types::{AgentName, Error, Result, RoutingFnName, StageName};
+
+/// Predicate over a [`RoutingContext`]. Paths are dotted and start with
+/// `outputs.<agent>`, `state` or `metadata`, as in stage `inputs`.
+#[derive(Clone)]
+pub struct Condition(Arc<dyn Fn(&RoutingContext<'_>) -> bool + Send + Sync>);
+
+impl Condition {
+    pub fn new(f: impl Fn(&RoutingContext<'_>) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn matches(&self, ctx: &RoutingContext<'_>) -> bool {
+        (self.0)(ctx)
+    }
+
+    pub fn and(self, other: Condition) -> Self {
+        Self::new(move |ctx| self.matches(ctx) && other.matches(ctx))
+    }
+
+    pub fn or(self, other: Condition) -> Self {
+        Self::new(move |ctx| self.matches(ctx) || other.matches(ctx))
+    }
+
+    pub fn negate(self) -> Self {
+        Self::new(move |ctx| !self.matches(ctx))
+    }
+}
+
+impl std::fmt::Debug for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Condition")
+    }
+}
+
+/// Value at `path` in the routing context.
+pub fn resolve(ctx: &RoutingContext<'_>, path: &str) -> Option<serde_json::Value> {
+    let mut segments = path.splitn(2, '.');
+    let head = segments.next()?;
+    let rest = segments.next();
+    match head {
+        "outputs" => {
+            let rest = rest?;
+            let (agent, rest) = rest.split_once('.').map_or((rest, None), |(a, r)| (a, Some(r)));
+            let output = ctx.outputs.get(agent)?;
+            let value = serde_json::Value::Object(
+                output.iter().map(|(k, v)| (k.as_str().to_string(), v.clone())).collect(),
+            );
+            match rest {
+                Some(path) => lookup(&value, path).cloned(),
+                None => Some(value),
+            }
+        }
+        "state" | "metadata" => {
+            let map = if head == "state" { ctx.state } else { ctx.metadata };
+            let rest = rest?;
+            let (key, rest) = rest.split_once('.').map_or((rest, None), |(k, r)| (k, Some(r)));
+            let value = map.get(key)?;
+            match rest {
+                Some(path) => lookup(value, path).cloned(),
+                None => Some(value.clone()),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `path` equals `value`.
+pub fn eq(path: impl Into<String>, value: impl Into<serde_json::Value>) -> Condition {
+    let (path, value) = (path.into(), value.into());
+    Condition::new(move |ctx| resolve(ctx, &path).as_ref() == Some(&value))
+}
+
+/// `path` is missing or differs from `value`.
+pub fn ne(path: impl Into<String>, value: impl Into<serde_json::Value>) -> Condition {
+    eq(path, value).negate()
+}
+
+/// `path` is a number greater than `bound`.
+pub fn gt(path: impl Into<String>, bound: f64) -> Condition {
+    let path = path.into();
+    Condition::new(move |ctx| resolve(ctx, &path).and_then(|v| v.as_f64()).is_some_and(|n| n > bound))
+}
+
+/// `path` is a number less than `bound`.
+pub fn lt(path: impl Into<String>, bound: f64) -> Condition {
+    let path = path.into();
+    Condition::new(move |ctx| resolve(ctx, &path).and_then(|v| v.as_f64()).is_some_and(|n| n < bound))
+}
+
+/// `path` is present and not null.
+pub fn exists(path: impl Into<String>) -> Condition {
+    let path = path.into();
+    Condition::new(move |ctx| resolve(ctx, &path).is_some_and(|v| !v.is_null()))
+}
+
+/// The stage's agent reported failure (and the stage has no `error_next`).
+pub fn failed() -> Condition {
+    Condition::new(|ctx| ctx.agent_failed)
+}
+
+/// A workflow plus the routing functions its `route_if` rules compiled to.
+pub struct BuiltPipeline {
+    pub workflow: Workflow,
+    pub routing_fns: Vec<(RoutingFnName, Arc<dyn RoutingFn>)>,
+}
+
+impl std::fmt::Debug for BuiltPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.routing_fns.iter().map(|(name, _)| name).collect();
+        f.debug_struct("BuiltPipeline")
+            .field("workflow", &self.workflow)
+            .field("routing_fns", &names)
+            .finish()
+    }
+}
+
+impl BuiltPipeline {
+    /// Register the routing functions on the kernel behind `handle` and
+    /// return the workflow.
+    pub async fn register(self, handle: &KernelHandle) -> Result<Workflow> {
+        for (name, routing_fn) in self.routing_fns {
+            handle.register_routing_fn(name.as_str(), routing_fn).await?;
+        }
+        Ok(self.workflow)
+    }
+}
+
+#[derive(Debug)]
+struct PendingStage {
+    stage: Stage,
+    rules: Vec<(Condition, String)>,
+}
+
+#[must_use]
+#[derive(Debug)]
+pub struct PipelineBuilder {
+    workflow: Workflow,
+    stages: Vec<PendingStage>,
+}
+
+impl PipelineBuilder {
+    /// Start a workflow bounded to 10 iterations, 50 LLM calls and 10
+    /// agent hops.
+    pub fn new(name: impl Into<String>) -> Self {
+        let workflow = Workflow {
+            name: name.into(),
+            stages: Vec::new(),
+            max_iterations: 10,
+            max_llm_calls: 50,
+            max_agent_hops: 10,
+            state_schema: Vec::new(),
+            execution_windows: Vec::new(),
+        };
+        Self { workflow, stages: Vec::new() }
+    }
+
+    pub fn max_iterations(mut self, n: i32) -> Self {
+        self.workflow.max_iterations = n;
+        self
+    }
+
+    pub fn max_llm_calls(mut self, n: i32) -> Self {
+        self.workflow.max_llm_calls = n;
+        self
+    }
+
+    pub fn max_agent_hops(mut self, n: i32) -> Self {
+        self.workflow.max_agent_hops = n;
+        self
+    }
+
+    pub fn state_field(mut self, key: impl Into<String>, merge: MergeStrategy) -> Self {
+        self.workflow.state_schema.push(StateField { key: key.into(), merge });
+        self
+    }
+
+    /// Open a stage. Its agent defaults to the stage name.
+    pub fn stage(mut self, name: &str) -> Self {
+        // Empty names are left for `build()` to reject rather than panicking here.
+        let stage = Stage {
+            name: StageName::from_string(name.to_string()).unwrap_or_default(),
+            agent: AgentName::from_string(name.to_string()).unwrap_or_default(),
+            ..Default::default()
+        };
+        self.stages.push(PendingStage {
+            stage,
+            rules: Vec::new(),
+        });
+        self
+    }
+
+    pub fn agent(self, agent: &str) -> Self {
+        self.with_stage(|s| s.agent = AgentName::from_string(agent.to_string()).unwrap_or_default())
+    }
+
+    pub fn default_next(self, target: &str) -> Self {
+        self.with_stage(|s| s.default_next = Some(StageName::from_string(target.to_string()).unwrap_or_default()))
+    }
+
+    pub fn error_next(self, target: &str) -> Self {
+        self.with_stage(|s| s.error_next = Some(StageName::from_string(target.to_string()).unwrap_or_default()))
+    }
+
+    pub fn max_visits(self, n: i32) -> Self {
+        self.with_stage(|s| s.max_visits = Some(n))
+    }
+
+    pub fn has_llm(self, has_llm: bool) -> Self {
+        self.with_stage(|s| s.agent_config.has_llm = has_llm)
+    }
+
+    /// Route to `target` when `condition` holds. Rules are tried in the
+    /// order they were added.
+    pub fn route_if(mut self, condition: Condition, target: &str) -> Self {
+        if let Some(pending) = self.stages.last_mut() {
+            pending.rules.push((condition, target.to_string()));
+        }
+        self
+    }
+
+    /// Set anything else on the current stage.
+    pub fn with_stage(mut self, f: impl FnOnce(&mut Stage)) -> Self {
+        if let Some(pending) = self.stages.last_mut() {
+            f(&mut pending.stage);
+        }
+        self
+    }
+
+    /// Assemble and validate the workflow. Fails on unknown `route_if`
+    /// targets or anything `Workflow::validate` rejects. Stage methods
+    /// called before the first `stage()` have no effect.
+    pub fn build(self) -> Result<BuiltPipeline> {
+        let Self { mut workflow, stages } = self;
+        if stages.is_empty() {
+            return Err(Error::validation("Pipeline must have at least one stage"));
+        }
+        let names: Vec<String> = stages.iter().map(|p| p.stage.name.as_str().to_string()).collect();
+        let mut routing_fns: Vec<(RoutingFnName, Arc<dyn RoutingFn>)> = Vec::new();
+        for PendingStage { mut stage, rules } in stages {
+            if !rules.is_empty() {
+                if let Some((_, target)) = rules.iter().find(|(_, t)| !names.contains(t)) {
+                    return Err(Error::validation(format!(
+                        "Stage '{}' has route_if target '{}' which does not exist in workflow",
+                        stage.name, target
+                    )));
+                }
+                if stage.routing_fn.is_some() {
+                    return Err(Error::validation(format!(
+                        "Stage '{}' has both route_if rules and a routing_fn",
+                        stage.name
+                    )));
+                }
+                let name = RoutingFnName::must(format!("{}.{}.route_if", workflow.name, stage.name));
+                let fallback = stage.default_next.as_ref().map(|s| s.as_str().to_string());
+                let routing_fn = move |ctx: &RoutingContext<'_>| -> RoutingResult {
+                    rules
+                        .iter()
+                        .find(|(condition, _)| condition.matches(ctx))
+                        .map(|(_, target)| target.clone())
+                        .or_else(|| fallback.clone())
+                        .map_or(RoutingResult::Terminate, RoutingResult::Next)
+                };
+                stage.routing_fn = Some(name.clone());
+                routing_fns.push((name, Arc::new(routing_fn)));
+            }
+            workflow.stages.push(stage);
+        }
+        workflow.validate()?;
+        Ok(BuiltPipeline { workflow, routing_fns })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::protocol::Instruction;
+    use crate::kernel::test_helpers;
+    use crate::kernel::Kernel;
+    use crate::types::RunId;
+
+    fn review_pipeline() -> BuiltPipeline {
+        PipelineBuilder::new("review")
+            .stage("verify").agent("verifier")
+                .route_if(eq("outputs.verifier.verdict", "fail"), "fix")
+                .default_next("report")
+            .stage("fix").agent("fixer").default_next("verify").max_visits(3)
+            .stage("report").agent("reporter")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn build_sets_stages_and_routing_fn() {
+        let built = review_pipeline();
+        let names: Vec<_> = built.workflow.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["verify", "fix", "report"]);
+        assert_eq!(built.workflow.stages[0].routing_fn.as_ref().map(|n| n.as_str()), Some("review.verify.route_if"));
+        assert_eq!(built.routing_fns.len(), 1);
+    }
+
+    #[test]
+    fn unknown_route_if_target_is_rejected() {
+        let err = PipelineBuilder::new("bad")
+            .stage("a").route_if(exists("state.x"), "nowhere")
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("route_if target 'nowhere'"));
+        assert!(PipelineBuilder::new("empty").build().is_err());
+    }
+
+    #[test]
+    fn route_if_rules_drive_the_kernel() {
+        let built = review_pipeline();
+        let mut kernel = Kernel::new();
+        for (name, routing_fn) in built.routing_fns {
+            kernel.register_routing_fn(name, routing_fn);
+        }
+        let run_id = RunId::must("builder");
+        let _ = kernel.initialize_orchestration(run_id.clone(), built.workflow, test_helpers::create_test_run(), false).unwrap();
+
+        let _ = kernel.get_next_instruction(&run_id).unwrap();
+        kernel.process_agent_result(&run_id, "verifier", serde_json::json!({"verdict": "fail"}), None, Default::default(), true, "", false, None).unwrap();
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::RunAgent { agent, .. } if agent == "fixer"));
+        kernel.process_agent_result(&run_id, "fixer", serde_json::json!({}), None, Default::default(), true, "", false, None).unwrap();
+        let _ = kernel.get_next_instruction(&run_id).unwrap();
+        kernel.process_agent_result(&run_id, "verifier", serde_json::json!({"verdict": "pass"}), None, Default::default(), true, "", false, None).unwrap();
+        assert!(matches!(kernel.get_next_instruction(&run_id).unwrap(), Instruction::RunAgent { agent, .. } if agent == "reporter"));
+    }
+}
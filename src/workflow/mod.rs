@@ -6,13 +6,18 @@
 //! pipelines, and self-routing agent harnesses all share this shape — the
 //! difference is purely in how stages route to each other.
 
+pub mod builder;
+pub mod output_schema;
 pub mod policy;
 pub mod stage;
 pub mod state_schema;
+pub mod window;
 
+pub use builder::{BuiltPipeline, Condition, PipelineBuilder};
 pub use policy::RetryPolicy;
 pub use stage::{AgentConfig, Stage};
 pub use state_schema::{MergeStrategy, StateField};
+pub use window::ExecutionWindow;
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -29,12 +34,20 @@ pub struct Workflow {
     pub name: String,
     /// First stage is the entry point.
     pub stages: Vec<Stage>,
+    /// Global bounds; 0 (or omitted) takes the kernel's `BoundsDefaults`.
+    #[serde(default)]
     pub max_iterations: i32,
+    #[serde(default)]
     pub max_llm_calls: i32,
+    #[serde(default)]
     pub max_agent_hops: i32,
     /// Merge strategies for state accumulation across loop-backs.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub state_schema: Vec<StateField>,
+    /// Recurring time windows in which agents may be dispatched. Outside
+    /// all of them the run waits (`Instruction::WaitWindow`). Empty = always.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub execution_windows: Vec<ExecutionWindow>,
 }
 
 impl Workflow {
@@ -42,29 +55,39 @@ impl Workflow {
         self.stages.iter().map(|s| s.name.as_str().into()).collect()
     }
 
+    /// Reject the workflow with the first of its [`validation_errors`](Self::validation_errors).
     pub fn validate(&self) -> Result<()> {
+        match self.validation_errors().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Every structural problem with the workflow, in check order.
+    pub fn validation_errors(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
         if self.name.is_empty() {
-            return Err(Error::validation("Pipeline name is required"));
+            errors.push(Error::validation("Pipeline name is required"));
         }
         if self.stages.is_empty() {
-            return Err(Error::validation("Pipeline must have at least one stage"));
+            errors.push(Error::validation("Pipeline must have at least one stage"));
         }
 
-        if self.max_iterations <= 0 {
-            return Err(Error::validation(format!(
-                "max_iterations must be > 0, got {}",
+        if self.max_iterations < 0 {
+            errors.push(Error::validation(format!(
+                "max_iterations must not be negative, got {}",
                 self.max_iterations
             )));
         }
-        if self.max_llm_calls <= 0 {
-            return Err(Error::validation(format!(
-                "max_llm_calls must be > 0, got {}",
+        if self.max_llm_calls < 0 {
+            errors.push(Error::validation(format!(
+                "max_llm_calls must not be negative, got {}",
                 self.max_llm_calls
             )));
         }
-        if self.max_agent_hops <= 0 {
-            return Err(Error::validation(format!(
-                "max_agent_hops must be > 0, got {}",
+        if self.max_agent_hops < 0 {
+            errors.push(Error::validation(format!(
+                "max_agent_hops must not be negative, got {}",
                 self.max_agent_hops
             )));
         }
@@ -73,17 +96,17 @@ impl Workflow {
         let mut output_keys: HashSet<&str> = HashSet::new();
         for stage in &self.stages {
             if stage.name.is_empty() {
-                return Err(Error::validation("Stage name must not be empty"));
+                errors.push(Error::validation("Stage name must not be empty"));
             }
             if !stage_names.insert(stage.name.as_str()) {
-                return Err(Error::validation(format!(
+                errors.push(Error::validation(format!(
                     "Duplicate stage name '{}'",
                     stage.name
                 )));
             }
             if let Some(ref ok) = stage.output_key {
                 if !output_keys.insert(ok.as_str()) {
-                    return Err(Error::validation(format!(
+                    errors.push(Error::validation(format!(
                         "Duplicate output_key '{}' on stage '{}'",
                         ok, stage.name
                     )));
@@ -93,7 +116,7 @@ impl Workflow {
 
         for stage in &self.stages {
             if stage.agent.is_empty() {
-                return Err(Error::validation(format!(
+                errors.push(Error::validation(format!(
                     "Stage '{}' must have a non-empty agent field",
                     stage.name
                 )));
@@ -103,7 +126,7 @@ impl Workflow {
             // infinite loop hiding behind static config.
             if let Some(ref dn) = stage.default_next {
                 if dn == &stage.name && stage.max_visits.is_none() {
-                    return Err(Error::validation(format!(
+                    errors.push(Error::validation(format!(
                         "Stage '{}' has default_next pointing to itself without max_visits (infinite loop)",
                         stage.name
                     )));
@@ -112,7 +135,7 @@ impl Workflow {
 
             if let Some(ref dn) = stage.default_next {
                 if !stage_names.contains(dn.as_str()) {
-                    return Err(Error::validation(format!(
+                    errors.push(Error::validation(format!(
                         "Stage '{}' has default_next '{}' which does not exist in workflow",
                         stage.name, dn
                     )));
@@ -121,7 +144,7 @@ impl Workflow {
 
             if let Some(ref en) = stage.error_next {
                 if !stage_names.contains(en.as_str()) {
-                    return Err(Error::validation(format!(
+                    errors.push(Error::validation(format!(
                         "Stage '{}' has error_next '{}' which does not exist in workflow",
                         stage.name, en
                     )));
@@ -130,7 +153,7 @@ impl Workflow {
 
             if let Some(mv) = stage.max_visits {
                 if mv <= 0 {
-                    return Err(Error::validation(format!(
+                    errors.push(Error::validation(format!(
                         "Stage '{}' has max_visits {} which must be positive",
                         stage.name, mv
                     )));
@@ -138,25 +161,84 @@ impl Workflow {
             }
             if let Some(mct) = stage.max_context_tokens {
                 if mct <= 0 {
-                    return Err(Error::validation(format!(
+                    errors.push(Error::validation(format!(
                         "Stage '{}' has max_context_tokens {} which must be positive",
                         stage.name, mct
                     )));
                 }
             }
+            if let Some(ref schema) = stage.output_schema {
+                if !schema.is_object() && !schema.is_boolean() {
+                    errors.push(Error::validation(format!(
+                        "Stage '{}' has output_schema which must be a JSON Schema object or boolean",
+                        stage.name
+                    )));
+                }
+                let unsupported = output_schema::unsupported_keywords(schema);
+                if !unsupported.is_empty() {
+                    errors.push(Error::validation(format!(
+                        "Stage '{}' output_schema uses keywords the kernel does not enforce: {}",
+                        stage.name, unsupported.join(", ")
+                    )));
+                }
+            }
+            if let Some((name, path)) = stage.inputs.iter().find(|(_, path)| !crate::kernel::field_mask::is_data_path(path)) {
+                errors.push(Error::validation(format!(
+                    "Stage '{}' input '{}' path '{}' must start with raw_input, outputs, state or metadata",
+                    stage.name, name, path
+                )));
+            }
+            if !stage.agent_config.model_fallbacks.is_empty() && !stage.agent_config.has_llm {
+                errors.push(Error::validation(format!(
+                    "Stage '{}' has model_fallbacks but has_llm is false",
+                    stage.name
+                )));
+            }
+            if stage.agent_config.model_fallbacks.iter().any(|m| m.trim().is_empty()) {
+                errors.push(Error::validation(format!(
+                    "Stage '{}' has an empty model_fallbacks entry",
+                    stage.name
+                )));
+            }
+            if stage.concurrency_group.as_ref().is_some_and(|g| g.trim().is_empty()) {
+                errors.push(Error::validation(format!(
+                    "Stage '{}' has an empty concurrency_group",
+                    stage.name
+                )));
+            }
+            if let Some(requirement) = &stage.agent_version {
+                if let Err(e) = semver::VersionReq::parse(requirement) {
+                    errors.push(Error::validation(format!(
+                        "Stage '{}' has an invalid agent_version '{}': {}",
+                        stage.name, requirement, e
+                    )));
+                }
+            }
+            if stage.output_schema_retries.is_some() && stage.output_schema.is_none() {
+                errors.push(Error::validation(format!(
+                    "Stage '{}' has output_schema_retries without an output_schema",
+                    stage.name
+                )));
+            }
+        }
+
+        for window in &self.execution_windows {
+            if let Err(e) = window.validate() {
+                errors.push(e);
+            }
         }
 
         let mut state_keys: HashSet<&str> = HashSet::new();
         for field in &self.state_schema {
             if !state_keys.insert(field.key.as_str()) {
-                return Err(Error::validation(format!(
+                errors.push(Error::validation(format!(
                     "Duplicate state_schema key '{}'",
                     field.key
                 )));
             }
         }
 
-        Ok(())
+        errors
     }
 
     /// Test-only minimal config constructor. Avoids field boilerplate.
@@ -169,6 +251,7 @@ impl Workflow {
             max_llm_calls: 50,
             max_agent_hops: 10,
             state_schema: vec![],
+            execution_windows: vec![],
         }
     }
 }
@@ -254,6 +337,17 @@ mod tests {
         assert!(err.to_string().contains("Duplicate output_key 'shared'"));
     }
 
+    #[test]
+    fn test_validate_unsupported_output_schema_keyword() {
+        let mut stage = minimal_stage("a");
+        stage.output_schema = Some(serde_json::json!({
+            "type": "object",
+            "properties": {"email": {"type": "string", "format": "email"}},
+        }));
+        let err = minimal_config(vec![stage]).validate().unwrap_err();
+        assert!(err.to_string().contains("/properties/email: 'format'"));
+    }
+
     #[test]
     fn test_validate_valid_pipeline() {
         let mut router = minimal_stage("router");
@@ -0,0 +1,262 @@
+//! Minimal JSON Schema validation for `Stage::output_schema`.
+//!
+//! Covers the keywords LLM output contracts actually use: `type`, `enum`,
+//! `const`, `properties`, `required`, `additionalProperties` (bool or
+//! schema), `items`, `minItems`/`maxItems`, `minLength`/`maxLength`, and
+//! `minimum`/`maximum`, plus annotations that don't constrain the value.
+//! `Workflow::validate` rejects a schema using any other keyword (see
+//! [`unsupported_keywords`]), since it would silently not be enforced.
+
+use serde_json::Value;
+
+/// Keywords [`validate`] enforces.
+pub const SUPPORTED_KEYWORDS: &[&str] = &[
+    "type", "enum", "const", "properties", "required", "additionalProperties", "items",
+    "minItems", "maxItems", "minLength", "maxLength", "minimum", "maximum",
+];
+
+/// Keywords accepted but not checked, because they never constrain a value.
+const ANNOTATION_KEYWORDS: &[&str] = &["$schema", "$id", "$comment", "title", "description", "default", "examples"];
+
+/// Keywords in `schema`, at any depth, that [`validate`] would not enforce.
+/// Each is prefixed with a JSON pointer into the schema; empty means fully
+/// supported.
+pub fn unsupported_keywords(schema: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+    collect_unsupported(schema, "", &mut found);
+    found
+}
+
+fn collect_unsupported(schema: &Value, path: &str, found: &mut Vec<String>) {
+    let Value::Object(map) = schema else { return };
+    for (key, child) in map {
+        if !SUPPORTED_KEYWORDS.contains(&key.as_str()) && !ANNOTATION_KEYWORDS.contains(&key.as_str()) {
+            found.push(format!("{}: '{}'", pointer(path), key));
+            continue;
+        }
+        match key.as_str() {
+            "properties" => {
+                for (name, property) in child.as_object().into_iter().flatten() {
+                    collect_unsupported(property, &format!("{}/properties/{}", path, name), found);
+                }
+            }
+            "additionalProperties" | "items" => collect_unsupported(child, &format!("{}/{}", path, key), found),
+            _ => {}
+        }
+    }
+}
+
+/// Validate `value` against `schema`. Returns one message per violation,
+/// each prefixed with a JSON pointer to the offending location. Empty means
+/// valid.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            errors.push(format!("{}: no value is allowed here", pointer(path)));
+            return;
+        }
+        Value::Object(map) => map,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                pointer(path),
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            errors.push(format!("{}: value is not one of the allowed enum values", pointer(path)));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: expected constant {}", pointer(path), expected));
+        }
+    }
+
+    match value {
+        Value::Object(obj) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !obj.contains_key(key) {
+                        errors.push(format!("{}: missing required property '{}'", pointer(path), key));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, child) in obj {
+                let child_path = format!("{}/{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => check(child_schema, child, &child_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => errors.push(format!(
+                            "{}: additional property '{}' is not allowed",
+                            pointer(path),
+                            key
+                        )),
+                        Some(extra) => check(extra, child, &child_path, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: expected at least {} items, got {}", pointer(path), min, items.len()));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if (items.len() as u64) > max {
+                    errors.push(format!("{}: expected at most {} items, got {}", pointer(path), max, items.len()));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}/{}", path, i), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    errors.push(format!("{}: expected at least {} characters, got {}", pointer(path), min, len));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    errors.push(format!("{}: expected at most {} characters, got {}", pointer(path), max, len));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let Some(n) = n.as_f64() else { return };
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    errors.push(format!("{}: {} is less than minimum {}", pointer(path), n, min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    errors.push(format!("{}: {} is greater than maximum {}", pointer(path), n, max));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn pointer(path: &str) -> &str {
+    if path.is_empty() { "/" } else { path }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["summary", "confidence"],
+            "additionalProperties": false,
+            "properties": {
+                "summary": {"type": "string", "minLength": 1},
+                "confidence": {"type": "number", "minimum": 0, "maximum": 1},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 3},
+                "verdict": {"enum": ["approve", "reject"]},
+            }
+        })
+    }
+
+    #[test]
+    fn valid_output_passes() {
+        let value = json!({"summary": "ok", "confidence": 0.9, "tags": ["a"], "verdict": "approve"});
+        assert!(validate(&schema(), &value).is_empty());
+    }
+
+    #[test]
+    fn violations_are_reported_with_paths() {
+        let value = json!({"confidence": 1.5, "tags": ["a", 2], "verdict": "maybe", "extra": true});
+        let errors = validate(&schema(), &value);
+
+        assert!(errors.iter().any(|e| e.contains("missing required property 'summary'")));
+        assert!(errors.iter().any(|e| e.starts_with("/confidence:")));
+        assert!(errors.iter().any(|e| e.starts_with("/tags/1: expected string")));
+        assert!(errors.iter().any(|e| e.starts_with("/verdict:")));
+        assert!(errors.iter().any(|e| e.contains("'extra' is not allowed")));
+    }
+
+    #[test]
+    fn type_mismatch_at_root() {
+        let errors = validate(&schema(), &json!("just text"));
+        assert_eq!(errors, vec!["/: expected object, got string".to_string()]);
+    }
+
+    #[test]
+    fn unsupported_keywords_are_found_at_any_depth() {
+        assert!(unsupported_keywords(&schema()).is_empty());
+        let s = json!({
+            "title": "Reply",
+            "type": "object",
+            "properties": {"email": {"type": "string", "pattern": "@"}},
+            "items": {"oneOf": []},
+        });
+        let mut found = unsupported_keywords(&s);
+        found.sort();
+        assert_eq!(found, vec!["/items: 'oneOf'".to_string(), "/properties/email: 'pattern'".to_string()]);
+    }
+
+    #[test]
+    fn integer_and_union_types() {
+        let s = json!({"type": ["integer", "null"]});
+        assert!(validate(&s, &json!(3)).is_empty());
+        assert!(validate(&s, &Value::Null).is_empty());
+        assert!(!validate(&s, &json!(3.5)).is_empty());
+    }
+}
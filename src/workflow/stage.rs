@@ -3,6 +3,7 @@
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use super::policy::RetryPolicy;
 use crate::agent::policy::ContextOverflow;
@@ -19,6 +20,11 @@ pub struct Stage {
     pub name: StageName,
     /// Agent name to dispatch.
     pub agent: AgentName,
+    /// Semver requirement on the agent version (e.g. `^2`, the `agent@^2`
+    /// pin). Resolved at dispatch to the highest matching version declared
+    /// in `Config.agents`; see `kernel::agents`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
     /// Name of a registered routing function. Called after agent completion
     /// to determine the next stage.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -36,6 +42,17 @@ pub struct Stage {
     /// generation. The kernel does not interpret it.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub response_format: Option<serde_json::Value>,
+    /// JSON Schema the agent's output must satisfy. Unlike `response_format`
+    /// the kernel enforces it: a violating result is recorded as a stage
+    /// failure carrying the schema errors. See [`super::output_schema`] for
+    /// the supported keywords.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    /// How many times to re-dispatch the agent when its output violates
+    /// `output_schema`, with the validation errors appended to
+    /// `metadata["loop_feedback"]`. Once spent, the violation fails the stage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema_retries: Option<u32>,
     /// State field key for this stage's output (defaults to stage name).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub output_key: Option<OutputKey>,
@@ -52,6 +69,32 @@ pub struct Stage {
     /// Retry policy for transient agent failures.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub retry_policy: Option<RetryPolicy>,
+    /// Name of a concurrency group shared across all sessions. The stage is
+    /// only dispatched while its run holds a permit, returned when its result
+    /// is processed; until then `get_next_instruction` answers
+    /// `WaitConcurrency`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency_group: Option<String>,
+    /// Pause for human review after this stage reports: the kernel raises
+    /// an interrupt carrying the stage output and waits for it to be
+    /// resolved before dispatching whatever comes next.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub checkpoint: bool,
+    /// Dotted paths into the agent context (e.g. `outputs.search`,
+    /// `metadata.locale`) this stage's agent may see. When set, everything
+    /// else under `raw_input`, `outputs`, `state`, and `metadata` is withheld.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible_fields: Option<Vec<String>>,
+    /// Dotted paths withheld from this stage's agent context (e.g.
+    /// `metadata.api_key`). Applied after `visible_fields`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_fields: Vec<String>,
+    /// Named inputs bound to dotted context paths (e.g.
+    /// `"query": "outputs.understand.query"`). Resolved after field masks
+    /// and delivered as `inputs` in the agent context; unresolved paths
+    /// bind `null`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub inputs: HashMap<String, String>,
     /// Agent execution config — transparent to kernel, consumed by worker.
     #[serde(flatten)]
     pub agent_config: AgentConfig,
@@ -74,4 +117,8 @@ pub struct AgentConfig {
     /// Model role (e.g. "fast", "reasoning") — resolved by the LLM provider.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model_role: Option<String>,
+    /// Models to try, in order, when the provider rejects a call on
+    /// `model_role`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub model_fallbacks: Vec<String>,
 }
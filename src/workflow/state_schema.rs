@@ -9,6 +9,10 @@ pub enum MergeStrategy {
     Replace,
     /// Append to an array; creates the array if absent.
     Append,
+    /// `Append`, except that an output identical to the agent's previous
+    /// one (same `OutputProvenance.content_hash`) is stored as
+    /// `{"$same_as": <index>}`, pointing at the entry holding the full copy.
+    AppendDedup,
     /// Shallow-merge object keys.
     MergeDict,
 }
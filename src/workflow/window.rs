@@ -0,0 +1,165 @@
+//! Execution windows: recurring daily time ranges during which a workflow's
+//! agents may be dispatched. Outside every window the kernel answers
+//! `get_next_instruction` with `Instruction::WaitWindow` instead of the next
+//! `RunAgent`.
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveTime, TimeZone, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Error, Result};
+
+/// One recurring window, e.g. weekdays 09:00–17:00 at UTC+1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExecutionWindow {
+    /// ISO weekdays the window opens on (1 = Monday … 7 = Sunday).
+    /// Empty = every day.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<u8>,
+    /// Opening time, `"HH:MM"` local to `utc_offset_minutes`.
+    pub start: String,
+    /// Closing time, `"HH:MM"`. At or before `start` means the window runs
+    /// past midnight into the next day.
+    pub end: String,
+    /// Offset of the window's local time from UTC, in minutes.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+impl ExecutionWindow {
+    pub fn validate(&self) -> Result<()> {
+        parse_time(&self.start)?;
+        parse_time(&self.end)?;
+        if let Some(day) = self.days.iter().find(|d| !(1..=7).contains(*d)) {
+            return Err(Error::validation(format!(
+                "execution window day {} must be 1 (Monday) through 7 (Sunday)",
+                day
+            )));
+        }
+        offset(self.utc_offset_minutes)?;
+        Ok(())
+    }
+
+    /// `Ok(None)` if `now` is inside this window, otherwise its next opening.
+    fn next_open(&self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let tz = offset(self.utc_offset_minutes)?;
+        let start = parse_time(&self.start)?;
+        let end = parse_time(&self.end)?;
+        let today = now.with_timezone(&tz).date_naive();
+
+        let mut next: Option<DateTime<Utc>> = None;
+        // Yesterday covers an overnight window still open this morning.
+        for delta in -1..=7 {
+            let date = today + Duration::days(delta);
+            let weekday = date.weekday().number_from_monday() as u8;
+            if !self.days.is_empty() && !self.days.contains(&weekday) {
+                continue;
+            }
+            let close_date = if end > start { date } else { date + Duration::days(1) };
+            let (Some(open), Some(close)) = (
+                tz.from_local_datetime(&date.and_time(start)).single(),
+                tz.from_local_datetime(&close_date.and_time(end)).single(),
+            ) else {
+                continue;
+            };
+            let (open, close) = (open.with_timezone(&Utc), close.with_timezone(&Utc));
+            if open <= now && now < close {
+                return Ok(None);
+            }
+            if open > now && next.map_or(true, |n| open < n) {
+                next = Some(open);
+            }
+        }
+        Ok(next.or(Some(now + Duration::days(8))))
+    }
+}
+
+/// When the union of `windows` next opens, or `None` if it is open at `now`
+/// (or there are no windows at all).
+pub fn next_open(windows: &[ExecutionWindow], now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+    let mut next: Option<DateTime<Utc>> = None;
+    for window in windows {
+        match window.next_open(now)? {
+            None => return Ok(None),
+            Some(at) => {
+                if next.map_or(true, |n| at < n) {
+                    next = Some(at);
+                }
+            }
+        }
+    }
+    Ok(next)
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|e| {
+        Error::validation(format!("execution window time '{}' must be HH:MM: {}", s, e))
+    })
+}
+
+fn offset(minutes: i32) -> Result<FixedOffset> {
+    FixedOffset::east_opt(minutes.saturating_mul(60)).ok_or_else(|| {
+        Error::validation(format!("execution window utc_offset_minutes {} out of range", minutes))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn business_hours() -> ExecutionWindow {
+        ExecutionWindow {
+            days: vec![1, 2, 3, 4, 5],
+            start: "09:00".into(),
+            end: "17:00".into(),
+            utc_offset_minutes: 60,
+        }
+    }
+
+    #[test]
+    fn open_inside_window() {
+        // Wednesday 10:00 local (09:00 UTC).
+        assert_eq!(next_open(&[business_hours()], at("2026-10-14T09:00:00Z")).unwrap(), None);
+    }
+
+    #[test]
+    fn closed_until_next_weekday_morning() {
+        // Friday 18:00 local → Monday 09:00 local.
+        let next = next_open(&[business_hours()], at("2026-10-16T17:00:00Z")).unwrap();
+        assert_eq!(next, Some(at("2026-10-19T08:00:00Z")));
+    }
+
+    #[test]
+    fn overnight_window_spans_midnight() {
+        let night = ExecutionWindow {
+            days: vec![],
+            start: "22:00".into(),
+            end: "06:00".into(),
+            utc_offset_minutes: 0,
+        };
+        assert_eq!(next_open(std::slice::from_ref(&night), at("2026-10-14T03:00:00Z")).unwrap(), None);
+        assert_eq!(
+            next_open(&[night], at("2026-10-14T12:00:00Z")).unwrap(),
+            Some(at("2026-10-14T22:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn no_windows_means_always_open() {
+        assert_eq!(next_open(&[], Utc::now()).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_windows_rejected() {
+        let mut w = business_hours();
+        w.start = "9am".into();
+        assert!(w.validate().is_err());
+        let mut w = business_hours();
+        w.days = vec![0];
+        assert!(w.validate().is_err());
+    }
+}
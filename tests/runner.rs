@@ -132,6 +132,7 @@ fn make_llm_agent(
         temperature: None,
         max_tokens: None,
         model: None,
+        model_fallbacks: Vec::new(),
         max_tool_rounds: 10,
         content_resolver: None,
         hooks: Vec::new(),
@@ -195,6 +196,43 @@ async fn test_kernel_actor_system_status() {
     cancel.cancel();
 }
 
+#[tokio::test]
+async fn test_kernel_perf_counts_commands() {
+    let kernel = Kernel::new();
+    let cancel = CancellationToken::new();
+    let handle = spawn(kernel, cancel.clone());
+
+    handle.get_system_status().await;
+    handle.get_system_status().await;
+    let perf = handle.get_kernel_perf().await.unwrap();
+    let status = perf.commands.iter().find(|c| c.command == "GetSystemStatus").unwrap();
+    assert_eq!(status.count, 2);
+    assert!(status.max_us >= status.p50_us);
+    assert_eq!(perf.queue_depth, 0);
+    assert_eq!(perf.queue_capacity, 256);
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn test_kernel_observer_reads_live_state() {
+    let kernel = Kernel::new();
+    let cancel = CancellationToken::new();
+    let handle = spawn(kernel, cancel.clone());
+    let observer = handle.observer();
+
+    let run_id = RunId::must("observed");
+    let _ = handle
+        .initialize_session(run_id.clone(), two_stage_pipeline(), Run::new("user1", "sess1", "hi", None), false)
+        .await
+        .expect("init should succeed");
+
+    let state = observer.get_session_state(&run_id).await.expect("observer can read session");
+    assert_eq!(state.current_stage.as_str(), "understand");
+    assert_eq!(observer.get_system_status().await.runs_total, 1);
+    assert!(observer.get_tool_health(None).await.is_ok());
+    cancel.cancel();
+}
+
 #[tokio::test]
 async fn test_kernel_actor_terminate_run() {
     let kernel = Kernel::new();
@@ -213,6 +251,68 @@ async fn test_kernel_actor_terminate_run() {
     cancel.cancel();
 }
 
+#[tokio::test]
+async fn test_start_run_returns_record_and_session() {
+    let kernel = Kernel::new();
+    let cancel = CancellationToken::new();
+    let handle = spawn(kernel, cancel.clone());
+
+    let quota = jeeves_core::kernel::ResourceQuota {
+        max_llm_calls: 7,
+        ..Default::default()
+    };
+    let run_id = RunId::must("start-run");
+    let (record, session) = handle
+        .start_run(run_id.clone(), two_stage_pipeline(), Run::new("user1", "sess1", "hi", None), Some(quota))
+        .await
+        .expect("start_run should succeed");
+
+    assert_eq!(record.run_id, run_id);
+    assert_eq!(record.user_id.as_str(), "user1");
+    assert_eq!(record.quota.max_llm_calls, 7);
+    assert_eq!(session.current_stage.as_str(), "understand");
+
+    let invalid: Workflow = serde_json::from_value(serde_json::json!({
+        "name": "bad", "stages": [], "max_iterations": 1, "max_llm_calls": 1, "max_agent_hops": 1
+    })).unwrap();
+    let bad_id = RunId::must("start-run-bad");
+    assert!(handle.start_run(bad_id, invalid, Run::new("user1", "sess1", "hi", None), None).await.is_err());
+    assert_eq!(handle.get_system_status().await.runs_total, 1);
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn test_wait_for_interrupt_long_polls() {
+    let kernel = Kernel::new();
+    let cancel = CancellationToken::new();
+    let handle = spawn(kernel, cancel.clone());
+
+    let run_id = RunId::must("long-poll");
+    let _ = handle
+        .initialize_session(run_id.clone(), two_stage_pipeline(), Run::new("user1", "sess1", "hi", None), false)
+        .await
+        .expect("init should succeed");
+
+    let none = handle.wait_for_interrupt(&run_id, Some(std::time::Duration::from_millis(20))).await.unwrap();
+    assert!(none.is_none());
+
+    let pending = {
+        let observer = handle.observer();
+        let run_id = run_id.clone();
+        tokio::spawn(async move { observer.wait_for_interrupt(&run_id, None).await })
+    };
+    tokio::task::yield_now().await;
+    let interrupt = jeeves_core::run::FlowInterrupt::new().with_question("Proceed?".into());
+    handle.set_run_interrupt(&run_id, interrupt.clone()).await.unwrap();
+    let woken = pending.await.unwrap().unwrap().expect("waiter should see the interrupt");
+    assert_eq!(woken.id, interrupt.id);
+
+    // Already pending: returns at once.
+    let now = handle.wait_for_interrupt(&run_id, None).await.unwrap();
+    assert_eq!(now.map(|i| i.id), Some(interrupt.id));
+    cancel.cancel();
+}
+
 #[tokio::test]
 async fn test_pipeline_with_three_stages() {
     let kernel = Kernel::new();
@@ -971,6 +1071,7 @@ async fn test_confirmation_gate_mcp_agent_buffered() {
         outputs: HashMap::new(),
         state: HashMap::new(),
         metadata: HashMap::new(),
+        inputs: HashMap::new(),
         event_tx: None,
         stage_name: Some("execute".into()),
         workflow_name: Arc::from("test"),
@@ -1022,6 +1123,7 @@ async fn test_confirmation_gate_safe_tool_no_interrupt() {
         outputs: HashMap::new(),
         state: HashMap::new(),
         metadata: HashMap::new(),
+        inputs: HashMap::new(),
         event_tx: None,
         stage_name: Some("execute".into()),
         workflow_name: Arc::from("test"),
@@ -1089,7 +1191,7 @@ async fn test_confirmation_gate_full_pipeline_buffered() {
     assert!(matches!(instr, jeeves_core::kernel::protocol::Instruction::WaitInterrupt { .. }));
 
     // Resolve the interrupt
-    let interrupt_id = if let jeeves_core::kernel::protocol::Instruction::WaitInterrupt { ref interrupt } = instr {
+    let interrupt_id = if let jeeves_core::kernel::protocol::Instruction::WaitInterrupt { ref interrupt, .. } = instr {
         interrupt.as_ref().unwrap().id.clone()
     } else {
         panic!("Expected WaitInterrupt");
@@ -1099,6 +1201,7 @@ async fn test_confirmation_gate_full_pipeline_buffered() {
         approved: Some(true),
         decision: None,
         data: None,
+        responder: None,
         received_at: chrono::Utc::now(),
     }).await.unwrap();
 